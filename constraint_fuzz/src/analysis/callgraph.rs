@@ -1,13 +1,14 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     path::{Path, PathBuf},
 };
 
 use crate::{config::get_library_name, deopt::Deopt};
 use eyre::Context;
 use once_cell::sync::OnceCell;
-use petgraph::{graph::NodeIndex, Directed, Graph};
+use petgraph::{graph::NodeIndex, visit::EdgeRef, Directed, Graph};
 use regex::Regex;
+use serde::Serialize;
 
 struct DotNode {
     id: u64,
@@ -61,8 +62,30 @@ fn dot_parser(dot_file: &Path) -> eyre::Result<(Vec<DotNode>, Vec<DotEdge>)> {
     Ok((nodes, edges))
 }
 
+/// Provenance of a call edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeSource {
+    /// from the call graph of the library build
+    Static,
+    /// observed in the execution traces only
+    Dynamic,
+    Both,
+}
+
+impl EdgeSource {
+    fn merge(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            EdgeSource::Both
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct CallGraph {
-    graph: Graph<String, u8, Directed>,
+    graph: Graph<String, EdgeSource, Directed>,
     node_map: HashMap<String, NodeIndex>,
 }
 
@@ -95,10 +118,112 @@ impl CallGraph {
             let src_node = self.node_map.get(&src.label).unwrap();
             let dst_node = self.node_map.get(&dst.label).unwrap();
 
-            self.graph.add_edge(*src_node, *dst_node, 0);
+            self.graph
+                .add_edge(*src_node, *dst_node, EdgeSource::Static);
         }
     }
 
+    /// build the graph from (caller, callee) pairs
+    pub fn from_edges<'a, I>(edges: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut call_graph = Self::new();
+        for (caller, callee) in edges {
+            let src_node = call_graph.get_or_add_node(caller);
+            let dst_node = call_graph.get_or_add_node(callee);
+            call_graph
+                .graph
+                .update_edge(src_node, dst_node, EdgeSource::Static);
+        }
+        call_graph
+    }
+
+    /// Merge (caller, callee) pairs observed in the executions, edges already in the graph are
+    /// marked as both.
+    pub fn merge_dynamic_edges<'a, I>(&mut self, edges: I)
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        for (caller, callee) in edges {
+            let src_node = self.get_or_add_node(caller);
+            let dst_node = self.get_or_add_node(callee);
+            let source = match self.graph.find_edge(src_node, dst_node) {
+                Some(edge) => self.graph[edge].merge(EdgeSource::Dynamic),
+                None => EdgeSource::Dynamic,
+            };
+            self.graph.update_edge(src_node, dst_node, source);
+        }
+    }
+
+    /// provenance of the call edge, None if there is no such edge
+    pub fn get_edge_source(&self, caller: &str, callee: &str) -> Option<EdgeSource> {
+        let src_node = self.node_map.get(caller)?;
+        let dst_node = self.node_map.get(callee)?;
+        let edge = self.graph.find_edge(*src_node, *dst_node)?;
+        Some(self.graph[edge])
+    }
+
+    /// Iterated element: (caller, callee, provenance)
+    pub fn iter_edges(&self) -> impl Iterator<Item = (&str, &str, EdgeSource)> {
+        self.graph.edge_references().map(|edge| {
+            (
+                self.graph[edge.source()].as_str(),
+                self.graph[edge.target()].as_str(),
+                *edge.weight(),
+            )
+        })
+    }
+
+    fn get_or_add_node(&mut self, func: &str) -> NodeIndex {
+        if let Some(idx) = self.node_map.get(func) {
+            return *idx;
+        }
+        let idx = self.graph.add_node(func.to_owned());
+        self.node_map.insert(func.to_owned(), idx);
+        idx
+    }
+
+    pub fn contains_func(&self, func: &str) -> bool {
+        self.node_map.contains_key(func)
+    }
+
+    /// callers of `func`, empty if `func` is not in the graph
+    pub fn get_direct_callers(&self, func: &str) -> Vec<&str> {
+        let node = match self.node_map.get(func) {
+            Some(node) => *node,
+            None => return vec![],
+        };
+        self.graph
+            .neighbors_directed(node, petgraph::Direction::Incoming)
+            .map(|neighbor| self.graph.node_weight(neighbor).unwrap().as_str())
+            .collect()
+    }
+
+    /// Functions reachable from `func` with their shortest call distances, `func` itself included.
+    pub fn get_reachable_funcs(&self, func: &str) -> HashMap<&str, usize> {
+        let mut dists: HashMap<&str, usize> = HashMap::new();
+        let start = match self.node_map.get(func) {
+            Some(node) => *node,
+            None => return dists,
+        };
+        let mut queue = VecDeque::from([(start, 0)]);
+        while let Some((node, dist)) = queue.pop_front() {
+            let name = self.graph.node_weight(node).unwrap().as_str();
+            if dists.contains_key(name) {
+                continue;
+            }
+            dists.insert(name, dist);
+            for neighbor in self
+                .graph
+                .neighbors_directed(node, petgraph::Direction::Outgoing)
+            {
+                queue.push_back((neighbor, dist + 1));
+            }
+        }
+        dists
+    }
+
     pub fn get_direct_callees(&self, func: &str) -> Vec<&str> {
         let node = self
             .node_map
@@ -146,17 +271,23 @@ impl CallGraph {
     }
 }
 
+/// path of the call graph dumped when building the library
+pub fn get_lib_call_graph_path(deopt: &Deopt) -> eyre::Result<PathBuf> {
+    let dot_path: PathBuf = [
+        deopt.get_library_build_dir()?,
+        "work".into(),
+        "callgraph.dot".into(),
+    ]
+    .iter()
+    .collect();
+    Ok(dot_path)
+}
+
 pub fn get_lib_call_graph() -> &'static CallGraph {
     static GRAPH: OnceCell<CallGraph> = OnceCell::new();
     GRAPH.get_or_init(|| {
         let deopt = Deopt::new(get_library_name()).unwrap();
-        let dot_path: PathBuf = [
-            deopt.get_library_build_dir().unwrap(),
-            "work".into(),
-            "callgraph.dot".into(),
-        ]
-        .iter()
-        .collect();
+        let dot_path = get_lib_call_graph_path(&deopt).unwrap();
         let (nodes, edges) = dot_parser(&dot_path).unwrap();
         let mut call_graph = CallGraph::new();
         call_graph.construct(nodes, edges);
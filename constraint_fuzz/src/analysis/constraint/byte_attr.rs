@@ -0,0 +1,278 @@
+//! Attribution of input bytes to branches by differential re-execution: byte ranges of a case are
+//! mutated one at a time, and the branches flipping their directions are influenced by them.
+//! This is a lightweight taint without a DFSan build.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs, ops,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    analysis::constraint::inter::{
+        exec_tree::{action::ExecAction, ExecForest},
+        loc::SrcLoc,
+    },
+    execution::Executor,
+    feedback::branches::constraints::UBConstraint,
+};
+
+/// limit of mutated executions of a case
+const DEFAULT_MAX_EXECS: usize = 256;
+
+/// number of times each direction is taken at a branch, keyed by the condition location
+pub type BrDirs = HashMap<SrcLoc, [usize; 2]>;
+
+pub fn collect_br_dirs(forest: &ExecForest) -> BrDirs {
+    let mut br_dirs: BrDirs = HashMap::new();
+    for func_node_ptr in forest.func_node_bfs_iter() {
+        let func_node = func_node_ptr.borrow();
+        for act in func_node.iter_acts() {
+            if let ExecAction::Intra(jump_act) = act {
+                let loc = jump_act
+                    .get_val_loc()
+                    .unwrap_or(jump_act.get_from_loc())
+                    .clone();
+                br_dirs.entry(loc).or_default()[jump_act.get_cond_val() as usize] += 1;
+            }
+        }
+    }
+    br_dirs
+}
+
+/// Branches reached in both executions with different direction counts, so that a loop
+/// condition exiting at another iteration is flipped as well.
+pub fn find_flipped_brs<'a>(base: &'a BrDirs, mutated: &BrDirs) -> Vec<&'a SrcLoc> {
+    base.iter()
+        .filter(|(loc, dirs)| mutated.get(*loc).is_some_and(|other| other != *dirs))
+        .map(|(loc, _)| loc)
+        .collect()
+}
+
+/// Split the input into at most `max_execs` ranges of the same size.
+pub fn split_ranges(len: usize, max_execs: usize) -> Vec<ops::Range<usize>> {
+    let chunk_size = len.div_ceil(max_execs.max(1)).max(1);
+    (0..len)
+        .step_by(chunk_size)
+        .map(|start| start..(start + chunk_size).min(len))
+        .collect()
+}
+
+/// flip the bits of the bytes in the range
+pub fn mutate_range(bytes: &[u8], range: ops::Range<usize>) -> Vec<u8> {
+    let mut mutated = bytes.to_vec();
+    for byte in mutated[range].iter_mut() {
+        *byte ^= 0xff;
+    }
+    mutated
+}
+
+/// Input bytes of a constraint: bytes influencing the branches inside its condition.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsBytes {
+    pub cons: UBConstraint,
+    pub offsets: BTreeSet<usize>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ByteAttribution {
+    case_path: PathBuf,
+    case_size: usize,
+    /// number of bytes mutated together
+    chunk_size: usize,
+    /// mutated executions without traces
+    failed_execs: usize,
+    /// branch location -> offsets of the input bytes influencing it
+    branches: BTreeMap<String, BTreeSet<usize>>,
+}
+
+impl ByteAttribution {
+    pub fn get_case_path(&self) -> &Path {
+        &self.case_path
+    }
+
+    pub fn get_failed_execs(&self) -> usize {
+        self.failed_execs
+    }
+
+    pub fn get_branches(&self) -> &BTreeMap<String, BTreeSet<usize>> {
+        &self.branches
+    }
+
+    pub fn get_offsets(&self, loc: &SrcLoc) -> Option<&BTreeSet<usize>> {
+        self.branches.get(&loc.to_string())
+    }
+
+    fn record(&mut self, range: ops::Range<usize>, flipped: &[&SrcLoc]) {
+        for loc in flipped {
+            self.branches
+                .entry(loc.to_string())
+                .or_default()
+                .extend(range.clone());
+        }
+    }
+
+    /// Attach the input bytes to the constraints, constraints without any are left out.
+    pub fn attach_to_cons(&self, cons_list: &[UBConstraint]) -> Vec<ConsBytes> {
+        let branch_locs: Vec<(SrcLoc, &BTreeSet<usize>)> = self
+            .branches
+            .iter()
+            .filter_map(|(loc, offsets)| Some((SrcLoc::from_str(loc).ok()?, offsets)))
+            .collect();
+        let mut cons_bytes_list = vec![];
+        for cons in cons_list {
+            let mut offsets = BTreeSet::new();
+            for (loc, br_offsets) in branch_locs.iter() {
+                if cons.is_hit_loc(loc).unwrap_or(false) {
+                    offsets.extend(br_offsets.iter().copied());
+                }
+            }
+            if !offsets.is_empty() {
+                cons_bytes_list.push(ConsBytes {
+                    cons: cons.clone(),
+                    offsets,
+                });
+            }
+        }
+        cons_bytes_list
+    }
+}
+
+/// Re-execute a case with the instrumented coverage fuzzer for each mutated byte range. Only the
+/// guard files are read, the coverage is not exported.
+pub struct ByteAttributor<'a> {
+    executor: &'a Executor,
+    fuzzer_binary: PathBuf,
+    max_execs: usize,
+}
+
+impl<'a> ByteAttributor<'a> {
+    pub fn new(executor: &'a Executor, fuzzer_binary: &Path) -> Self {
+        Self {
+            executor,
+            fuzzer_binary: fuzzer_binary.to_path_buf(),
+            max_execs: DEFAULT_MAX_EXECS,
+        }
+    }
+
+    pub fn with_max_execs(mut self, max_execs: usize) -> Self {
+        self.max_execs = max_execs;
+        self
+    }
+
+    /// Execute the case in `run_dir` and collect its branch directions.
+    /// Crashing executions still leave partial traces.
+    fn run_case(&self, case: &[u8], run_dir: &Path) -> Result<BrDirs> {
+        let guard_dir = run_dir.join("guards");
+        fs::create_dir_all(&guard_dir)?;
+        let case_path = run_dir.join("case");
+        fs::write(&case_path, case)?;
+        let err_op = self.executor.execute_cov_fuzzer(
+            &self.fuzzer_binary,
+            &case_path,
+            &run_dir.join("case.profraw"),
+            &guard_dir,
+            &run_dir.join("case.json"),
+        )?;
+        if let Some(err) = &err_op {
+            log::debug!("Mutated case failed in {:?}: {}", run_dir, err);
+        }
+        // guard files of crashing or timed-out executions may be damaged
+        let forest =
+            ExecForest::from_guard_dir_with_constraint(&guard_dir, None, err_op.is_some())?;
+        Ok(collect_br_dirs(&forest))
+    }
+
+    pub fn attribute(&self, case_path: &Path) -> Result<ByteAttribution> {
+        let case = fs::read(case_path)?;
+        let ranges = split_ranges(case.len(), self.max_execs);
+        let mut attr = ByteAttribution {
+            case_path: case_path.to_path_buf(),
+            case_size: case.len(),
+            chunk_size: ranges.first().map_or(0, |range| range.len()),
+            ..Default::default()
+        };
+
+        let tmp_dir = tempfile::tempdir()?;
+        let base = self.run_case(&case, &tmp_dir.path().join("base"))?;
+        for (idx, range) in ranges.into_iter().enumerate() {
+            let run_dir = tmp_dir.path().join(idx.to_string());
+            let mutated = mutate_range(&case, range.clone());
+            match self.run_case(&mutated, &run_dir) {
+                Ok(br_dirs) => attr.record(range, &find_flipped_brs(&base, &br_dirs)),
+                Err(e) => {
+                    log::warn!("Failed to trace case mutated at {:?}: {}", range, e);
+                    attr.failed_execs += 1;
+                }
+            }
+            // traces may be large
+            fs::remove_dir_all(&run_dir)?;
+        }
+        log::info!(
+            "{} branches influenced by the input bytes of {:?}",
+            attr.branches.len(),
+            case_path
+        );
+        Ok(attr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{build_guard_forest, ub_cons};
+
+    /// guards of the conditions at the given lines, with their values
+    fn get_br_dirs(guard_dir: &Path, brs: &[(usize, usize)]) -> Result<BrDirs> {
+        let mut lines = vec!["enter main".to_owned()];
+        lines.extend(brs.iter().map(|(line, val)| {
+            format!(
+                "Br Guard: /src/a.c:{line}:9 /src/a.c:{line}:5 {val} /src/a.c:{}:3",
+                line + 1
+            )
+        }));
+        lines.push("return from main".to_owned());
+        Ok(collect_br_dirs(&build_guard_forest(guard_dir, &lines)?))
+    }
+
+    #[test]
+    fn test_byte_attribution() -> Result<()> {
+        assert_eq!(split_ranges(10, 4), [0..3, 3..6, 6..9, 9..10]);
+        assert_eq!(split_ranges(3, 256), [0..1, 1..2, 2..3]);
+        assert_eq!(mutate_range(&[0x0f, 0x00], 1..2), [0x0f, 0xff]);
+
+        let work_dir = tempfile::tempdir()?;
+        let base = get_br_dirs(&work_dir.path().join("base"), &[(5, 1), (9, 0)])?;
+        let mutated = get_br_dirs(&work_dir.path().join("mutated"), &[(5, 1), (9, 1)])?;
+        let flipped = find_flipped_brs(&base, &mutated);
+        assert_eq!(flipped.len(), 1);
+        assert_eq!(flipped[0].get_line(), Some(9));
+
+        // the loop exits one iteration earlier, both directions are taken in both executions
+        let loop_base = get_br_dirs(
+            &work_dir.path().join("loop_base"),
+            &[(5, 1), (5, 1), (5, 0)],
+        )?;
+        let loop_mutated = get_br_dirs(&work_dir.path().join("loop_mutated"), &[(5, 1), (5, 0)])?;
+        let flipped_loop = find_flipped_brs(&loop_base, &loop_mutated);
+        assert_eq!(flipped_loop.len(), 1);
+        assert_eq!(flipped_loop[0].get_line(), Some(5));
+
+        let mut attr = ByteAttribution::default();
+        attr.record(3..6, &flipped);
+        let cons = ub_cons(
+            "len > 4",
+            true,
+            "/src/a.c",
+            [9, 9, 9, 16],
+            "int main(int len)",
+        )?;
+        let cons_bytes = attr.attach_to_cons(&[cons]);
+        assert_eq!(cons_bytes.len(), 1);
+        assert_eq!(cons_bytes[0].offsets, BTreeSet::from([3, 4, 5]));
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+//! Calling-context-sensitive branch coverage. Branch directions are keyed by the call chain suffix
+//! of the branch, so a direction covered via one caller no longer hides that it is unselected via
+//! another.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::analysis::constraint::{
+    exec_rec::ExecRec,
+    inter::{
+        exec_tree::{
+            action::ExecAction,
+            thread_tree::{FuncIter, SharedFuncNodePtr},
+            ExecForest,
+        },
+        loc::{SrcLoc, SrcTextCache},
+    },
+};
+
+/// number of callers kept in a context by default
+pub const DEFAULT_CTX_DEPTH: usize = 2;
+
+/// function names from the outermost kept caller to the function of the branch
+pub type CallCtx = Vec<String>;
+
+/// directions taken at a branch, indexed by the condition value
+type CtxDirs = [bool; 2];
+
+pub struct CtxBrCov {
+    /// number of callers kept in a context
+    depth: usize,
+    /// value location of a regular branch -> directions taken under each context
+    brs: HashMap<SrcLoc, BTreeMap<CallCtx, CtxDirs>>,
+}
+
+impl CtxBrCov {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            brs: HashMap::new(),
+        }
+    }
+
+    /// Merge the branch coverage of all executions of an experiment.
+    /// Executions whose forests fail to build are skipped.
+    pub fn from_expe_dir(expe_dir: &Path, depth: usize) -> Result<Self> {
+        let mut ctx_cov = Self::new(depth);
+        for exec in ExecRec::get_exec_list_from_expe_dir(expe_dir)? {
+            match exec.get_exec_forest() {
+                Ok(forest) => ctx_cov.add_forest(forest),
+                Err(e) => log::warn!("Failed to build execution forest of {}: {}", exec, e),
+            }
+        }
+        Ok(ctx_cov)
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn get_ctx_dirs(&self, val_loc: &SrcLoc) -> Option<&BTreeMap<CallCtx, [bool; 2]>> {
+        self.brs.get(val_loc)
+    }
+
+    fn get_ctx(&self, chain: &[String]) -> CallCtx {
+        let start = chain.len().saturating_sub(self.depth + 1);
+        chain[start..].to_vec()
+    }
+
+    /// Merge the directions of the regular branches in a forest. Init nodes are omitted and
+    /// spawned threads are followed from their creation sites.
+    pub fn add_forest(&mut self, forest: &ExecForest) {
+        let mut chain: Vec<String> = vec![];
+        let mut stack: Vec<(SharedFuncNodePtr, usize)> = forest
+            .get_top_root_ptrs()
+            .into_iter()
+            .map(|ptr| (ptr, 0))
+            .collect();
+
+        while let Some((node_ptr, depth)) = stack.pop() {
+            let node = node_ptr.borrow();
+            chain.truncate(depth);
+            let sub_depth = match node.get_func_name() {
+                Some(name) => {
+                    chain.push(name.to_owned());
+                    depth + 1
+                }
+                None => depth,
+            };
+            let ctx = self.get_ctx(&chain);
+            for act in node.iter_acts() {
+                let jump_act = match act {
+                    ExecAction::Intra(jump_act) => jump_act,
+                    _ => continue,
+                };
+                if let Some(val_loc) = jump_act.get_val_loc() {
+                    let ctx_dirs = self.brs.entry(val_loc.clone()).or_default();
+                    ctx_dirs.entry(ctx.clone()).or_default()[jump_act.get_cond_val() as usize] =
+                        true;
+                }
+            }
+            for child_ptr in node_ptr.iter_sub_funcs_cross_thread() {
+                stack.push((child_ptr, sub_depth));
+            }
+        }
+    }
+
+    /// Directions covered under some contexts of a branch but unselected under others.
+    pub fn find_ctx_gaps(&self) -> Vec<CtxGap> {
+        let mut gaps = vec![];
+        let mut text_cache = SrcTextCache::default();
+        for (val_loc, ctx_dirs) in self.brs.iter() {
+            for dir in [true, false] {
+                let (covered, unselected): (Vec<_>, Vec<_>) =
+                    ctx_dirs.iter().partition(|(_, dirs)| dirs[dir as usize]);
+                if covered.is_empty() {
+                    continue;
+                }
+                let covered_ctxs: Vec<CallCtx> =
+                    covered.into_iter().map(|(ctx, _)| ctx.clone()).collect();
+                for (ctx, _) in unselected {
+                    gaps.push(CtxGap::new(
+                        val_loc,
+                        ctx,
+                        dir,
+                        &covered_ctxs,
+                        &mut text_cache,
+                    ));
+                }
+            }
+        }
+        gaps.sort_by(|a, b| {
+            (&a.fpath, a.loc, &a.ctx, a.res).cmp(&(&b.fpath, b.loc, &b.ctx, b.res))
+        });
+        gaps
+    }
+
+    /// Find the context-specific gaps and write them into `out_path` as JSON.
+    pub fn write_ctx_gaps<P: AsRef<Path>>(&self, out_path: P) -> Result<Vec<CtxGap>> {
+        let gaps = self.find_ctx_gaps();
+        let writer = BufWriter::new(File::create(out_path.as_ref())?);
+        serde_json::to_writer_pretty(writer, &gaps)?;
+        log::info!(
+            "{} context-specific gaps of {} branches written to {}",
+            gaps.len(),
+            self.brs.len(),
+            out_path.as_ref().display()
+        );
+        Ok(gaps)
+    }
+}
+
+/// Context-specific counterpart of `UBConstraint`: the `res` direction of the condition is
+/// unselected under `ctx`, while it is covered under `covered_ctxs`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CtxGap {
+    pub fpath: PathBuf,
+    /// value location of the branch condition
+    pub loc: [usize; 2],
+    /// source text of the branch condition
+    pub cond_text: Option<String>,
+    /// the unselected direction
+    pub res: bool,
+    pub ctx: CallCtx,
+    pub covered_ctxs: Vec<CallCtx>,
+}
+
+impl CtxGap {
+    fn new(
+        val_loc: &SrcLoc,
+        ctx: &CallCtx,
+        unselected: bool,
+        covered_ctxs: &[CallCtx],
+        text_cache: &mut SrcTextCache,
+    ) -> Self {
+        let cond_text = match text_cache.get_line_text(val_loc) {
+            Ok(text) => Some(text),
+            Err(e) => {
+                log::debug!("Failed to get source text of {}: {}", val_loc, e);
+                None
+            }
+        };
+        Self {
+            fpath: val_loc
+                .get_src_path()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            loc: [
+                val_loc.get_line().unwrap_or(0),
+                val_loc.get_col().unwrap_or(0),
+            ],
+            cond_text,
+            res: unselected,
+            ctx: ctx.clone(),
+            covered_ctxs: covered_ctxs.to_vec(),
+        }
+    }
+
+    /// contexts in which the unselected direction is covered
+    pub fn get_covered_ctxs(&self) -> BTreeSet<String> {
+        self.covered_ctxs
+            .iter()
+            .map(|ctx| ctx.join(" -> "))
+            .collect()
+    }
+}
+
+impl fmt::Display for CtxGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "branch at {}:{}:{}",
+            self.fpath.display(),
+            self.loc[0],
+            self.loc[1]
+        )?;
+        if let Some(text) = &self.cond_text {
+            write!(f, " ({})", text)?;
+        }
+        write!(
+            f,
+            " always {} under {}, {} arm covered under {} other contexts",
+            !self.res,
+            self.ctx.join(" -> "),
+            self.res,
+            self.covered_ctxs.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{add_expe_exec, cov_export};
+
+    #[test]
+    fn test_ctx_gaps() -> Result<()> {
+        let expe_dir = tempfile::tempdir()?;
+        let lines = [
+            "enter main",
+            "enter decode_a",
+            "enter read_size",
+            "Br Guard: /src/a.c:5:11 /src/a.c:5:5 1 /src/a.c:6:7",
+            "return from read_size",
+            "return from decode_a",
+            "enter decode_b",
+            "enter read_size",
+            "Br Guard: /src/a.c:5:11 /src/a.c:5:5 0 /src/a.c:8:3",
+            "return from read_size",
+            "enter read_size",
+            "Br Guard: /src/a.c:5:11 /src/a.c:5:5 0 /src/a.c:8:3",
+            "return from read_size",
+            "return from decode_b",
+            "return from main",
+        ];
+        add_expe_exec(expe_dir.path(), "1", &cov_export(json!([])), &lines)?;
+
+        // the callers are dropped without context
+        let ctx_cov = CtxBrCov::from_expe_dir(expe_dir.path(), 0)?;
+        assert!(ctx_cov.find_ctx_gaps().is_empty());
+
+        let ctx_cov = CtxBrCov::from_expe_dir(expe_dir.path(), 1)?;
+        let val_loc = SrcLoc::from_str("/src/a.c:5:11")?;
+        assert_eq!(ctx_cov.get_ctx_dirs(&val_loc).unwrap().len(), 2);
+        let gaps = ctx_cov.find_ctx_gaps();
+        assert_eq!(gaps.len(), 2);
+        // the true arm is unselected under decode_b
+        let gap = gaps.iter().find(|gap| gap.res).unwrap();
+        assert_eq!(gap.ctx, ["decode_b", "read_size"]);
+        assert_eq!(
+            gap.get_covered_ctxs(),
+            BTreeSet::from(["decode_a -> read_size".to_owned()])
+        );
+        assert_eq!(gap.loc, [5, 11]);
+        assert!(gap.to_string().ends_with(
+            "always false under decode_b -> read_size, true arm covered under 1 other contexts"
+        ));
+        Ok(())
+    }
+}
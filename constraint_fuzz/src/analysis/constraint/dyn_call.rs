@@ -0,0 +1,244 @@
+//! Dynamic call graph extracted from the execution traces. Calls through function pointers are
+//! missing in the static call graph of the library, while the traces record the invocation
+//! location and the target of every call.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::BufWriter,
+    path::Path,
+};
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::analysis::{
+    callgraph::{CallGraph, EdgeSource},
+    constraint::{
+        exec_rec::ExecRec,
+        inter::exec_tree::{action::ExecAction, ExecForest},
+    },
+};
+
+/// target -> number of calls
+pub type CallTargets = BTreeMap<String, usize>;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CallSite {
+    caller: String,
+    targets: CallTargets,
+}
+
+impl CallSite {
+    pub fn get_caller(&self) -> &str {
+        &self.caller
+    }
+
+    pub fn get_targets(&self) -> &CallTargets {
+        &self.targets
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DynCallGraph {
+    /// (caller, callee) -> number of calls
+    edges: HashMap<(String, String), usize>,
+    /// invocation location -> call site
+    call_sites: BTreeMap<String, CallSite>,
+    /// location of an indirect branch -> destination locations with their counts
+    indirect_brs: BTreeMap<String, CallTargets>,
+}
+
+impl DynCallGraph {
+    /// Merge the calls of all executions of an experiment.
+    /// Executions whose forests fail to build are skipped.
+    pub fn from_expe_dir(expe_dir: &Path) -> Result<Self> {
+        let mut dyn_graph = Self::default();
+        for exec in ExecRec::get_exec_list_from_expe_dir(expe_dir)? {
+            match exec.get_exec_forest() {
+                Ok(forest) => dyn_graph.add_forest(forest),
+                Err(e) => log::warn!("Failed to build execution forest of {}: {}", exec, e),
+            }
+        }
+        Ok(dyn_graph)
+    }
+
+    /// Merge the calls of a forest, calls from init nodes have no caller and are omitted.
+    pub fn add_forest(&mut self, forest: &ExecForest) {
+        for func_node_ptr in forest.func_node_bfs_iter() {
+            let func_node = func_node_ptr.borrow();
+            let caller = match func_node.get_func_name() {
+                Some(name) => name,
+                None => continue,
+            };
+            for act in func_node.iter_acts() {
+                match act {
+                    ExecAction::Func(func_act) if func_act.is_call() => {
+                        let callee = func_act.get_name();
+                        *self
+                            .edges
+                            .entry((caller.to_owned(), callee.to_owned()))
+                            .or_insert(0) += 1;
+                        if let Some(invoc_loc) = func_act.get_invoc_loc() {
+                            let site = self.call_sites.entry(invoc_loc.to_string()).or_default();
+                            site.caller = caller.to_owned();
+                            *site.targets.entry(callee.to_owned()).or_insert(0) += 1;
+                        }
+                    }
+                    ExecAction::Intra(jump_act) if jump_act.is_indirect_guard() => {
+                        let dests = self
+                            .indirect_brs
+                            .entry(jump_act.get_from_loc().to_string())
+                            .or_default();
+                        *dests
+                            .entry(jump_act.get_dest_loc().to_string())
+                            .or_insert(0) += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Iterated element: (caller, callee)
+    pub fn iter_edges(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.edges
+            .keys()
+            .map(|(caller, callee)| (caller.as_str(), callee.as_str()))
+    }
+
+    pub fn get_call_count(&self, caller: &str, callee: &str) -> usize {
+        self.edges
+            .get(&(caller.to_owned(), callee.to_owned()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn get_call_sites(&self) -> &BTreeMap<String, CallSite> {
+        &self.call_sites
+    }
+
+    /// Call sites with more than one target, or with targets missing in the static call graph,
+    /// which can only be calls through function pointers.
+    pub fn find_indirect_sites(&self, static_graph: &CallGraph) -> BTreeMap<&str, &CallSite> {
+        self.call_sites
+            .iter()
+            .filter(|(_, site)| {
+                site.targets.len() > 1
+                    || site
+                        .targets
+                        .keys()
+                        .any(|target| static_graph.get_edge_source(&site.caller, target).is_none())
+            })
+            .map(|(loc, site)| (loc.as_str(), site))
+            .collect()
+    }
+
+    /// Merge the dynamic edges into a copy of the static call graph and report the provenance of
+    /// the edges and the indirect call sites.
+    pub fn merge_with(&self, static_graph: &CallGraph) -> (CallGraph, DynCallReport) {
+        let mut merged = static_graph.clone();
+        merged.merge_dynamic_edges(self.iter_edges());
+
+        let mut report = DynCallReport::default();
+        for (_, _, source) in merged.iter_edges() {
+            *report.edge_sources.entry(source).or_insert(0) += 1;
+        }
+        report.indirect_sites = self
+            .find_indirect_sites(static_graph)
+            .into_iter()
+            .map(|(loc, site)| (loc.to_owned(), site.clone()))
+            .collect();
+        report.indirect_brs = self.indirect_brs.clone();
+        (merged, report)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DynCallReport {
+    /// number of call edges of each provenance in the merged graph
+    edge_sources: BTreeMap<EdgeSource, usize>,
+    /// invocation location -> call site through a function pointer
+    indirect_sites: BTreeMap<String, CallSite>,
+    /// location of an indirect branch -> observed destinations
+    indirect_brs: BTreeMap<String, CallTargets>,
+}
+
+impl DynCallReport {
+    pub fn get_edge_count(&self, source: EdgeSource) -> usize {
+        self.edge_sources.get(&source).copied().unwrap_or(0)
+    }
+
+    pub fn get_indirect_sites(&self) -> &BTreeMap<String, CallSite> {
+        &self.indirect_sites
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, out_path: P) -> Result<()> {
+        let writer = BufWriter::new(File::create(out_path.as_ref())?);
+        serde_json::to_writer_pretty(writer, self)?;
+        log::info!(
+            "{} indirect call sites written to {}",
+            self.indirect_sites.len(),
+            out_path.as_ref().display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{add_expe_exec, cov_export};
+
+    #[test]
+    fn test_dyn_call_graph() -> Result<()> {
+        let expe_dir = tempfile::tempdir()?;
+        let lines = [
+            "enter main",
+            "Function Invocation: /src/a.c:10:5 enter decode(int)",
+            "Function Invocation: /src/a.c:20:3 enter read_a(int)",
+            "return from read_a",
+            "Function Invocation: /src/a.c:20:3 enter read_b(int)",
+            "return from read_b",
+            "return from decode",
+            "return from main",
+        ];
+        add_expe_exec(expe_dir.path(), "1", &cov_export(json!([])), &lines)?;
+        // an execution with a damaged guard file is skipped
+        add_expe_exec(
+            expe_dir.path(),
+            "2",
+            &cov_export(json!([])),
+            &["enter main", "garbage"],
+        )?;
+
+        let dyn_graph = DynCallGraph::from_expe_dir(expe_dir.path())?;
+        assert_eq!(dyn_graph.get_call_count("decode", "read_b"), 1);
+        assert_eq!(dyn_graph.get_call_sites().len(), 2);
+
+        let static_graph = CallGraph::from_edges([("main", "decode"), ("main", "init")]);
+        let (merged, report) = dyn_graph.merge_with(&static_graph);
+        assert_eq!(
+            merged.get_edge_source("main", "decode"),
+            Some(EdgeSource::Both)
+        );
+        assert_eq!(
+            merged.get_edge_source("main", "init"),
+            Some(EdgeSource::Static)
+        );
+        assert_eq!(
+            merged.get_edge_source("decode", "read_a"),
+            Some(EdgeSource::Dynamic)
+        );
+        assert_eq!(report.get_edge_count(EdgeSource::Dynamic), 2);
+
+        let sites = report.get_indirect_sites();
+        assert_eq!(sites.len(), 1);
+        let site = &sites["/src/a.c:20:3"];
+        assert_eq!(site.get_caller(), "decode");
+        assert_eq!(site.get_targets().len(), 2);
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use color_eyre::eyre::Result;
 use eyre::bail;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
@@ -9,6 +11,9 @@ This file implements mapping between case path and record names
 use std::sync::LazyLock;
 use std::sync::RwLock;
 
+use crate::deopt::utils::{buffer_read_to_bytes, write_bytes_to_file};
+use crate::execution::logger::ProgramError;
+
 pub type ExecName = String;
 
 // Exec Record name -> case path
@@ -44,3 +49,125 @@ pub fn get_case_path_from_exec_name(rec_name: &ExecName) -> Result<PathBuf> {
         rec_name
     )
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseExit {
+    Normal,
+    Timeout,
+    /// crashed or exited abnormally
+    Failed,
+}
+
+impl CaseExit {
+    pub fn from_exec_err(err_op: Option<&ProgramError>) -> Self {
+        match err_op {
+            None => CaseExit::Normal,
+            Some(ProgramError::Timeout(_)) => CaseExit::Timeout,
+            Some(_) => CaseExit::Failed,
+        }
+    }
+}
+
+/// Case executed by the coverage pool, identified by its execution name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseEntry {
+    pub case_path: PathBuf,
+    /// md5 of the case content
+    pub hash: String,
+    pub size: u64,
+    pub exit: CaseExit,
+}
+
+impl CaseEntry {
+    pub fn from_case_path(case_path: &Path, exit: CaseExit) -> Result<Self> {
+        let content = std::fs::read(case_path)?;
+        Ok(Self {
+            case_path: case_path.to_path_buf(),
+            hash: format!("{:x}", md5::compute(&content)),
+            size: content.len() as u64,
+            exit,
+        })
+    }
+}
+
+/// Persistent exec name to case mapping of an experiment, so that cases can be found by
+/// processes other than the one executing them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CaseManifest {
+    entries: BTreeMap<ExecName, CaseEntry>,
+}
+
+impl CaseManifest {
+    /// Load the manifest, an empty one is returned if the file does not exist.
+    pub fn load<P: AsRef<Path>>(manifest_path: P) -> Result<Self> {
+        let manifest_path = manifest_path.as_ref();
+        if !manifest_path.is_file() {
+            return Ok(Self::default());
+        }
+        let bytes = buffer_read_to_bytes(manifest_path)?;
+        let manifest = serde_json::from_slice(&bytes)
+            .map_err(|e| eyre::eyre!("Failed to parse case manifest {:?}: {}", manifest_path, e))?;
+        Ok(manifest)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, manifest_path: P) -> Result<()> {
+        let json_str = serde_json::to_string_pretty(self)?;
+        write_bytes_to_file(manifest_path, json_str.as_bytes())
+    }
+
+    pub fn insert(&mut self, exec_name: ExecName, entry: CaseEntry) {
+        self.entries.insert(exec_name, entry);
+    }
+
+    pub fn get(&self, exec_name: &str) -> Option<&CaseEntry> {
+        self.entries.get(exec_name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::constraint::exec_rec::ExecRec,
+        test_utils::{add_expe_exec, cov_export},
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_case_manifest() -> Result<()> {
+        let expe_dir = tempfile::tempdir()?;
+        let case_path = expe_dir.path().join("crash-1");
+        std::fs::write(&case_path, b"\x00\x01\x02")?;
+
+        let exec_name = "e3b0c44298fc1c14";
+        let mut manifest = CaseManifest::default();
+        let entry = CaseEntry::from_case_path(&case_path, CaseExit::Failed)?;
+        assert_eq!(entry.size, 3);
+        manifest.insert(exec_name.to_owned(), entry.clone());
+        manifest.save(ExecRec::get_case_manifest_path(expe_dir.path())?)?;
+
+        // the mapping of this process does not know the exec name
+        let exec = add_expe_exec(
+            expe_dir.path(),
+            exec_name,
+            &cov_export(json!([])),
+            &["enter main"],
+        )?;
+        assert_eq!(exec.get_case_entry(), Some(&entry));
+        assert!(exec.is_abnormal());
+        let exec_list = ExecRec::get_exec_list_from_expe_dir(expe_dir.path())?;
+        assert_eq!(exec_list.len(), 1);
+        assert_eq!(exec_list[0].get_case_entry(), Some(&entry));
+        assert_eq!(exec_list[0].get_case_path()?, case_path);
+        Ok(())
+    }
+}
@@ -0,0 +1,363 @@
+//! Call-context trie merged over all executions of an experiment. Contexts are hash-consed, so
+//! the same call chain of different inputs shares one node, which records its hit count and the
+//! inputs reaching it.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analysis::constraint::{
+        exec_rec::ExecRec,
+        inter::exec_tree::{
+            thread_tree::{FuncIter, SharedFuncNodePtr},
+            ExecForest,
+        },
+    },
+    deopt::utils::{buffer_read_to_bytes, write_bytes_to_file},
+};
+
+pub type TrieNodeId = usize;
+
+pub const TRIE_ROOT_ID: TrieNodeId = 0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrieInput {
+    pub exec_name: String,
+    /// case path from the case manifest
+    pub case_path: Option<PathBuf>,
+}
+
+impl fmt::Display for TrieInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.case_path {
+            Some(case_path) => write!(f, "{} ({})", self.exec_name, case_path.display()),
+            None => write!(f, "{}", self.exec_name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrieNode {
+    /// interned function name, None for the root
+    func_id: Option<usize>,
+    parent: Option<TrieNodeId>,
+    /// number of invocations under this context
+    hits: usize,
+    /// indices of the inputs reaching this context
+    inputs: BTreeSet<usize>,
+}
+
+impl TrieNode {
+    pub fn get_parent(&self) -> Option<TrieNodeId> {
+        self.parent
+    }
+
+    pub fn get_hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn get_inputs(&self) -> &BTreeSet<usize> {
+        &self.inputs
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecTrie {
+    func_names: Vec<String>,
+    nodes: Vec<TrieNode>,
+    inputs: Vec<TrieInput>,
+    /// function name -> interned id, rebuilt on load
+    #[serde(skip)]
+    func_ids: HashMap<String, usize>,
+    /// (parent, function) -> child, rebuilt on load
+    #[serde(skip)]
+    child_ids: HashMap<(TrieNodeId, usize), TrieNodeId>,
+}
+
+impl Default for ExecTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecTrie {
+    pub fn new() -> Self {
+        let root = TrieNode {
+            func_id: None,
+            parent: None,
+            hits: 0,
+            inputs: BTreeSet::new(),
+        };
+        Self {
+            func_names: vec![],
+            nodes: vec![root],
+            inputs: vec![],
+            func_ids: HashMap::new(),
+            child_ids: HashMap::new(),
+        }
+    }
+
+    /// Merge the executions of an experiment. Executions whose forests fail to build are skipped.
+    pub fn from_expe_dir(expe_dir: &Path) -> Result<Self> {
+        let mut trie = Self::new();
+        // forests are dropped along with their executions
+        for exec in ExecRec::get_exec_list_from_expe_dir(expe_dir)? {
+            let forest = match exec.get_exec_forest() {
+                Ok(forest) => forest,
+                Err(e) => {
+                    log::warn!("Failed to build execution forest of {}: {}", exec, e);
+                    continue;
+                }
+            };
+            let input = TrieInput {
+                exec_name: exec.get_exec_name().to_owned(),
+                case_path: exec.get_case_entry().map(|entry| entry.case_path.clone()),
+            };
+            trie.add_forest(input, forest);
+        }
+        log::info!(
+            "{} contexts merged from {} executions",
+            trie.nodes.len() - 1,
+            trie.inputs.len()
+        );
+        Ok(trie)
+    }
+
+    pub fn load<P: AsRef<Path>>(trie_path: P) -> Result<Self> {
+        let bytes = buffer_read_to_bytes(trie_path.as_ref())?;
+        let mut trie: Self = serde_json::from_slice(&bytes).map_err(|e| {
+            eyre::eyre!(
+                "Failed to parse execution trie {:?}: {}",
+                trie_path.as_ref(),
+                e
+            )
+        })?;
+        trie.rebuild_index();
+        Ok(trie)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, trie_path: P) -> Result<()> {
+        let json_str = serde_json::to_string(self)?;
+        write_bytes_to_file(trie_path, json_str.as_bytes())
+    }
+
+    fn rebuild_index(&mut self) {
+        self.func_ids = self
+            .func_names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id))
+            .collect();
+        self.child_ids = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(id, node)| Some(((node.parent?, node.func_id?), id)))
+            .collect();
+    }
+
+    fn intern(&mut self, func_name: &str) -> usize {
+        if let Some(id) = self.func_ids.get(func_name) {
+            return *id;
+        }
+        let id = self.func_names.len();
+        self.func_names.push(func_name.to_owned());
+        self.func_ids.insert(func_name.to_owned(), id);
+        id
+    }
+
+    /// The node of `func_name` called under `parent`, created if absent.
+    fn get_or_add_child(&mut self, parent: TrieNodeId, func_name: &str) -> TrieNodeId {
+        let func_id = self.intern(func_name);
+        if let Some(id) = self.child_ids.get(&(parent, func_id)) {
+            return *id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(TrieNode {
+            func_id: Some(func_id),
+            parent: Some(parent),
+            hits: 0,
+            inputs: BTreeSet::new(),
+        });
+        self.child_ids.insert((parent, func_id), id);
+        id
+    }
+
+    /// Merge the call contexts of a forest. Init nodes are omitted and spawned threads are
+    /// merged under their creation sites.
+    pub fn add_forest(&mut self, input: TrieInput, forest: &ExecForest) {
+        let input_idx = self.inputs.len();
+        self.inputs.push(input);
+        self.nodes[TRIE_ROOT_ID].hits += 1;
+        self.nodes[TRIE_ROOT_ID].inputs.insert(input_idx);
+
+        let mut stack: Vec<(SharedFuncNodePtr, TrieNodeId)> = forest
+            .get_top_root_ptrs()
+            .into_iter()
+            .map(|ptr| (ptr, TRIE_ROOT_ID))
+            .collect();
+        while let Some((node_ptr, parent)) = stack.pop() {
+            let sub_parent = match node_ptr.borrow().get_func_name() {
+                Some(name) => {
+                    let id = self.get_or_add_child(parent, name);
+                    let trie_node = &mut self.nodes[id];
+                    trie_node.hits += 1;
+                    trie_node.inputs.insert(input_idx);
+                    id
+                }
+                None => parent,
+            };
+            for child_ptr in node_ptr.iter_sub_funcs_cross_thread() {
+                stack.push((child_ptr, sub_parent));
+            }
+        }
+    }
+
+    pub fn get_node(&self, id: TrieNodeId) -> Option<&TrieNode> {
+        self.nodes.get(id)
+    }
+
+    pub fn get_input(&self, idx: usize) -> Option<&TrieInput> {
+        self.inputs.get(idx)
+    }
+
+    pub fn get_func_name(&self, id: TrieNodeId) -> Option<&str> {
+        let func_id = self.nodes.get(id)?.func_id?;
+        Some(&self.func_names[func_id])
+    }
+
+    /// number of distinct call contexts
+    pub fn count_contexts(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// Function names from the outermost call to the node.
+    pub fn get_context(&self, id: TrieNodeId) -> Vec<&str> {
+        let mut context = vec![];
+        let mut cur = Some(id);
+        while let Some(node_id) = cur {
+            if let Some(name) = self.get_func_name(node_id) {
+                context.push(name);
+            }
+            cur = self.nodes.get(node_id).and_then(|node| node.parent);
+        }
+        context.reverse();
+        context
+    }
+
+    fn has_ancestor(&self, id: TrieNodeId, func_id: usize) -> bool {
+        let mut cur = self.nodes[id].parent;
+        while let Some(node_id) = cur {
+            let node = &self.nodes[node_id];
+            if node.func_id == Some(func_id) {
+                return true;
+            }
+            cur = node.parent;
+        }
+        false
+    }
+
+    /// Contexts of `func_name`, restricted to the ones called under `under` if given.
+    pub fn find_contexts(&self, func_name: &str, under: Option<&str>) -> Vec<TrieNodeId> {
+        let func_id = match self.func_ids.get(func_name) {
+            Some(id) => *id,
+            None => return vec![],
+        };
+        let under_id = match under {
+            Some(name) => match self.func_ids.get(name) {
+                Some(id) => Some(*id),
+                None => return vec![],
+            },
+            None => None,
+        };
+        self.child_ids
+            .iter()
+            .filter(|((_, id), _)| *id == func_id)
+            .map(|(_, node_id)| *node_id)
+            .filter(|node_id| under_id.is_none_or(|id| self.has_ancestor(*node_id, id)))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Inputs reaching `func_name`, restricted to the calls under `under` if given.
+    pub fn find_inputs(&self, func_name: &str, under: Option<&str>) -> Vec<&TrieInput> {
+        let input_ids: BTreeSet<usize> = self
+            .find_contexts(func_name, under)
+            .into_iter()
+            .flat_map(|id| self.nodes[id].inputs.iter().copied())
+            .collect();
+        input_ids.into_iter().map(|idx| &self.inputs[idx]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{add_expe_exec, cov_export};
+
+    #[test]
+    fn test_exec_trie() -> Result<()> {
+        let expe_dir = tempfile::tempdir()?;
+        let cov = cov_export(json!([]));
+        add_expe_exec(
+            expe_dir.path(),
+            "left",
+            &cov,
+            &[
+                "enter main",
+                "enter decode_partition",
+                "enter read_tx_size",
+                "return from read_tx_size",
+                "enter read_tx_size",
+                "return from read_tx_size",
+                "return from decode_partition",
+                "return from main",
+            ],
+        )?;
+        add_expe_exec(
+            expe_dir.path(),
+            "right",
+            &cov,
+            &[
+                "enter main",
+                "enter read_tx_size",
+                "return from read_tx_size",
+                "enter decode_partition",
+                "return from decode_partition",
+                "return from main",
+            ],
+        )?;
+
+        let trie = ExecTrie::from_expe_dir(expe_dir.path())?;
+        // main, main/decode_partition, main/decode_partition/read_tx_size, main/read_tx_size
+        assert_eq!(trie.count_contexts(), 4);
+
+        let contexts = trie.find_contexts("read_tx_size", Some("decode_partition"));
+        assert_eq!(contexts.len(), 1);
+        assert_eq!(
+            trie.get_context(contexts[0]),
+            ["main", "decode_partition", "read_tx_size"]
+        );
+        assert_eq!(trie.get_node(contexts[0]).unwrap().get_hits(), 2);
+        let inputs = trie.find_inputs("read_tx_size", Some("decode_partition"));
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].exec_name, "left");
+        assert_eq!(trie.find_inputs("read_tx_size", None).len(), 2);
+
+        let trie_path = expe_dir.path().join("exec_trie.json");
+        trie.save(&trie_path)?;
+        let loaded = ExecTrie::load(&trie_path)?;
+        assert_eq!(loaded.find_contexts("read_tx_size", None).len(), 2);
+        assert_eq!(loaded.find_inputs("decode_partition", None).len(), 2);
+        Ok(())
+    }
+}
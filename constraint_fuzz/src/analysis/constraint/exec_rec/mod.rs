@@ -1,16 +1,23 @@
 use color_eyre::eyre::{eyre, Result};
+use std::cell::OnceCell;
 use std::fs;
 use std::path::Path;
 use std::{fmt, path::PathBuf};
 
-use crate::analysis::constraint::exec_rec::case_map::get_case_path_from_exec_name;
+use crate::analysis::constraint::exec_rec::case_map::{
+    get_case_path_from_exec_name, CaseEntry, CaseExit, CaseManifest,
+};
 use crate::analysis::constraint::inter::exec_tree::ExecForest;
+use crate::config::get_exec_node_cap;
 use crate::deopt::utils::{
     buffer_read_to_bytes, create_dir_if_nonexist, get_basename_str_from_path, get_parent_dir,
 };
+use crate::feedback::branches::constraints::UBConstraint;
 use crate::feedback::clang_coverage::CodeCoverage;
 
 pub mod case_map;
+pub mod exec_trie;
+pub mod trace_check;
 
 pub struct ExecRec {
     exec_name: String,
@@ -18,7 +25,10 @@ pub struct ExecRec {
     execg_dir: PathBuf,
     cov_path: PathBuf,
     cov: CodeCoverage,
-    exec_forest: ExecForest,
+    /// built on first use since the whole forest may be large
+    exec_forest_cell: OnceCell<ExecForest>,
+    /// executed case recorded in the case manifest
+    case_entry: Option<CaseEntry>,
 }
 
 impl fmt::Display for ExecRec {
@@ -63,6 +73,12 @@ impl ExecRec {
         Ok(fs_dir)
     }
 
+    /// manifest of the executed cases, keyed by exec names
+    pub fn get_case_manifest_path(expe_dir: &Path) -> Result<PathBuf> {
+        let msg_dir = Self::get_exec_msg_dir(expe_dir)?;
+        Ok(msg_dir.join("case_manifest.json"))
+    }
+
     /// Main interface of exec message directory construction
     pub fn setup_exec_dir(expe_dir: &Path) -> Result<(PathBuf, PathBuf)> {
         // create coverage directory
@@ -100,6 +116,7 @@ impl ExecRec {
 
     pub fn get_exec_list_from_expe_dir(expe_dir: &Path) -> Result<Vec<Self>> {
         let exec_cov_dir = Self::get_exec_cov_dir(expe_dir)?;
+        let manifest = CaseManifest::load(Self::get_case_manifest_path(expe_dir)?)?;
         let mut exec_list = vec![];
 
         // iterate over all files in the coverage directory
@@ -107,30 +124,37 @@ impl ExecRec {
             let entry = ent_res?;
             let fpath = entry.path();
             if fpath.is_file() {
-                let exec = Self::from_cov_path(&fpath)?;
-                exec_list.push(exec);
+                exec_list.push(Self::from_cov_path_with_manifest(&fpath, &manifest)?);
             }
         }
 
         Ok(exec_list)
     }
 
+    /// The case of the execution is looked up in the case manifest of its experiment.
     pub fn from_cov_path(cov_path: &Path) -> Result<Self> {
+        let expe_dir = Self::get_expe_dir_from_cov_path(cov_path)?;
+        let manifest = CaseManifest::load(Self::get_case_manifest_path(&expe_dir)?)?;
+        Self::from_cov_path_with_manifest(cov_path, &manifest)
+    }
+
+    fn from_cov_path_with_manifest(cov_path: &Path, manifest: &CaseManifest) -> Result<Self> {
         let exec_name = get_basename_str_from_path(cov_path)?;
 
         let expe_dir = Self::get_expe_dir_from_cov_path(cov_path)?;
         let sg_guard_dir = Self::get_sg_guard_dir(&expe_dir, &exec_name)?;
-        let exec_forest = ExecForest::from_guard_dir(&sg_guard_dir)?;
 
         let buf = buffer_read_to_bytes(cov_path)?;
         let cov: CodeCoverage = serde_json::from_slice(&buf)?;
+        let case_entry = manifest.get(&exec_name).cloned();
 
         Ok(Self {
             exec_name,
             execg_dir: sg_guard_dir.to_owned(),
             cov_path: cov_path.to_owned(),
-            exec_forest,
+            exec_forest_cell: OnceCell::new(),
             cov,
+            case_entry,
         })
     }
 
@@ -140,7 +164,6 @@ impl ExecRec {
         let cov_dir = exec_dir.join("cov");
         let cov_path = cov_dir.join(&exec_name);
 
-        let exec_forest = ExecForest::from_guard_dir(sg_guard_dir)?;
         let cov: CodeCoverage = {
             let buf = buffer_read_to_bytes(&cov_path)?;
             serde_json::from_slice(&buf)?
@@ -156,8 +179,9 @@ impl ExecRec {
             exec_name,
             execg_dir: sg_guard_dir.to_owned(),
             cov_path,
-            exec_forest,
+            exec_forest_cell: OnceCell::new(),
             cov,
+            case_entry: None,
         })
     }
 }
@@ -167,8 +191,63 @@ impl ExecRec {
      * Property gettting methods
      */
 
+    pub fn get_exec_name(&self) -> &str {
+        &self.exec_name
+    }
+
+    pub fn get_cov(&self) -> &CodeCoverage {
+        &self.cov
+    }
+
+    pub fn get_exec_forest(&self) -> Result<&ExecForest> {
+        if let Some(forest) = self.exec_forest_cell.get() {
+            return Ok(forest);
+        }
+        let forest =
+            ExecForest::from_guard_dir_with_constraint(&self.execg_dir, None, self.is_abnormal())?;
+        Ok(self.exec_forest_cell.get_or_init(|| forest))
+    }
+
+    /// Build a forest with only the parts related to the constraint, which is not cached.
+    pub fn build_exec_forest_for_cons(&self, cons: &UBConstraint) -> Result<ExecForest> {
+        ExecForest::from_guard_dir_for_cons(
+            &self.execg_dir,
+            cons,
+            get_exec_node_cap(),
+            self.is_abnormal(),
+        )
+    }
+
+    /// Build a forest with only the parts related to `func_name` without truncation, which is
+    /// not cached.
+    pub fn build_exec_forest_for_func(&self, func_name: &str) -> Result<ExecForest> {
+        ExecForest::from_guard_dir_for_func(
+            &self.execg_dir,
+            func_name,
+            None,
+            get_exec_node_cap(),
+            self.is_abnormal(),
+        )
+    }
+
+    /// The case crashed or timed out, so its guard files may be damaged and are read in
+    /// recovery mode.
+    pub fn is_abnormal(&self) -> bool {
+        self.case_entry
+            .as_ref()
+            .is_some_and(|entry| entry.exit != CaseExit::Normal)
+    }
+
+    pub fn get_case_entry(&self) -> Option<&CaseEntry> {
+        self.case_entry.as_ref()
+    }
+
+    /// Case path from the case manifest, or from the mapping of the executing process.
     pub fn get_case_path(&self) -> Result<PathBuf> {
-        let case_path = get_case_path_from_exec_name(&self.exec_name)?;
+        let case_path = match &self.case_entry {
+            Some(entry) => entry.case_path.clone(),
+            None => get_case_path_from_exec_name(&self.exec_name)?,
+        };
         assert!(
             case_path.is_file(),
             "Case path does not exist: {}",
@@ -0,0 +1,378 @@
+//! Cross-check of the execution trees against the coverage of the same case, which catches
+//! instrumentation gaps, e.g. functions skipped by the pass, before the trace analysis is trusted.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    analysis::constraint::{
+        exec_rec::ExecRec,
+        inter::{
+            exec_tree::{action::ExecAction, ExecForest},
+            loc::SrcLoc,
+        },
+    },
+    feedback::{
+        branches::constraints::Range,
+        clang_coverage::{BranchCount, CodeCoverage, CovFunction},
+    },
+};
+
+/// directions taken at a branch, indexed by the condition value
+type TracedDirs = [bool; 2];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceCovMismatch {
+    /// executed in the coverage without any function node in the trace
+    UntracedFunc { exec_count: usize },
+    /// the direction is covered but never taken in the trace
+    NotTraced { range: Range, dir: bool },
+    /// the direction is taken in the trace but not covered
+    NotCovered { loc: String, dir: bool },
+}
+
+impl fmt::Display for TraceCovMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceCovMismatch::UntracedFunc { exec_count } => {
+                write!(f, "executed {} times but not traced", exec_count)
+            }
+            TraceCovMismatch::NotTraced { range, dir } => write!(
+                f,
+                "{} arm of branch at {}:{} covered but not traced",
+                dir, range[0], range[1]
+            ),
+            TraceCovMismatch::NotCovered { loc, dir } => {
+                write!(f, "{} arm of branch at {} traced but not covered", dir, loc)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TraceCovCheck {
+    exec_name: String,
+    /// the trace misses actions because of damaged guard files
+    incomplete: bool,
+    /// number of coverage branches checked
+    checked_brs: usize,
+    /// function name in the coverage, i.e. `file:func` for static functions -> mismatches in it
+    mismatches: BTreeMap<String, Vec<TraceCovMismatch>>,
+}
+
+impl TraceCovCheck {
+    pub fn get_exec_name(&self) -> &str {
+        &self.exec_name
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    pub fn get_checked_brs(&self) -> usize {
+        self.checked_brs
+    }
+
+    pub fn get_mismatches(&self) -> &BTreeMap<String, Vec<TraceCovMismatch>> {
+        &self.mismatches
+    }
+
+    pub fn count_mismatches(&self) -> usize {
+        self.mismatches.values().map(|list| list.len()).sum()
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    fn add_mismatch(&mut self, func_name: &str, mismatch: TraceCovMismatch) {
+        self.mismatches
+            .entry(func_name.to_owned())
+            .or_default()
+            .push(mismatch);
+    }
+}
+
+impl fmt::Display for TraceCovCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} branches checked, {} mismatches",
+            self.exec_name,
+            self.checked_brs,
+            self.count_mismatches()
+        )?;
+        if self.incomplete {
+            write!(f, " (incomplete trace)")?;
+        }
+        writeln!(f)?;
+        for (func_name, list) in self.mismatches.iter() {
+            for mismatch in list {
+                writeln!(f, "  {}: {}", func_name, mismatch)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// (source file, function name) of a traced function
+type TracedFunc = (PathBuf, String);
+
+/// Branches taken in the trace. Static functions of different files may share names, so
+/// functions are keyed by the files of the locations of their actions.
+#[derive(Default)]
+struct TracedBrs {
+    /// directions taken at each value location of regular branches
+    brs: HashMap<TracedFunc, HashMap<SrcLoc, TracedDirs>>,
+    /// functions whose actions carry no locations, so their files are unknown
+    unlocated: HashSet<String>,
+}
+
+/// source file of the function executing the action
+fn get_act_src_path(act: &ExecAction) -> Option<&Path> {
+    match act {
+        ExecAction::Intra(jump_act) => jump_act.get_from_loc().get_src_path(),
+        ExecAction::Func(func_act) if func_act.is_call() => {
+            func_act.get_invoc_loc().and_then(SrcLoc::get_src_path)
+        }
+        _ => None,
+    }
+}
+
+fn collect_traced_brs(forest: &ExecForest) -> TracedBrs {
+    let mut traced = TracedBrs::default();
+    for func_node_ptr in forest.func_node_bfs_iter() {
+        let func_node = func_node_ptr.borrow();
+        let func_name = match func_node.get_func_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut located = false;
+        for act in func_node.iter_acts() {
+            let fpath = match get_act_src_path(act) {
+                Some(fpath) => fpath,
+                None => continue,
+            };
+            located = true;
+            let brs = traced
+                .brs
+                .entry((fpath.to_path_buf(), func_name.to_owned()))
+                .or_default();
+            if let ExecAction::Intra(jump_act) = act {
+                if let Some(val_loc) = jump_act.get_val_loc() {
+                    brs.entry(val_loc.clone()).or_default()[jump_act.get_cond_val() as usize] =
+                        true;
+                }
+            }
+        }
+        if !located {
+            traced.unlocated.insert(func_name.to_owned());
+        }
+    }
+    traced
+}
+
+fn check_func(
+    check: &mut TraceCovCheck,
+    func: &CovFunction,
+    fpath: &Path,
+    traced_brs: &HashMap<SrcLoc, TracedDirs>,
+) -> Result<()> {
+    let func_name = func.name.as_str();
+    let mut matched: HashSet<&SrcLoc> = HashSet::new();
+    for cov_br in func.iter_cov_branches() {
+        let range = cov_br.get_range()?;
+        let br_fpath = func.get_source_file_path_by_cov_branch(cov_br)?;
+        let mut dirs: TracedDirs = [false; 2];
+        for (val_loc, traced_dirs) in traced_brs.iter() {
+            if val_loc.inside_range(&range, &br_fpath)? {
+                dirs[0] |= traced_dirs[0];
+                dirs[1] |= traced_dirs[1];
+                matched.insert(val_loc);
+            }
+        }
+        check.checked_brs += 1;
+
+        let counts = [*cov_br.get_false_count(), *cov_br.get_true_count()];
+        for dir in [true, false] {
+            let is_covered = counts[dir as usize] > 0;
+            let is_traced = dirs[dir as usize];
+            if is_covered && !is_traced {
+                check.add_mismatch(func_name, TraceCovMismatch::NotTraced { range, dir });
+            } else if is_traced && !is_covered {
+                let loc = format!("{}:{}:{}", br_fpath.display(), range[0], range[1]);
+                check.add_mismatch(func_name, TraceCovMismatch::NotCovered { loc, dir });
+            }
+        }
+    }
+
+    // traced branches without coverage branches
+    let mut unmatched: Vec<(&SrcLoc, &TracedDirs)> = traced_brs
+        .iter()
+        .filter(|(val_loc, _)| !matched.contains(val_loc) && val_loc.get_src_path() == Some(fpath))
+        .collect();
+    unmatched.sort_by_key(|(val_loc, _)| (val_loc.get_line(), val_loc.get_col()));
+    for (val_loc, traced_dirs) in unmatched {
+        for dir in [true, false] {
+            if traced_dirs[dir as usize] {
+                let loc = val_loc.to_string();
+                check.add_mismatch(func_name, TraceCovMismatch::NotCovered { loc, dir });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that the directions of regular branches taken in the trace are covered and vice versa.
+/// Only functions of the instrumented files, i.e. files with branches in the trace, are checked.
+pub fn check_trace_cov(forest: &ExecForest, cov: &CodeCoverage) -> Result<TraceCovCheck> {
+    let traced = collect_traced_brs(forest);
+    let instru_files: HashSet<&Path> = traced
+        .brs
+        .values()
+        .flat_map(|brs| brs.keys())
+        .filter_map(|val_loc| val_loc.get_src_path())
+        .collect();
+
+    let mut check = TraceCovCheck {
+        incomplete: forest.is_incomplete(),
+        ..Default::default()
+    };
+    let empty = HashMap::new();
+    for func in cov.iter_function_covs() {
+        let fpath = match func.filenames.first() {
+            Some(fpath) => PathBuf::from(fpath),
+            None => continue,
+        };
+        if !instru_files.contains(fpath.as_path()) {
+            continue;
+        }
+        let func_name = func.get_name();
+        let traced_brs = match traced.brs.get(&(fpath.clone(), func_name.to_owned())) {
+            Some(brs) => brs,
+            None if func.count > 0 && !traced.unlocated.contains(func_name) => {
+                check.add_mismatch(
+                    &func.name,
+                    TraceCovMismatch::UntracedFunc {
+                        exec_count: func.count,
+                    },
+                );
+                continue;
+            }
+            None => &empty,
+        };
+        check_func(&mut check, func, &fpath, traced_brs)?;
+    }
+    Ok(check)
+}
+
+impl ExecRec {
+    /// Cross-check the execution forest against the coverage of the case.
+    pub fn check_trace_cov(&self) -> Result<TraceCovCheck> {
+        let mut check = check_trace_cov(self.get_exec_forest()?, self.get_cov())?;
+        check.exec_name = self.get_exec_name().to_owned();
+        if !check.is_consistent() {
+            log::warn!(
+                "{} mismatches between trace and coverage of {}",
+                check.count_mismatches(),
+                self
+            );
+        }
+        Ok(check)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_expe_exec, cov_export};
+    use serde_json::json;
+
+    #[test]
+    fn test_check_trace_cov() -> Result<()> {
+        let expe_dir = tempfile::tempdir()?;
+        let lines = [
+            "enter main",
+            "Br Guard: /src/a.c:5:11 /src/a.c:5:5 1 /src/a.c:6:7",
+            "Br Guard: /src/a.c:9:9 /src/a.c:9:5 0 /src/a.c:11:3",
+            "Br Guard: /src/a.c:20:9 /src/a.c:20:5 1 /src/a.c:21:3",
+            "enter helper",
+            "Br Guard: /src/a.c:35:9 /src/a.c:35:5 1 /src/a.c:36:3",
+            "return from helper",
+            "enter decode",
+            "Br Guard: /src/b.c:5:9 /src/b.c:5:5 1 /src/b.c:6:3",
+            "return from decode",
+            "return from main",
+        ];
+        let cov = cov_export(json!([{
+            "branches": [
+                [5, 9, 5, 14, 1, 0, 0, 0, 4],
+                [9, 9, 9, 12, 1, 1, 0, 0, 4],
+            ],
+            "filenames": ["/src/a.c"],
+            "regions": [[1, 1, 30, 1, 1, 0, 0, 0]],
+            "count": 1,
+            "name": "main",
+        }, {
+            "branches": [],
+            "filenames": ["/src/a.c"],
+            "regions": [[40, 1, 50, 1, 2, 0, 0, 0]],
+            "count": 2,
+            "name": "a.c:skipped",
+        }, {
+            "branches": [[35, 9, 35, 12, 1, 0, 0, 0, 4]],
+            "filenames": ["/src/a.c"],
+            "regions": [[31, 1, 38, 1, 1, 0, 0, 0]],
+            "count": 1,
+            "name": "a.c:helper",
+        }, {
+            "branches": [[5, 9, 5, 12, 1, 0, 0, 0, 4]],
+            "filenames": ["/src/b.c"],
+            "regions": [[1, 1, 8, 1, 1, 0, 0, 0]],
+            "count": 1,
+            "name": "decode",
+        }, {
+            "branches": [],
+            "filenames": ["/src/b.c"],
+            "regions": [[10, 1, 20, 1, 1, 0, 0, 0]],
+            "count": 1,
+            "name": "b.c:helper",
+        }]));
+        let exec = add_expe_exec(expe_dir.path(), "1", &cov, &lines)?;
+
+        let check = exec.check_trace_cov()?;
+        assert_eq!(check.get_exec_name(), "1");
+        assert_eq!(check.get_checked_brs(), 4);
+        assert_eq!(
+            check.get_mismatches()["main"],
+            [
+                TraceCovMismatch::NotTraced {
+                    range: [9, 9, 9, 12],
+                    dir: true
+                },
+                TraceCovMismatch::NotCovered {
+                    loc: "/src/a.c:20:9".to_owned(),
+                    dir: true
+                },
+            ]
+        );
+        assert_eq!(
+            check.get_mismatches()["a.c:skipped"],
+            [TraceCovMismatch::UntracedFunc { exec_count: 2 }]
+        );
+        // the helper of a.c does not hide the one of b.c
+        assert_eq!(
+            check.get_mismatches()["b.c:helper"],
+            [TraceCovMismatch::UntracedFunc { exec_count: 1 }]
+        );
+        assert_eq!(check.get_mismatches().len(), 3);
+        Ok(())
+    }
+}
@@ -47,15 +47,14 @@ impl GuardParseError {
                 Ok(None)
             }
             Err(GuardParseError::ParseError { data }) => Err(data),
-            Err(GuardParseError::SkipError { data, skip_num: _ }) => {
-                panic!(
-                    "GuardParse Skip Error should not be converted to eyre Result: {}",
-                    data
-                );
+            Err(GuardParseError::SkipError { data, skip_num }) => {
+                log::trace!("GuardParse Skip Error after {} chars: {}", skip_num, data);
+                Err(data)
             }
         }
     }
 
+    /// Used at the end of parsing: a SkipError left here can not be resolved by skipping
     pub fn to_eyre_ultimate<T>(res: std::result::Result<T, GuardParseError>) -> Result<T> {
         match res {
             Ok(val) => Ok(val),
@@ -64,11 +63,9 @@ impl GuardParseError {
                 Err(data)
             }
             Err(GuardParseError::ParseError { data }) => Err(data),
-            Err(GuardParseError::SkipError { data, skip_num: _ }) => {
-                panic!(
-                    "GuardParse Skip Error should not be converted to eyre Result: {}",
-                    data
-                );
+            Err(GuardParseError::SkipError { data, skip_num }) => {
+                log::trace!("GuardParse Skip Error after {} chars: {}", skip_num, data);
+                Err(data)
             }
         }
     }
@@ -67,6 +67,18 @@ impl FuncActionType {
     pub fn get_func_name_from_unwind_guard(line: &str) -> Result<&str> {
         Self::get_func_name_from_line(line, Self::UNWIND_PREFIX)
     }
+
+    /// Function name of a call guard, with or without the invocation part.
+    /// Unlike `FuncAction::parse_call_guard`, the invocation location is not parsed.
+    pub fn get_func_name_from_call_guard(line: &str) -> Option<&str> {
+        let entry_part = match line.strip_prefix(Self::INVOC_PREFIX) {
+            Some(invoc_part) => invoc_part.trim_start().split_once(char::is_whitespace)?.1,
+            None => line,
+        };
+        let name_part = entry_part.strip_prefix(Self::ENT_PREFIX)?;
+        let end = name_part.find('(').unwrap_or(name_part.len());
+        Some(&name_part[..end])
+    }
 }
 
 #[derive(Clone)]
@@ -141,6 +153,18 @@ impl FuncAction {
         }
     }
 
+    pub fn get_invoc_loc(&self) -> Option<&SrcLoc> {
+        if let FuncActionType::Call {
+            child_ptr: _,
+            invoc_loc,
+        } = &self.act_type
+        {
+            invoc_loc.as_ref()
+        } else {
+            None
+        }
+    }
+
     pub fn parse_return_guard(line: &str) -> Result<Self> {
         let func_name = FuncActionType::get_func_name_from_return_guard(line)?;
         Ok(Self {
@@ -218,20 +242,20 @@ impl FuncAction {
 // }
 
 #[derive(Clone)]
-enum JumpActionType {
-    BrGuard { val_loc: SrcLoc },
-    MergeBrGuard,
-    SwitchGuard,
-    IndirectGuard,
+pub(super) enum JumpActionType {
+    Br { val_loc: SrcLoc },
+    MergeBr,
+    Switch,
+    Indirect,
 }
 
 impl JumpActionType {
     pub fn from_simple_prefix(prefix: &str) -> Option<Self> {
         match prefix {
             // Only Merge Br Guard is in simple version
-            "Merge Br Guard:" => Some(JumpActionType::MergeBrGuard),
-            "Switch Guard:" => Some(JumpActionType::SwitchGuard),
-            "IndirectBr Guard:" => Some(JumpActionType::IndirectGuard),
+            "Merge Br Guard:" => Some(JumpActionType::MergeBr),
+            "Switch Guard:" => Some(JumpActionType::Switch),
+            "IndirectBr Guard:" => Some(JumpActionType::Indirect),
             _ => None,
         }
     }
@@ -240,12 +264,12 @@ impl JumpActionType {
 impl fmt::Debug for JumpActionType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            JumpActionType::BrGuard { val_loc } => {
+            JumpActionType::Br { val_loc } => {
                 write!(f, "BrGuard with value loc {:?}", val_loc)
             }
-            JumpActionType::MergeBrGuard => write!(f, "MergeBrGuard"),
-            JumpActionType::SwitchGuard => write!(f, "SwitchGuard"),
-            JumpActionType::IndirectGuard => write!(f, "IndirectGuard"),
+            JumpActionType::MergeBr => write!(f, "MergeBrGuard"),
+            JumpActionType::Switch => write!(f, "SwitchGuard"),
+            JumpActionType::Indirect => write!(f, "IndirectGuard"),
         }
     }
 }
@@ -256,17 +280,20 @@ pub struct JumpAction {
     from_loc: SrcLoc,
     cond_val: bool,
     dest_loc: SrcLoc,
+    /// operands of the compare feeding a regular branch, recorded before the branch, boxed as
+    /// most branches have none
+    operands: Option<Box<BrOperands>>,
 }
 
 impl JumpAction {
     fn get_dot_id(&self, cnt: usize) -> String {
         match self.intra_type {
-            JumpActionType::BrGuard { .. } => {
+            JumpActionType::Br { .. } => {
                 format!("Branch_Guard_Action_{}", cnt)
             }
-            JumpActionType::MergeBrGuard => format!("Branch_Merge_Guard_Action_{}", cnt),
-            JumpActionType::SwitchGuard => format!("Switch_Guard_Action_{}", cnt),
-            JumpActionType::IndirectGuard => format!("Indirect_Branch_Guard_Action_{}", cnt),
+            JumpActionType::MergeBr => format!("Branch_Merge_Guard_Action_{}", cnt),
+            JumpActionType::Switch => format!("Switch_Guard_Action_{}", cnt),
+            JumpActionType::Indirect => format!("Indirect_Branch_Guard_Action_{}", cnt),
         }
     }
 }
@@ -277,11 +304,67 @@ impl fmt::Debug for JumpAction {
             f,
             "{:?} at {:?} with value {} to {:?}",
             self.intra_type, self.from_loc, self.cond_val, self.dest_loc
-        )
+        )?;
+        if let Some(operands) = &self.operands {
+            write!(f, " ({})", operands)?;
+        }
+        Ok(())
     }
 }
 
 impl JumpAction {
+    pub(super) fn new(
+        intra_type: JumpActionType,
+        from_loc: SrcLoc,
+        cond_val: bool,
+        dest_loc: SrcLoc,
+    ) -> Self {
+        Self {
+            intra_type,
+            from_loc,
+            cond_val,
+            dest_loc,
+            operands: None,
+        }
+    }
+
+    pub fn get_from_loc(&self) -> &SrcLoc {
+        &self.from_loc
+    }
+
+    pub fn get_dest_loc(&self) -> &SrcLoc {
+        &self.dest_loc
+    }
+
+    pub fn get_cond_val(&self) -> bool {
+        self.cond_val
+    }
+
+    /// value location of a regular branch guard
+    pub fn get_val_loc(&self) -> Option<&SrcLoc> {
+        match &self.intra_type {
+            JumpActionType::Br { val_loc } => Some(val_loc),
+            _ => None,
+        }
+    }
+
+    pub fn is_switch_guard(&self) -> bool {
+        matches!(self.intra_type, JumpActionType::Switch)
+    }
+
+    pub fn is_indirect_guard(&self) -> bool {
+        matches!(self.intra_type, JumpActionType::Indirect)
+    }
+
+    /// operand values of the compare, only available for regular branch guards
+    pub fn get_operands(&self) -> Option<&BrOperands> {
+        self.operands.as_deref()
+    }
+
+    pub(super) fn set_operands(&mut self, operands: Option<BrOperands>) {
+        self.operands = operands.map(Box::new);
+    }
+
     fn parse_simple_guard(line: &str) -> std::result::Result<Self, GuardParseError> {
         let prefix = get_prefix(line)?;
         let intra_type = JumpActionType::from_simple_prefix(prefix).ok_or_else(|| {
@@ -316,12 +399,7 @@ impl JumpAction {
             .ok_or_else(|| eyre::eyre!("Missing destination location"))?;
         let dest_loc = SrcLoc::from_str(dest_loc_str)?;
 
-        Ok(Self {
-            intra_type,
-            from_loc: cond_loc,
-            cond_val,
-            dest_loc,
-        })
+        Ok(Self::new(intra_type, cond_loc, cond_val, dest_loc))
     }
 
     pub fn parse_jump_guard(line: &str) -> std::result::Result<Self, GuardParseError> {
@@ -345,7 +423,7 @@ impl JumpAction {
 
             // parse value hit
             let va_loc = SrcLoc::from_str(value_hit_str)?;
-            let br_act_type = JumpActionType::BrGuard { val_loc: va_loc };
+            let br_act_type = JumpActionType::Br { val_loc: va_loc };
 
             // parse intra action
             let br_act = JumpAction::from_slice(intra_act_str, br_act_type)?;
@@ -375,12 +453,162 @@ impl JumpAction {
         };
         let dest_loc = SrcLoc::from_str(parts[2])?;
 
-        Ok(Self {
-            intra_type: act_type,
-            from_loc: cond_loc,
-            cond_val,
-            dest_loc,
-        })
+        Ok(Self::new(act_type, cond_loc, cond_val, dest_loc))
+    }
+}
+
+/// Value of a compare operand, typed by the compare predicate and the operand type.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OperandVal {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+    Ptr(u64),
+}
+
+impl fmt::Display for OperandVal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperandVal::Signed(val) => write!(f, "{}", val),
+            OperandVal::Unsigned(val) => write!(f, "{}", val),
+            OperandVal::Float(val) => write!(f, "{}", val),
+            OperandVal::Ptr(val) => write!(f, "{:#x}", val),
+        }
+    }
+}
+
+impl OperandVal {
+    /// parse a value printed by the runtime, `kind` is one of `s`, `u`, `f` and `p`
+    fn parse(kind: &str, val_str: &str) -> Result<Self> {
+        let val = match kind {
+            "s" => OperandVal::Signed(val_str.parse()?),
+            "u" => OperandVal::Unsigned(val_str.parse()?),
+            "f" => OperandVal::Float(val_str.parse()?),
+            "p" => OperandVal::Ptr(val_str.parse()?),
+            _ => bail!("Unknown operand kind: {}", kind),
+        };
+        Ok(val)
+    }
+
+    /// The value as an unsigned integer of `width` bits, e.g. for the operands of eq and ne,
+    /// which are sign-extended regardless of their source types.
+    pub fn to_unsigned(self, width: u32) -> Option<u64> {
+        let bits = match self {
+            OperandVal::Signed(val) => val as u64,
+            OperandVal::Unsigned(val) => val,
+            _ => return None,
+        };
+        match width {
+            1..=63 => Some(bits & ((1u64 << width) - 1)),
+            64 => Some(bits),
+            _ => None,
+        }
+    }
+
+    /// decode a value from its 64-bit representation in binary traces
+    pub(super) fn from_bits(kind: u8, bits: u64) -> Result<Self> {
+        let val = match kind {
+            b's' => OperandVal::Signed(bits as i64),
+            b'u' => OperandVal::Unsigned(bits),
+            b'f' => OperandVal::Float(f64::from_bits(bits)),
+            b'p' => OperandVal::Ptr(bits),
+            _ => bail!("Unknown operand kind: {:#x}", kind),
+        };
+        Ok(val)
+    }
+}
+
+/// Operand values of the compare feeding a conditional branch.
+/// Guard format: `Br Operands: <val_loc> <predicate> <kind>[width] <lhs> <rhs>`,
+/// where the predicate is the LLVM compare predicate, e.g. `slt` or `oeq`, and the bit width
+/// follows the kind of integer operands, e.g. `s32`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BrOperands {
+    val_loc: SrcLoc,
+    pred: String,
+    lhs: OperandVal,
+    rhs: OperandVal,
+    /// bit width of integer operands, None for others and older traces
+    width: Option<u8>,
+}
+
+impl fmt::Display for BrOperands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.lhs, self.pred, self.rhs)
+    }
+}
+
+impl BrOperands {
+    const OPERANDS_PREFIX: &'static str = "Br Operands:";
+
+    pub(super) fn new(val_loc: SrcLoc, pred: String, lhs: OperandVal, rhs: OperandVal) -> Self {
+        Self {
+            val_loc,
+            pred,
+            lhs,
+            rhs,
+            width: None,
+        }
+    }
+
+    /// a width of 0 stands for non-integer operands
+    pub(super) fn with_width(mut self, width: u8) -> Self {
+        self.width = (width != 0).then_some(width);
+        self
+    }
+
+    pub fn parse_operands_guard(line: &str) -> std::result::Result<Self, GuardParseError> {
+        let content = line.strip_prefix(Self::OPERANDS_PREFIX).ok_or_else(|| {
+            GuardParseError::as_prefix_err(eyre::eyre!(
+                "Line does not start with '{}': {}",
+                Self::OPERANDS_PREFIX,
+                line
+            ))
+        })?;
+
+        let parts: Vec<&str> = content.split_whitespace().collect();
+        if parts.len() != 5 {
+            return Err(GuardParseError::as_parse_err(eyre::eyre!(
+                "Expected 5 parts in operands guard, found {}: {}",
+                parts.len(),
+                line
+            )));
+        }
+        let val_loc = SrcLoc::from_str(parts[0])?;
+        let (kind, width_str) = parts[2].split_at(parts[2].len().min(1));
+        let width = match width_str {
+            "" => 0,
+            _ => width_str.parse().map_err(|e| {
+                GuardParseError::as_parse_err(eyre::eyre!(
+                    "Invalid operand width in {}: {}",
+                    line,
+                    e
+                ))
+            })?,
+        };
+        let lhs = OperandVal::parse(kind, parts[3])?;
+        let rhs = OperandVal::parse(kind, parts[4])?;
+        Ok(Self::new(val_loc, parts[1].to_owned(), lhs, rhs).with_width(width))
+    }
+
+    pub fn get_val_loc(&self) -> &SrcLoc {
+        &self.val_loc
+    }
+
+    pub fn get_pred(&self) -> &str {
+        &self.pred
+    }
+
+    pub fn get_lhs(&self) -> OperandVal {
+        self.lhs
+    }
+
+    pub fn get_rhs(&self) -> OperandVal {
+        self.rhs
+    }
+
+    pub fn get_width(&self) -> Option<u32> {
+        self.width.map(u32::from)
     }
 }
 
@@ -388,15 +616,37 @@ impl JumpAction {
 pub struct ThreadAction {
     loc: SrcLoc,
     tid: Tid, // using String for simplicity, can be changed to a more appropriate type
+    /// root of the spawned thread tree, linked after the whole forest is built
+    root_ptr: Option<SharedFuncNodePtr>,
 }
 
 impl ThreadAction {
     const THREAD_ACTION_PREFIX: &'static str = "Thread Creation:";
 
+    pub(super) fn new(loc: SrcLoc, tid: Tid) -> Self {
+        Self {
+            loc,
+            tid,
+            root_ptr: None,
+        }
+    }
+
     pub fn get_thread_id(&self) -> Tid {
         self.tid
     }
 
+    pub fn get_loc(&self) -> &SrcLoc {
+        &self.loc
+    }
+
+    pub fn get_root_ptr(&self) -> Option<SharedFuncNodePtr> {
+        self.root_ptr.clone()
+    }
+
+    pub(super) fn set_root_ptr(&mut self, root_ptr: SharedFuncNodePtr) {
+        self.root_ptr = Some(root_ptr);
+    }
+
     pub fn parse_thread_guard(line: &str) -> std::result::Result<Self, GuardParseError> {
         if !line.starts_with(Self::THREAD_ACTION_PREFIX) {
             return Err(GuardParseError::as_prefix_err(eyre::eyre!(
@@ -424,7 +674,7 @@ impl ThreadAction {
             ))
         })?;
 
-        Ok(Self { loc, tid })
+        Ok(Self::new(loc, tid))
     }
 }
 
@@ -467,6 +717,31 @@ impl LoopAction {
     const OUT_PREFIX: &'static str = "Out of Loop:";
     const NO_START_PREFIX: &'static str = "Loop end without loop start:";
 
+    /// Loop Hit or Loop Limit Exceed action
+    pub(super) fn new_entry(header_loc: SrcLoc, count: usize, exceed: bool) -> Self {
+        let entry_type = if exceed {
+            LoopEntryType::Exceed
+        } else {
+            LoopEntryType::Hit
+        };
+        Self {
+            la_type: LoopActionType::LoopEntry { count, entry_type },
+            header_loc,
+        }
+    }
+
+    /// Out of Loop action, or Loop end without loop start action if `count` is None
+    pub(super) fn new_end(header_loc: SrcLoc, out_loc: SrcLoc, count: Option<usize>) -> Self {
+        let end_type = match count {
+            Some(count) => LoopEndType::Out { count },
+            None => LoopEndType::NoStart,
+        };
+        Self {
+            la_type: LoopActionType::LoopEnd { out_loc, end_type },
+            header_loc,
+        }
+    }
+
     fn parse_loop_cnt(slice: &str) -> Result<usize> {
         const LOOP_CNT_PREFIX: &str = "at count";
         let slice = slice.trim();
@@ -607,6 +882,36 @@ impl LoopAction {
         &self.header_loc
     }
 
+    pub fn is_loop_entry(&self) -> bool {
+        matches!(self.la_type, LoopActionType::LoopEntry { .. })
+    }
+
+    pub fn is_loop_end(&self) -> bool {
+        matches!(self.la_type, LoopActionType::LoopEnd { .. })
+    }
+
+    /// the loop entry exceeding the loop limit of the runtime
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(
+            self.la_type,
+            LoopActionType::LoopEntry {
+                entry_type: LoopEntryType::Exceed,
+                ..
+            }
+        )
+    }
+
+    /// header hit count of the loop instance when it ends normally
+    pub fn get_out_count(&self) -> Option<usize> {
+        match self.la_type {
+            LoopActionType::LoopEnd {
+                end_type: LoopEndType::Out { count },
+                ..
+            } => Some(count),
+            _ => None,
+        }
+    }
+
     pub fn get_out_loc(&self) -> Option<&SrcLoc> {
         match &self.la_type {
             LoopActionType::LoopEntry {
@@ -718,6 +1023,24 @@ impl ExecAction {
         }
     }
 
+    /// root of the spawned thread if the action is a linked thread creation
+    pub fn get_thread_root_ptr(&self) -> Option<SharedFuncNodePtr> {
+        match self {
+            ExecAction::Thread(thread_act) => thread_act.get_root_ptr(),
+            _ => None,
+        }
+    }
+
+    /// Node entered by the action: callee of a call, or root of a spawned thread if
+    /// `cross_thread` is set.
+    pub fn get_sub_node_ptr(&self, cross_thread: bool) -> Option<SharedFuncNodePtr> {
+        match self {
+            ExecAction::Func(func_act) => func_act.get_child_ptr(),
+            ExecAction::Thread(thread_act) if cross_thread => thread_act.get_root_ptr(),
+            _ => None,
+        }
+    }
+
     pub fn get_func_call_act(&self) -> Option<&FuncAction> {
         if let ExecAction::Func(func_act) = self {
             if func_act.is_call() {
@@ -762,8 +1085,10 @@ impl fmt::Debug for ExecAction {
             ExecAction::Thread(thread_act) => {
                 write!(
                     f,
-                    "ThreadAction: loc: {:?}, tid: {}",
-                    thread_act.loc, thread_act.tid
+                    "ThreadAction: loc: {:?}, tid: {}, linked: {}",
+                    thread_act.loc,
+                    thread_act.tid,
+                    thread_act.root_ptr.is_some()
                 )
             }
         }
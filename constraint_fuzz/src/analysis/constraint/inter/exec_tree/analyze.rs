@@ -9,7 +9,9 @@ use crate::{
         exec_tree::action::ExecAction,
         exec_tree::thread_tree::{
             incre_dot_counter, DotId, ExecFuncNode, FuncIter, SharedFuncNodePtr, ThreadExecTree,
+            Tid,
         },
+        exec_tree::ExecForest,
         loc::SrcLoc,
     },
     deopt::utils::write_bytes_to_file,
@@ -23,13 +25,25 @@ use dot_writer::{Attributes, DotWriter, Style};
 
 pub struct ThreadTreeIter {
     queue: Vec<SharedFuncNodePtr>,
+    cross_thread: bool,
 }
 
 impl ThreadTreeIter {
     pub fn new(root: SharedFuncNodePtr) -> Self {
-        let mut queue = Vec::new();
-        queue.push(root);
-        Self { queue }
+        Self::from_roots(vec![root])
+    }
+
+    pub fn from_roots(roots: Vec<SharedFuncNodePtr>) -> Self {
+        Self {
+            queue: roots,
+            cross_thread: false,
+        }
+    }
+
+    /// also visit the threads spawned by linked thread creation actions
+    pub fn cross_thread(mut self) -> Self {
+        self.cross_thread = true;
+        self
     }
 }
 
@@ -44,10 +58,7 @@ impl Iterator for ThreadTreeIter {
         let cur_node = cur_node_ptr.borrow();
         // add children to the queue
         for act in cur_node.iter_acts() {
-            if let Some(func_act) = act.get_func_call_act() {
-                let child_ptr = func_act
-                    .get_child_ptr()
-                    .expect("Function action is a call but has no child pointer");
+            if let Some(child_ptr) = act.get_sub_node_ptr(self.cross_thread) {
                 self.queue.push(child_ptr);
             }
         }
@@ -161,7 +172,7 @@ impl ThreadExecTree {
         })?;
         match act {
             // ignore intra-function actions
-            ExecAction::Func(_) => Ok(false),
+            ExecAction::Func(_) | ExecAction::Thread(_) => Ok(false),
             _ => Ok(true),
         }
     }
@@ -221,11 +232,34 @@ impl ThreadExecTree {
                 })?;
                 let sub_func_id = Self::draw_func_cluster(sub_func_ptr, digraph)?;
                 digraph.edge(act_id, sub_func_id);
+            } else if let ExecAction::Thread(thread_act) = act {
+                if let Some(thread_root_ptr) = thread_act.get_root_ptr() {
+                    let thread_root_id = Self::draw_func_cluster(thread_root_ptr, digraph)?;
+                    Self::draw_thread_edge(
+                        digraph,
+                        act_id,
+                        &thread_root_id,
+                        thread_act.get_thread_id(),
+                    );
+                }
             }
         }
         Ok(cur_func_id.to_owned())
     }
 
+    fn draw_thread_edge<'d, 'w>(
+        digraph: &mut dot_writer::Scope<'d, 'w>,
+        from_id: &str,
+        thread_root_id: &str,
+        tid: Tid,
+    ) {
+        digraph
+            .edge(from_id, thread_root_id)
+            .attributes()
+            .set_style(Style::Dashed)
+            .set_label(&format!("thread {}", tid));
+    }
+
     fn draw_graph<'d, 'w>(&self, digraph: &mut dot_writer::Scope<'d, 'w>) -> Result<()> {
         Self::draw_func_cluster(self.root_ptr.clone(), digraph)?;
         Ok(())
@@ -264,6 +298,21 @@ impl ThreadExecTree {
             let sub_func_id = Self::draw_func_cluster_for_func_tree(sub_func_ptr, digraph)?;
             digraph.edge(&cur_func_id, sub_func_id);
         }
+        // threads spawned
+        for act in cur_func.iter_acts() {
+            if let ExecAction::Thread(thread_act) = act {
+                if let Some(thread_root_ptr) = thread_act.get_root_ptr() {
+                    let thread_root_id =
+                        Self::draw_func_cluster_for_func_tree(thread_root_ptr, digraph)?;
+                    Self::draw_thread_edge(
+                        digraph,
+                        &cur_func_id,
+                        &thread_root_id,
+                        thread_act.get_thread_id(),
+                    );
+                }
+            }
+        }
 
         Ok(cur_func_id.to_owned())
     }
@@ -636,6 +685,81 @@ impl ThreadExecTree {
     }
 }
 
+impl ExecForest {
+    /// Iterated element: FuncNode of every thread, spawned threads are visited after their
+    /// creation sites.
+    pub fn func_node_bfs_iter(&self) -> ThreadTreeIter {
+        ThreadTreeIter::from_roots(self.get_top_root_ptrs()).cross_thread()
+    }
+
+    /// Function names from the entry to the first invocation of `func_name` in execution order.
+    /// Init nodes are omitted and spawned threads are followed from their creation sites.
+    pub fn get_call_chain(&self, func_name: &str) -> Option<Vec<String>> {
+        let mut chain: Vec<String> = vec![];
+        let mut stack: Vec<(SharedFuncNodePtr, usize)> = self
+            .get_top_root_ptrs()
+            .into_iter()
+            .rev()
+            .map(|ptr| (ptr, 0))
+            .collect();
+
+        while let Some((node_ptr, depth)) = stack.pop() {
+            let node = node_ptr.borrow();
+            chain.truncate(depth);
+            let sub_depth = match node.get_func_name() {
+                Some(name) => {
+                    chain.push(name.to_owned());
+                    if name == func_name {
+                        return Some(chain);
+                    }
+                    depth + 1
+                }
+                None => depth,
+            };
+            let children: Vec<SharedFuncNodePtr> = node_ptr.iter_sub_funcs_cross_thread().collect();
+            for child_ptr in children.into_iter().rev() {
+                stack.push((child_ptr, sub_depth));
+            }
+        }
+        None
+    }
+
+    fn write_dot_file<P, F>(&self, dot_path: P, mut draw_root: F) -> Result<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(SharedFuncNodePtr, &mut dot_writer::Scope) -> Result<String>,
+    {
+        let mut dot_bytes = vec![];
+
+        // brackets to ensure that `dot_writer` is dropped before we write to the file
+        {
+            let mut dot_writer = DotWriter::from(&mut dot_bytes);
+            dot_writer.set_pretty_print(true);
+            let mut digraph = dot_writer.digraph();
+            for root_ptr in self.get_top_root_ptrs() {
+                draw_root(root_ptr, &mut digraph)?;
+            }
+        }
+        write_bytes_to_file(dot_path.as_ref(), &dot_bytes)?;
+        log::info!(
+            "dot conversion completed, written to: {}",
+            dot_path.as_ref().display()
+        );
+        Ok(())
+    }
+
+    /// DOT output of the whole forest, spawned threads are drawn under their creation actions.
+    pub fn to_dot_file<P: AsRef<Path>>(&self, dot_path: P) -> Result<()> {
+        log::info!("Starting to convert ExecForest to DOT format");
+        self.write_dot_file(dot_path, ThreadExecTree::draw_func_cluster)
+    }
+
+    pub fn to_func_tree_dot_file<P: AsRef<Path>>(&self, dot_path: P) -> Result<()> {
+        log::info!("Starting to convert ExecForest to Function-Tree DOT file");
+        self.write_dot_file(dot_path, ThreadExecTree::draw_func_cluster_for_func_tree)
+    }
+}
+
 #[derive(PartialEq, Eq, Hash)]
 pub struct RecurEntry {
     func_cycle: Vec<String>,
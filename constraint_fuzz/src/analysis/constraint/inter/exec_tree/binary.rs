@@ -0,0 +1,561 @@
+//! Decoder of the binary guard format emitted by the func_stack runtime
+//! when `FUNC_STACK_FORMAT=bin`. See `func_stack_pass/src/runtime/bin_trace.h`.
+//!
+//! File := Header Record*
+//! Header := "FSTB" u32(version)
+//! Record := StrDef | Event | Operands | Raw
+//! StrDef := u8(0x01) u32(id) u32(len) bytes[len]
+//! Event := u8(kind) u8(flag) u32(a) u32(b) u32(c) u64(num)
+//! Operands := Event(kind = 0x25) u64(rhs)
+//! Raw := u8(0x60) u32(len) bytes[len]
+
+use std::{
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+use color_eyre::eyre::Result;
+use eyre::bail;
+
+use crate::analysis::constraint::inter::{
+    exec_tree::{
+        action::{
+            BrOperands, ExecAction, FuncAction, FuncActionType, JumpAction, JumpActionType,
+            LoopAction, OperandVal, RecurAction, ThreadAction,
+        },
+        thread_tree::UBVHit,
+    },
+    loc::SrcLoc,
+};
+
+pub const BIN_TRACE_MAGIC: &[u8; 4] = b"FSTB";
+pub const BIN_TRACE_VERSION: u32 = 2;
+
+const KIND_STR_DEF: u8 = 0x01;
+const KIND_ENTER: u8 = 0x10;
+const KIND_RETURN: u8 = 0x11;
+const KIND_UNWIND: u8 = 0x12;
+const KIND_BR_GUARD: u8 = 0x20;
+const KIND_MERGE_BR_GUARD: u8 = 0x21;
+const KIND_SWITCH_GUARD: u8 = 0x22;
+const KIND_INDIRECT_GUARD: u8 = 0x23;
+const KIND_VALUE: u8 = 0x24;
+const KIND_BR_OPERANDS: u8 = 0x25;
+const KIND_LOOP_HIT: u8 = 0x30;
+const KIND_LOOP_EXCEED: u8 = 0x31;
+const KIND_LOOP_OUT: u8 = 0x32;
+const KIND_LOOP_NO_START: u8 = 0x33;
+const KIND_RECUR_LOCKED: u8 = 0x40;
+const KIND_RECUR_RELEASED: u8 = 0x41;
+const KIND_THREAD: u8 = 0x50;
+const KIND_RAW: u8 = 0x60;
+
+/// size of an event record following the kind byte
+const EVENT_BODY_LEN: usize = 21;
+
+/// A decoded record.
+/// Function entries need the context of the tree to create the child node, so they are left to
+/// the tree builder. Operands are attached to the following branch guard by the tree builder as
+/// well. Raw records are text lines the runtime failed to encode.
+pub enum BinRecord {
+    Act(ExecAction),
+    Enter {
+        invoc_loc: Option<SrcLoc>,
+        func_name: String,
+    },
+    Operands(BrOperands),
+    Raw(String),
+}
+
+struct BinEvent {
+    kind: u8,
+    flag: u8,
+    a: u32,
+    b: u32,
+    c: u32,
+    num: u64,
+}
+
+impl BinEvent {
+    fn from_bytes(kind: u8, buf: &[u8; EVENT_BODY_LEN]) -> Self {
+        let read_u32 = |off: usize| u32::from_le_bytes(buf[off..off + 4].try_into().unwrap());
+        Self {
+            kind,
+            flag: buf[0],
+            a: read_u32(1),
+            b: read_u32(5),
+            c: read_u32(9),
+            num: u64::from_le_bytes(buf[13..21].try_into().unwrap()),
+        }
+    }
+
+    fn get_cond_val(&self) -> Result<bool> {
+        match self.flag {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => bail!("Unexpected condition value: {}", self.flag),
+        }
+    }
+}
+
+/// Streaming reader of a binary guard file, yielding records in execution order.
+pub struct BinTraceReader<R: Read> {
+    reader: R,
+    /// string table, string with id `i` is stored at `i - 1`
+    str_table: Vec<String>,
+    /// parsed locations of the string table
+    loc_cache: Vec<Option<SrcLoc>>,
+    /// number of bytes consumed
+    pos: u64,
+}
+
+impl<R: Read> BinTraceReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != BIN_TRACE_MAGIC {
+            bail!("Not a binary trace, magic: {:?}", magic);
+        }
+        let mut ver_buf = [0u8; 4];
+        reader.read_exact(&mut ver_buf)?;
+        let version = u32::from_le_bytes(ver_buf);
+        if version != BIN_TRACE_VERSION {
+            bail!("Unsupported binary trace version: {}", version);
+        }
+
+        Ok(Self {
+            reader,
+            str_table: vec![],
+            loc_cache: vec![],
+            pos: (BIN_TRACE_MAGIC.len() + 4) as u64,
+        })
+    }
+
+    /// Offset of the next record in the file
+    pub fn get_pos(&self) -> u64 {
+        self.pos
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.reader.read_exact(buf)?;
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Read a u8 at the boundary of records, return None at the end of file.
+    fn read_kind(&mut self) -> Result<Option<u8>> {
+        let mut buf = [0u8; 1];
+        loop {
+            match self.reader.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {
+                    self.pos += 1;
+                    return Ok(Some(buf[0]));
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        self.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_u32()? as usize;
+        let mut buf = vec![0u8; len];
+        self.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_str_def(&mut self) -> Result<()> {
+        let id = self.read_u32()? as usize;
+        let buf = self.read_bytes()?;
+        if id != self.str_table.len() + 1 {
+            bail!(
+                "Unexpected string id {}, expected {}",
+                id,
+                self.str_table.len() + 1
+            );
+        }
+        self.str_table.push(String::from_utf8(buf)?);
+        self.loc_cache.push(None);
+        Ok(())
+    }
+
+    fn get_str(&self, id: u32) -> Result<&str> {
+        if id == 0 {
+            bail!("Missing string in binary record");
+        }
+        self.str_table
+            .get(id as usize - 1)
+            .map(|s| s.as_str())
+            .ok_or_else(|| eyre::eyre!("Undefined string id: {}", id))
+    }
+
+    /// function name without the argument part, which is the same as the text parser
+    fn get_func_name(&self, id: u32) -> Result<String> {
+        let name = self.get_str(id)?;
+        let end = name.find('(').unwrap_or(name.len());
+        Ok(name[..end].to_owned())
+    }
+
+    fn get_loc(&mut self, id: u32) -> Result<SrcLoc> {
+        let idx = (id as usize)
+            .checked_sub(1)
+            .ok_or_else(|| eyre::eyre!("Missing location in binary record"))?;
+        if let Some(Some(loc)) = self.loc_cache.get(idx) {
+            return Ok(loc.clone());
+        }
+        let loc = SrcLoc::from_str(self.get_str(id)?)?;
+        self.loc_cache[idx] = Some(loc.clone());
+        Ok(loc)
+    }
+
+    /// operands event: flag = operand kind, a = value location, b = predicate, c = integer bit
+    /// width, num = lhs
+    fn decode_operands(&mut self, ev: BinEvent, rhs: u64) -> Result<BinRecord> {
+        let operands = BrOperands::new(
+            self.get_loc(ev.a)?,
+            self.get_str(ev.b)?.to_owned(),
+            OperandVal::from_bits(ev.flag, ev.num)?,
+            OperandVal::from_bits(ev.flag, rhs)?,
+        )
+        .with_width(u8::try_from(ev.c)?);
+        Ok(BinRecord::Operands(operands))
+    }
+
+    fn decode_event(&mut self, ev: BinEvent) -> Result<BinRecord> {
+        let act = match ev.kind {
+            KIND_ENTER => {
+                let invoc_loc = if ev.b == 0 {
+                    None
+                } else {
+                    Some(self.get_loc(ev.b)?)
+                };
+                return Ok(BinRecord::Enter {
+                    invoc_loc,
+                    func_name: self.get_func_name(ev.a)?,
+                });
+            }
+            KIND_RETURN => ExecAction::Func(FuncAction::new(
+                FuncActionType::Return,
+                self.get_func_name(ev.a)?,
+            )),
+            KIND_UNWIND => ExecAction::Func(FuncAction::new(
+                FuncActionType::Unwind,
+                self.get_func_name(ev.a)?,
+            )),
+            KIND_BR_GUARD | KIND_MERGE_BR_GUARD | KIND_SWITCH_GUARD | KIND_INDIRECT_GUARD => {
+                let intra_type = match ev.kind {
+                    KIND_BR_GUARD => JumpActionType::Br {
+                        val_loc: self.get_loc(ev.a)?,
+                    },
+                    KIND_MERGE_BR_GUARD => JumpActionType::MergeBr,
+                    KIND_SWITCH_GUARD => JumpActionType::Switch,
+                    _ => JumpActionType::Indirect,
+                };
+                ExecAction::Intra(JumpAction::new(
+                    intra_type,
+                    self.get_loc(ev.b)?,
+                    ev.get_cond_val()?,
+                    self.get_loc(ev.c)?,
+                ))
+            }
+            KIND_VALUE => ExecAction::Value(UBVHit::new(self.get_loc(ev.a)?)),
+            KIND_LOOP_HIT | KIND_LOOP_EXCEED => ExecAction::Loop(LoopAction::new_entry(
+                self.get_loc(ev.a)?,
+                ev.num as usize,
+                ev.kind == KIND_LOOP_EXCEED,
+            )),
+            KIND_LOOP_OUT => ExecAction::Loop(LoopAction::new_end(
+                self.get_loc(ev.a)?,
+                self.get_loc(ev.b)?,
+                Some(ev.num as usize),
+            )),
+            KIND_LOOP_NO_START => ExecAction::Loop(LoopAction::new_end(
+                self.get_loc(ev.a)?,
+                self.get_loc(ev.b)?,
+                None,
+            )),
+            KIND_RECUR_LOCKED => ExecAction::Recur(RecurAction::Locked),
+            KIND_RECUR_RELEASED => ExecAction::Recur(RecurAction::Released),
+            KIND_THREAD => {
+                ExecAction::Thread(ThreadAction::new(self.get_loc(ev.a)?, ev.num as usize))
+            }
+            kind => bail!("Unknown binary record kind: {:#x}", kind),
+        };
+        Ok(BinRecord::Act(act))
+    }
+
+    /// Read the next record, return None at the end of file.
+    pub fn read_record(&mut self) -> Result<Option<BinRecord>> {
+        loop {
+            let kind = match self.read_kind()? {
+                Some(kind) => kind,
+                None => return Ok(None),
+            };
+            if kind == KIND_STR_DEF {
+                self.read_str_def()?;
+                continue;
+            }
+            if kind == KIND_RAW {
+                let line = String::from_utf8(self.read_bytes()?)?;
+                return Ok(Some(BinRecord::Raw(line)));
+            }
+
+            let mut buf = [0u8; EVENT_BODY_LEN];
+            self.read_exact(&mut buf)?;
+            let ev = BinEvent::from_bytes(kind, &buf);
+            let rec = if kind == KIND_BR_OPERANDS {
+                let mut rhs_buf = [0u8; 8];
+                self.read_exact(&mut rhs_buf)?;
+                self.decode_operands(ev, u64::from_le_bytes(rhs_buf))?
+            } else {
+                self.decode_event(ev)?
+            };
+            return Ok(Some(rec));
+        }
+    }
+
+    /// Skip the records before `offset`, only string definitions are decoded.
+    pub fn skip_to(&mut self, offset: u64) -> Result<()> {
+        let mut buf = [0u8; EVENT_BODY_LEN + 8];
+        while self.pos < offset {
+            let kind = match self.read_kind()? {
+                Some(kind) => kind,
+                None => break,
+            };
+            let body_len = match kind {
+                KIND_STR_DEF => {
+                    self.read_str_def()?;
+                    continue;
+                }
+                KIND_RAW => {
+                    self.read_bytes()?;
+                    continue;
+                }
+                KIND_BR_OPERANDS => EVENT_BODY_LEN + 8,
+                _ => EVENT_BODY_LEN,
+            };
+            self.read_exact(&mut buf[..body_len])?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for BinTraceReader<R> {
+    type Item = Result<BinRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Check whether the guard file is in binary format by its magic.
+pub fn is_bin_trace<P: AsRef<Path>>(fpath: P) -> Result<bool> {
+    let mut file = File::open(fpath.as_ref())?;
+    let mut magic = [0u8; 4];
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == BIN_TRACE_MAGIC),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    fn put_str(buf: &mut Vec<u8>, id: u32, s: &str) {
+        buf.push(KIND_STR_DEF);
+        buf.extend(id.to_le_bytes());
+        buf.extend((s.len() as u32).to_le_bytes());
+        buf.extend(s.as_bytes());
+    }
+
+    fn put_event(buf: &mut Vec<u8>, kind: u8, flag: u8, a: u32, b: u32, c: u32, num: u64) {
+        buf.push(kind);
+        buf.push(flag);
+        buf.extend(a.to_le_bytes());
+        buf.extend(b.to_le_bytes());
+        buf.extend(c.to_le_bytes());
+        buf.extend(num.to_le_bytes());
+    }
+
+    #[test]
+    fn test_bin_trace_reader() -> Result<()> {
+        let mut buf = BIN_TRACE_MAGIC.to_vec();
+        buf.extend(BIN_TRACE_VERSION.to_le_bytes());
+        put_str(&mut buf, 1, "/src/a.c:3:5");
+        put_str(&mut buf, 2, "foo(int)");
+        put_event(&mut buf, KIND_ENTER, 0, 2, 1, 0, 0);
+        put_str(&mut buf, 3, "/src/a.c:10:7");
+        put_str(&mut buf, 4, "/src/a.c:11:3");
+        put_event(&mut buf, KIND_BR_GUARD, 1, 3, 3, 4, 0);
+        put_event(&mut buf, KIND_LOOP_OUT, 0, 3, 4, 0, 2);
+        let ret_offset = buf.len() as u64;
+        put_event(&mut buf, KIND_RETURN, 0, 2, 0, 0, 0);
+        put_str(&mut buf, 5, "ult");
+        put_event(&mut buf, KIND_BR_OPERANDS, b'u', 3, 5, 32, 7);
+        buf.extend(9u64.to_le_bytes());
+
+        let reader = BinTraceReader::new(buf.as_slice())?;
+        let recs = reader.collect::<Result<Vec<_>>>()?;
+        assert_eq!(recs.len(), 5);
+
+        match &recs[0] {
+            BinRecord::Enter {
+                invoc_loc,
+                func_name,
+            } => {
+                assert_eq!(func_name, "foo");
+                assert_eq!(invoc_loc.as_ref(), Some(&SrcLoc::from_str("/src/a.c:3:5")?));
+            }
+            _ => panic!("expect a function entry"),
+        }
+        match &recs[1] {
+            BinRecord::Act(ExecAction::Intra(jump_act)) => {
+                assert!(jump_act.get_cond_val());
+                assert_eq!(jump_act.get_dest_loc(), &SrcLoc::from_str("/src/a.c:11:3")?);
+            }
+            _ => panic!("expect a branch guard"),
+        }
+        match &recs[2] {
+            BinRecord::Act(ExecAction::Loop(loop_act)) => {
+                assert!(loop_act.is_loop_end());
+                assert_eq!(loop_act.get_count(), Some(2));
+            }
+            _ => panic!("expect a loop end"),
+        }
+        match &recs[3] {
+            BinRecord::Act(ExecAction::Func(func_act)) => {
+                assert!(func_act.is_return());
+                assert_eq!(func_act.get_name(), "foo");
+            }
+            _ => panic!("expect a function return"),
+        }
+        match &recs[4] {
+            BinRecord::Operands(operands) => {
+                assert_eq!(operands.get_pred(), "ult");
+                assert_eq!(operands.get_lhs(), OperandVal::Unsigned(7));
+                assert_eq!(operands.get_rhs(), OperandVal::Unsigned(9));
+                assert_eq!(operands.get_width(), Some(32));
+            }
+            _ => panic!("expect branch operands"),
+        }
+
+        // strings defined in the skipped records are still available
+        let mut reader = BinTraceReader::new(buf.as_slice())?;
+        reader.skip_to(ret_offset)?;
+        assert_eq!(reader.get_pos(), ret_offset);
+        assert_eq!(reader.collect::<Result<Vec<_>>>()?.len(), 2);
+        Ok(())
+    }
+
+    /// Encode events with the runtime encoder of func_stack_pass and read them back, skipped
+    /// without a C++ compiler.
+    #[test]
+    fn test_bin_trace_round_trip() -> Result<()> {
+        let fsp_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../func_stack_pass");
+        let work_dir = tempfile::tempdir()?;
+        // the only macro of the configured header used by the encoder
+        std::fs::write(
+            work_dir.path().join("config.h"),
+            "#define FORMAT_ENV_VAR \"FUNC_STACK_FORMAT\"\n",
+        )?;
+        let encoder = work_dir.path().join("bin_trace_encode");
+        let cxx = std::env::var("CXX").unwrap_or_else(|_| "c++".to_owned());
+        let status = Command::new(&cxx)
+            .arg("-std=c++17")
+            .arg("-I")
+            .arg(work_dir.path())
+            .arg("-I")
+            .arg(fsp_dir.join("src"))
+            .arg(fsp_dir.join("src/runtime/bin_trace.cc"))
+            .arg(fsp_dir.join("tests/bin_trace_encode.cc"))
+            .arg("-o")
+            .arg(&encoder)
+            .status();
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                log::warn!("Round trip skipped, failed to run {}: {}", cxx, e);
+                return Ok(());
+            }
+        };
+        assert!(status.success());
+        let trace_path = work_dir.path().join("1_main");
+        assert!(Command::new(&encoder).arg(&trace_path).status()?.success());
+        assert!(is_bin_trace(&trace_path)?);
+
+        let mut reader = BinTraceReader::new(File::open(&trace_path)?)?;
+        let recs = reader.by_ref().collect::<Result<Vec<_>>>()?;
+        assert_eq!(recs.len(), 10);
+        match &recs[0] {
+            BinRecord::Enter {
+                invoc_loc,
+                func_name,
+            } => {
+                assert_eq!(func_name, "foo");
+                assert_eq!(invoc_loc.as_ref(), Some(&SrcLoc::from_str("/src/a.c:3:5")?));
+            }
+            _ => panic!("expect a function entry"),
+        }
+        match &recs[1] {
+            BinRecord::Operands(operands) => {
+                assert_eq!(operands.get_pred(), "slt");
+                assert_eq!(operands.get_lhs(), OperandVal::Signed(-1));
+                assert_eq!(operands.get_rhs(), OperandVal::Signed(4));
+                assert_eq!(operands.get_width(), Some(32));
+            }
+            _ => panic!("expect branch operands"),
+        }
+        // a guard written in pieces is encoded once its line ends
+        match &recs[2] {
+            BinRecord::Act(ExecAction::Intra(jump_act)) => {
+                assert!(jump_act.get_cond_val());
+                assert_eq!(jump_act.get_dest_loc(), &SrcLoc::from_str("/src/a.c:11:3")?);
+            }
+            _ => panic!("expect a branch guard"),
+        }
+        match &recs[4] {
+            BinRecord::Act(ExecAction::Loop(loop_act)) => {
+                assert!(loop_act.is_loop_end());
+                assert_eq!(loop_act.get_count(), Some(2));
+            }
+            _ => panic!("expect a loop end"),
+        }
+        match &recs[5] {
+            BinRecord::Raw(line) => assert_eq!(line, "Unknown Guard: /src/a.c:13:1"),
+            _ => panic!("expect a raw record"),
+        }
+        assert!(matches!(
+            recs[6],
+            BinRecord::Act(ExecAction::Recur(RecurAction::Locked))
+        ));
+        match &recs[7] {
+            BinRecord::Act(ExecAction::Thread(thread_act)) => {
+                assert_eq!(thread_act.get_thread_id(), 7);
+            }
+            _ => panic!("expect a thread creation"),
+        }
+        assert!(matches!(recs[8], BinRecord::Act(ExecAction::Value(_))));
+        match &recs[9] {
+            BinRecord::Act(ExecAction::Func(func_act)) => {
+                assert!(func_act.is_return());
+                assert_eq!(func_act.get_name(), "foo");
+            }
+            _ => panic!("expect a function return"),
+        }
+        // raw text is not interned and repeated strings are defined once
+        assert_eq!(reader.str_table.len(), 9);
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+//! First divergence between two executions, which explains why an input misses a branch reached
+//! by a near-identical one.
+
+use std::fmt;
+
+use color_eyre::eyre::Result;
+
+use crate::analysis::constraint::{
+    exec_rec::ExecRec,
+    inter::{
+        exec_tree::{
+            action::{ExecAction, JumpAction},
+            thread_tree::{SharedFuncNodePtr, ThreadExecTree},
+        },
+        loc::SrcLoc,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivergeKind {
+    /// the same branch is taken in different directions
+    Branch,
+    /// different branches or calls are reached
+    Path,
+    /// the actions of one side end earlier
+    End,
+}
+
+/// Diverging point of one execution.
+#[derive(Clone)]
+pub struct DivergeSide {
+    /// function names from the outermost call to the diverging function
+    stack: Vec<String>,
+    /// the diverging action, None if the actions of the function end
+    act: Option<ExecAction>,
+}
+
+impl DivergeSide {
+    pub fn get_stack(&self) -> &[String] {
+        &self.stack
+    }
+
+    pub fn get_act(&self) -> Option<&ExecAction> {
+        self.act.as_ref()
+    }
+
+    pub fn get_jump(&self) -> Option<&JumpAction> {
+        match &self.act {
+            Some(ExecAction::Intra(jump_act)) => Some(jump_act),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DivergeSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.stack.join(" -> "))?;
+        match &self.act {
+            Some(act) => write!(f, "{:?}", act),
+            None => write!(f, "end of actions"),
+        }
+    }
+}
+
+pub struct Divergence {
+    kind: DivergeKind,
+    left: DivergeSide,
+    right: DivergeSide,
+    /// location of the diverging branch condition
+    cond_loc: Option<SrcLoc>,
+    /// source text of the diverging branch condition
+    cond_text: Option<String>,
+}
+
+impl Divergence {
+    fn new(kind: DivergeKind, left: DivergeSide, right: DivergeSide) -> Self {
+        let cond_loc =
+            left.get_jump()
+                .or(right.get_jump())
+                .map(|jump_act| match jump_act.get_val_loc() {
+                    Some(val_loc) => val_loc.clone(),
+                    None => jump_act.get_from_loc().clone(),
+                });
+        let cond_text = cond_loc.as_ref().and_then(|loc| match loc.get_line_text() {
+            Ok(text) => Some(text),
+            Err(e) => {
+                log::debug!("Failed to get source text of {}: {}", loc, e);
+                None
+            }
+        });
+        Self {
+            kind,
+            left,
+            right,
+            cond_loc,
+            cond_text,
+        }
+    }
+
+    pub fn get_kind(&self) -> DivergeKind {
+        self.kind
+    }
+
+    pub fn get_left(&self) -> &DivergeSide {
+        &self.left
+    }
+
+    pub fn get_right(&self) -> &DivergeSide {
+        &self.right
+    }
+
+    pub fn get_cond_loc(&self) -> Option<&SrcLoc> {
+        self.cond_loc.as_ref()
+    }
+
+    pub fn get_cond_text(&self) -> Option<&str> {
+        self.cond_text.as_deref()
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} divergence", self.kind)?;
+        if let Some(loc) = &self.cond_loc {
+            write!(f, " at {}", loc)?;
+        }
+        if let Some(text) = &self.cond_text {
+            write!(f, ": {}", text)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  left:  {}", self.left)?;
+        writeln!(f, "  right: {}", self.right)
+    }
+}
+
+/// Jumps and function actions are aligned, other actions are only records of values.
+fn is_aligned(act: &ExecAction) -> bool {
+    matches!(act, ExecAction::Intra(_) | ExecAction::Func(_))
+}
+
+fn same_jump(a: &JumpAction, b: &JumpAction) -> bool {
+    a.get_from_loc() == b.get_from_loc() && a.get_val_loc() == b.get_val_loc()
+}
+
+fn get_frame_name(node_ptr: &SharedFuncNodePtr) -> String {
+    node_ptr.borrow().get_func_name_or_init().to_owned()
+}
+
+fn diverge_at(
+    kind: DivergeKind,
+    left_stack: &[String],
+    left_act: Option<&ExecAction>,
+    right_stack: &[String],
+    right_act: Option<&ExecAction>,
+) -> Option<Divergence> {
+    let left = DivergeSide {
+        stack: left_stack.to_vec(),
+        act: left_act.cloned(),
+    };
+    let right = DivergeSide {
+        stack: right_stack.to_vec(),
+        act: right_act.cloned(),
+    };
+    Some(Divergence::new(kind, left, right))
+}
+
+/// Walk the actions of two function nodes in lockstep, descending into calls of the same
+/// function on both sides.
+fn align_nodes(
+    left_ptr: &SharedFuncNodePtr,
+    right_ptr: &SharedFuncNodePtr,
+    left_stack: &mut Vec<String>,
+    right_stack: &mut Vec<String>,
+) -> Option<Divergence> {
+    left_stack.push(get_frame_name(left_ptr));
+    right_stack.push(get_frame_name(right_ptr));
+
+    let left_node = left_ptr.borrow();
+    let right_node = right_ptr.borrow();
+    let mut left_iter = left_node.iter_acts().filter(|act| is_aligned(act));
+    let mut right_iter = right_node.iter_acts().filter(|act| is_aligned(act));
+
+    let res = loop {
+        let (left_act, right_act) = match (left_iter.next(), right_iter.next()) {
+            (None, None) => break None,
+            (Some(left_act), Some(right_act)) => (left_act, right_act),
+            (left_act, right_act) => {
+                break diverge_at(
+                    DivergeKind::End,
+                    left_stack,
+                    left_act,
+                    right_stack,
+                    right_act,
+                )
+            }
+        };
+        match (left_act, right_act) {
+            (ExecAction::Intra(left_jump), ExecAction::Intra(right_jump)) => {
+                if !same_jump(left_jump, right_jump) {
+                    break diverge_at(
+                        DivergeKind::Path,
+                        left_stack,
+                        Some(left_act),
+                        right_stack,
+                        Some(right_act),
+                    );
+                }
+                if left_jump.get_cond_val() != right_jump.get_cond_val()
+                    || left_jump.get_dest_loc() != right_jump.get_dest_loc()
+                {
+                    break diverge_at(
+                        DivergeKind::Branch,
+                        left_stack,
+                        Some(left_act),
+                        right_stack,
+                        Some(right_act),
+                    );
+                }
+            }
+            (ExecAction::Func(left_func), ExecAction::Func(right_func))
+                if left_func.get_name() == right_func.get_name()
+                    && left_func.is_call() == right_func.is_call() =>
+            {
+                let child_ptrs = left_func.get_child_ptr().zip(right_func.get_child_ptr());
+                if let Some((left_child, right_child)) = child_ptrs {
+                    let res = align_nodes(&left_child, &right_child, left_stack, right_stack);
+                    if res.is_some() {
+                        break res;
+                    }
+                }
+            }
+            _ => {
+                break diverge_at(
+                    DivergeKind::Path,
+                    left_stack,
+                    Some(left_act),
+                    right_stack,
+                    Some(right_act),
+                )
+            }
+        }
+    };
+
+    left_stack.pop();
+    right_stack.pop();
+    res
+}
+
+/// First divergence of the actions under two roots, None if they are aligned throughout.
+pub fn find_first_divergence(
+    left_root: &SharedFuncNodePtr,
+    right_root: &SharedFuncNodePtr,
+) -> Option<Divergence> {
+    align_nodes(left_root, right_root, &mut vec![], &mut vec![])
+}
+
+impl ThreadExecTree {
+    /// First divergence from another thread tree, None if they are aligned throughout.
+    pub fn find_first_divergence(&self, other: &ThreadExecTree) -> Option<Divergence> {
+        find_first_divergence(&self.get_root_ptr(), &other.get_root_ptr())
+    }
+}
+
+impl ExecRec {
+    /// First divergence of the main threads from another execution.
+    pub fn find_first_divergence(&self, other: &ExecRec) -> Result<Option<Divergence>> {
+        let left_root = self.get_exec_forest()?.get_main_root_ptr();
+        let right_root = other.get_exec_forest()?.get_main_root_ptr();
+        Ok(find_first_divergence(&left_root, &right_root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{add_expe_exec, cov_export};
+
+    #[test]
+    fn test_first_divergence() -> Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let src = work_dir.path().join("a.c");
+        std::fs::write(
+            &src,
+            "int parse(int len) {\n  if (len > 4)\n    return 1;\n  return 0;\n}\n",
+        )?;
+        let src = src.display();
+        let guard_lines = |cond_val: usize, dest_line: usize| {
+            [
+                "enter main".to_owned(),
+                "Unconditional Branch Value: /src/main.c:3:5".to_owned(),
+                "enter parse".to_owned(),
+                format!("Br Guard: {src}:2:11 {src}:2:3 {cond_val} {src}:{dest_line}:5"),
+                "return from parse".to_owned(),
+                "return from main".to_owned(),
+            ]
+        };
+        let cov = cov_export(json!([]));
+        let left = add_expe_exec(work_dir.path(), "left", &cov, &guard_lines(1, 3))?;
+        let right = add_expe_exec(work_dir.path(), "right", &cov, &guard_lines(0, 4))?;
+
+        assert!(left.find_first_divergence(&left)?.is_none());
+        let div = left.find_first_divergence(&right)?.unwrap();
+        assert_eq!(div.get_kind(), DivergeKind::Branch);
+        assert_eq!(div.get_left().get_stack(), ["_init", "main", "parse"]);
+        assert!(div.get_left().get_jump().unwrap().get_cond_val());
+        assert!(!div.get_right().get_jump().unwrap().get_cond_val());
+        assert_eq!(div.get_cond_text(), Some("if (len > 4)"));
+        Ok(())
+    }
+}
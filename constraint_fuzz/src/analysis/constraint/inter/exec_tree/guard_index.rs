@@ -0,0 +1,206 @@
+//! Index of function entries in a guard file, used to materialize only the parts of the
+//! execution tree related to a target function.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::Path,
+};
+
+use color_eyre::eyre::Result;
+
+use crate::analysis::constraint::inter::exec_tree::{
+    action::{ExecAction, FuncActionType},
+    binary::{is_bin_trace, BinRecord, BinTraceReader},
+};
+
+/// A guard record in either text or binary format.
+pub enum GuardRecord {
+    Line(String),
+    Bin(BinRecord),
+}
+
+impl GuardRecord {
+    /// function name if the record is a function entry
+    fn get_entry_name(&self) -> Option<&str> {
+        match self {
+            GuardRecord::Line(line) => FuncActionType::get_func_name_from_call_guard(line),
+            GuardRecord::Bin(BinRecord::Enter { func_name, .. }) => Some(func_name),
+            GuardRecord::Bin(BinRecord::Raw(line)) => {
+                FuncActionType::get_func_name_from_call_guard(line)
+            }
+            GuardRecord::Bin(_) => None,
+        }
+    }
+
+    /// Only returns move up in the execution tree, unwinds don't.
+    fn is_return(&self) -> bool {
+        match self {
+            GuardRecord::Line(line) => FuncActionType::is_return_guard(line),
+            GuardRecord::Bin(BinRecord::Act(ExecAction::Func(func_act))) => func_act.is_return(),
+            GuardRecord::Bin(BinRecord::Raw(line)) => FuncActionType::is_return_guard(line),
+            GuardRecord::Bin(_) => false,
+        }
+    }
+}
+
+/// Sequential reader of guard records with their offsets in the file.
+pub enum GuardSource {
+    Text { reader: BufReader<File>, pos: u64 },
+    Bin(BinTraceReader<BufReader<File>>),
+}
+
+impl GuardSource {
+    pub fn open<P: AsRef<Path>>(fpath: P) -> Result<Self> {
+        let reader = BufReader::new(File::open(fpath.as_ref())?);
+        if is_bin_trace(fpath.as_ref())? {
+            return Ok(GuardSource::Bin(BinTraceReader::new(reader)?));
+        }
+        Ok(GuardSource::Text { reader, pos: 0 })
+    }
+
+    /// Read the next record with its offset, return None at the end of file.
+    pub fn next_record(&mut self) -> Result<Option<(u64, GuardRecord)>> {
+        match self {
+            GuardSource::Text { reader, pos } => {
+                let mut buf = vec![];
+                let len = reader.read_until(b'\n', &mut buf)?;
+                if len == 0 {
+                    return Ok(None);
+                }
+                let offset = *pos;
+                *pos += len as u64;
+                // same as `BufRead::lines`
+                if buf.ends_with(b"\n") {
+                    buf.pop();
+                    if buf.ends_with(b"\r") {
+                        buf.pop();
+                    }
+                }
+                // corrupted bytes are left to the guard parsers instead of failing the read
+                let line = String::from_utf8(buf)
+                    .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+                Ok(Some((offset, GuardRecord::Line(line))))
+            }
+            GuardSource::Bin(reader) => {
+                let offset = reader.get_pos();
+                let rec_op = reader.read_record()?;
+                Ok(rec_op.map(|rec| (offset, GuardRecord::Bin(rec))))
+            }
+        }
+    }
+
+    /// Skip the records before `offset`.
+    /// Text files are sought directly. Binary files can't be sought since the string definitions
+    /// in between are referred to later, so the skipped events are read without being decoded.
+    pub fn skip_to(&mut self, offset: u64) -> Result<()> {
+        match self {
+            GuardSource::Text { reader, pos } => {
+                if offset > *pos {
+                    reader.seek(SeekFrom::Start(offset))?;
+                    *pos = offset;
+                }
+            }
+            GuardSource::Bin(reader) => reader.skip_to(offset)?,
+        }
+        Ok(())
+    }
+}
+
+struct EntryIdx {
+    offset: u64,
+    /// offset of the corresponding return record, None if the function never returns
+    ret_offset: Option<u64>,
+    parent: Option<usize>,
+    /// the function or its caller chain is the target function
+    in_target: bool,
+    /// the function should be materialized
+    needed: bool,
+}
+
+/// Function entries of a guard file in execution order.
+/// An entry is needed if it is inside a call of the target function, or it leads to one.
+pub struct GuardIndex {
+    entries: Vec<EntryIdx>,
+}
+
+impl GuardIndex {
+    pub fn from_guard_file<P: AsRef<Path>>(fpath: P, target_func: &str) -> Result<Self> {
+        let mut source = GuardSource::open(fpath.as_ref())?;
+        let mut entries: Vec<EntryIdx> = vec![];
+        let mut stack: Vec<usize> = vec![];
+
+        loop {
+            let (offset, rec) = match source.next_record() {
+                Ok(Some(item)) => item,
+                Ok(None) => break,
+                Err(e) => {
+                    // entries after a corrupted record are not indexed
+                    log::warn!("Stop indexing guard file {:?}: {}", fpath.as_ref(), e);
+                    break;
+                }
+            };
+            if let Some(func_name) = rec.get_entry_name() {
+                let parent = stack.last().copied();
+                let in_target =
+                    func_name == target_func || parent.is_some_and(|idx| entries[idx].in_target);
+                stack.push(entries.len());
+                entries.push(EntryIdx {
+                    offset,
+                    ret_offset: None,
+                    parent,
+                    in_target,
+                    needed: false,
+                });
+            } else if rec.is_return() {
+                if let Some(idx) = stack.pop() {
+                    entries[idx].ret_offset = Some(offset);
+                }
+            }
+        }
+
+        // mark the target subtrees and their caller chains
+        for idx in 0..entries.len() {
+            if !entries[idx].in_target {
+                continue;
+            }
+            let mut cur = Some(idx);
+            while let Some(cur_idx) = cur {
+                if entries[cur_idx].needed {
+                    break;
+                }
+                entries[cur_idx].needed = true;
+                cur = entries[cur_idx].parent;
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn count_needed(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.needed).count()
+    }
+
+    /// index of the entry starting at `offset`
+    pub fn find_entry(&self, offset: u64) -> Option<usize> {
+        self.entries
+            .binary_search_by_key(&offset, |entry| entry.offset)
+            .ok()
+    }
+
+    pub fn is_needed(&self, idx: usize) -> bool {
+        self.entries[idx].needed
+    }
+
+    pub fn get_ret_offset(&self, idx: usize) -> Option<u64> {
+        self.entries[idx].ret_offset
+    }
+}
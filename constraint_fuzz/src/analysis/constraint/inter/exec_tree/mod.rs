@@ -1,13 +1,26 @@
-use std::{collections::HashMap, fs::read_dir, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_dir,
+    path::Path,
+};
 
 use color_eyre::eyre::Result;
 
-use crate::analysis::constraint::inter::exec_tree::thread_tree::{
-    SharedFuncNodePtr, ThreadExecTree, Tid, THCPMAPPING,
+use crate::{
+    analysis::constraint::inter::exec_tree::{
+        action::ExecAction,
+        thread_tree::{
+            ActionPoint, GuardDamage, SharedFuncNodePtr, ThreadExecTree, Tid, THCPMAPPING,
+        },
+    },
+    feedback::branches::constraints::UBConstraint,
 };
 
 pub mod action;
 pub mod analyze;
+pub mod binary;
+pub mod diverge;
+pub mod guard_index;
 pub mod thread_tree;
 
 pub struct ExecForest {
@@ -15,6 +28,8 @@ pub struct ExecForest {
     thcp_mapping: THCPMAPPING,
     /// tid to idx mapping
     tid_mapping: HashMap<Tid, usize>,
+    /// threads whose creation actions are linked to their roots
+    linked_tids: HashSet<Tid>,
 
     thread_tree_list: Vec<ThreadExecTree>,
     main_idx: usize,
@@ -40,7 +55,11 @@ impl ExecForest {
         Ok(fname.ends_with(MAIN_SUFFIX))
     }
 
-    pub fn from_guard_dir<P: AsRef<Path>>(guard_dir: P) -> Result<Self> {
+    fn from_guard_dir_impl<P, F>(guard_dir: P, mut build_tree: F) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path) -> Result<(ThreadExecTree, THCPMAPPING)>,
+    {
         assert!(guard_dir.as_ref().is_dir());
         let mut tree_list = vec![];
         let mut thcp_mapping = HashMap::new();
@@ -48,7 +67,7 @@ impl ExecForest {
 
         let mut tid_mapping = HashMap::new();
 
-        for ent_res in read_dir(guard_dir)? {
+        for ent_res in read_dir(guard_dir.as_ref())? {
             let ent = ent_res?;
             let guard_fpath = ent.path();
 
@@ -56,21 +75,144 @@ impl ExecForest {
                 idx = tree_list.len();
             }
 
-            let (tree, sub_mapping) = ThreadExecTree::from_guard_file(&guard_fpath)?;
+            let (tree, sub_mapping) = build_tree(&guard_fpath)?;
 
             let tid = tree.get_tid();
             tid_mapping.insert(tid, tree_list.len());
             tree_list.push(tree);
-            thcp_mapping.extend(sub_mapping);
+            for (sub_tid, act_point) in sub_mapping {
+                if sub_tid == tid {
+                    log::warn!("Thread {} creates a thread with its own id", tid);
+                    continue;
+                }
+                thcp_mapping.insert(sub_tid, act_point);
+            }
         }
-        Ok(Self {
+        let mut forest = Self {
             thcp_mapping,
             tid_mapping,
+            linked_tids: HashSet::new(),
             thread_tree_list: tree_list,
             main_idx: idx,
+        };
+        forest.link_threads();
+        if forest.is_incomplete() {
+            log::warn!(
+                "Guard files in {:?} are damaged: {}",
+                guard_dir.as_ref(),
+                forest.get_damage()
+            );
+        }
+        Ok(forest)
+    }
+
+    /// Link thread creation actions to the roots of the spawned thread trees.
+    fn link_threads(&mut self) {
+        for (tid, act_point) in self.thcp_mapping.iter() {
+            let root_ptr = match self.get_thread_root_ptr(*tid) {
+                Some(ptr) => ptr,
+                None => {
+                    log::debug!("Guard file of thread {} not found", tid);
+                    continue;
+                }
+            };
+            let func_node_ptr = act_point.get_func_node_ptr();
+            let mut func_node = func_node_ptr.borrow_mut();
+            match func_node.data.get_mut(act_point.get_act_idx()) {
+                Some(ExecAction::Thread(thread_act)) if thread_act.get_thread_id() == *tid => {
+                    thread_act.set_root_ptr(root_ptr);
+                    self.linked_tids.insert(*tid);
+                }
+                _ => log::warn!(
+                    "Creation action of thread {} not found at its action point",
+                    tid
+                ),
+            }
+        }
+    }
+
+    pub fn from_guard_dir<P: AsRef<Path>>(guard_dir: P) -> Result<Self> {
+        Self::from_guard_dir_with_constraint(guard_dir, None, false)
+    }
+
+    /// Build every thread tree, truncated after the `trunc_cnt`-th hit of the constraint if
+    /// specified. `recovery` should be enabled only for executions expected to leave damaged
+    /// guard files, e.g. crashing or timed-out ones.
+    pub fn from_guard_dir_with_constraint<P: AsRef<Path>>(
+        guard_dir: P,
+        cons_op: Option<&UBConstraint>,
+        recovery: bool,
+    ) -> Result<Self> {
+        Self::from_guard_dir_impl(guard_dir, |guard_fpath| {
+            ThreadExecTree::from_guard_file_with_constraint(guard_fpath, cons_op, recovery)
         })
     }
 
+    /// Build the forest on demand: only call chains reaching the function of the constraint and
+    /// the calls inside it are materialized, with at most `node_cap` function nodes.
+    /// Each thread tree is truncated after the `trunc_cnt`-th hit of the constraint.
+    pub fn from_guard_dir_for_cons<P: AsRef<Path>>(
+        guard_dir: P,
+        cons: &UBConstraint,
+        node_cap: usize,
+        recovery: bool,
+    ) -> Result<Self> {
+        let target_func = cons.get_func_name()?;
+        Self::from_guard_dir_for_func(guard_dir, &target_func, Some(cons), node_cap, recovery)
+    }
+
+    /// Build the forest with only the call chains reaching `target_func` and the calls inside
+    /// it, truncated after the `trunc_cnt`-th hit of the constraint if specified.
+    pub fn from_guard_dir_for_func<P: AsRef<Path>>(
+        guard_dir: P,
+        target_func: &str,
+        trunc_cons_op: Option<&UBConstraint>,
+        node_cap: usize,
+        recovery: bool,
+    ) -> Result<Self> {
+        let mut node_budget = node_cap;
+        let forest = Self::from_guard_dir_impl(guard_dir.as_ref(), |guard_fpath| {
+            ThreadExecTree::from_guard_file_for_func(
+                guard_fpath,
+                target_func,
+                trunc_cons_op,
+                recovery,
+                &mut node_budget,
+            )
+        })?;
+        if node_budget == 0 {
+            log::warn!(
+                "Node cap {} reached when building forest for {} from {:?}",
+                node_cap,
+                target_func,
+                guard_dir.as_ref()
+            );
+        }
+        Ok(forest)
+    }
+
+    pub fn get_thread_root_ptr(&self, tid: Tid) -> Option<SharedFuncNodePtr> {
+        let idx = self.tid_mapping.get(&tid)?;
+        Some(self.thread_tree_list[*idx].get_root_ptr())
+    }
+
+    /// action point of the creation of the thread
+    pub fn get_creation_point(&self, tid: Tid) -> Option<&ActionPoint> {
+        self.thcp_mapping.get(&tid)
+    }
+
+    /// Roots not reachable from other threads: the main thread first, then threads whose
+    /// creation actions are missing.
+    pub fn get_top_root_ptrs(&self) -> Vec<SharedFuncNodePtr> {
+        let mut roots = vec![self.get_main_root_ptr()];
+        for (idx, tree) in self.thread_tree_list.iter().enumerate() {
+            if idx != self.main_idx && !self.linked_tids.contains(&tree.get_tid()) {
+                roots.push(tree.get_root_ptr());
+            }
+        }
+        roots
+    }
+
     pub fn iter_trees(&self) -> impl Iterator<Item = &ThreadExecTree> {
         self.thread_tree_list.iter()
     }
@@ -78,4 +220,91 @@ impl ExecForest {
     pub fn len(&self) -> usize {
         self.thread_tree_list.len()
     }
+
+    /// damage of all guard files tolerated when building the thread trees
+    pub fn get_damage(&self) -> GuardDamage {
+        let mut damage = GuardDamage::default();
+        for tree in self.thread_tree_list.iter() {
+            damage.merge(tree.get_damage());
+        }
+        damage
+    }
+
+    /// some thread trees miss actions because of damaged guard files, e.g. of crashing inputs
+    pub fn is_incomplete(&self) -> bool {
+        self.thread_tree_list
+            .iter()
+            .any(|tree| tree.is_incomplete())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_main_guard;
+
+    #[test]
+    fn test_link_threads() -> Result<()> {
+        let guard_dir = tempfile::tempdir()?;
+        let main_lines = [
+            "enter main",
+            "Thread Creation: /src/a.c:5:3 2",
+            "return from main",
+        ];
+        let worker_lines = ["enter worker", "return from worker"];
+        write_main_guard(guard_dir.path(), &main_lines)?;
+        std::fs::write(guard_dir.path().join("2"), worker_lines.join("\n") + "\n")?;
+
+        let forest = ExecForest::from_guard_dir(guard_dir.path())?;
+        assert_eq!(forest.len(), 2);
+        // the worker is reachable from the main thread only
+        assert_eq!(forest.get_top_root_ptrs().len(), 1);
+
+        let func_names: Vec<String> = forest
+            .func_node_bfs_iter()
+            .map(|ptr| ptr.borrow().get_func_name_or_init().to_owned())
+            .collect();
+        assert_eq!(func_names, ["_init", "main", "_init", "worker"]);
+        assert!(!forest.is_incomplete());
+        Ok(())
+    }
+
+    #[test]
+    fn test_damaged_guard_file() -> Result<()> {
+        let guard_dir = tempfile::tempdir()?;
+        let guard_fpath = guard_dir.path().join("1_main");
+        let mut content = [
+            "enter main",
+            "enter parse",
+            "Br Guard: /src/a.c:5",
+            "garbage",
+            "Merge Br Guard: /src/a.c:3:5 1 /src/a.c:4:5",
+            "enter crash",
+        ]
+        .join("\n")
+        .into_bytes();
+        // the execution crashed while writing the record
+        content.extend_from_slice(b"\nUnconditional Branch Value: /src/\xff");
+        std::fs::write(&guard_fpath, content)?;
+
+        // strict mode fails on the malformed records
+        assert!(ThreadExecTree::new(&guard_fpath)?
+            .read_guard_file(&guard_fpath)
+            .is_err());
+
+        assert!(ExecForest::from_guard_dir(guard_dir.path()).is_err());
+        let forest = ExecForest::from_guard_dir_with_constraint(guard_dir.path(), None, true)?;
+        assert!(forest.is_incomplete());
+        let damage = forest.get_damage();
+        assert_eq!(damage.get_skipped_recs(), 3);
+        assert_eq!(damage.get_closed_frames(), 3);
+        assert!(!damage.is_truncated());
+
+        let func_names: Vec<String> = forest
+            .func_node_bfs_iter()
+            .map(|ptr| ptr.borrow().get_func_name_or_init().to_owned())
+            .collect();
+        assert_eq!(func_names, ["_init", "main", "parse", "crash"]);
+        Ok(())
+    }
 }
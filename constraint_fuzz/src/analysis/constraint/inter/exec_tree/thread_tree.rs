@@ -6,26 +6,27 @@ use std::path::PathBuf;
 use std::sync::{Mutex, OnceLock};
 use std::{
     cell::RefCell,
-    fs::File,
-    io::{BufRead, BufReader},
     path::Path,
     rc::{Rc, Weak},
 };
 
 use crate::analysis::constraint::inter::error::GuardParseError;
 use crate::analysis::constraint::inter::exec_tree::action::{
-    get_prefix, ExecAction, FuncAction, FuncActionType, JumpAction, LoopAction, RecurAction,
+    BrOperands, ExecAction, FuncAction, FuncActionType, JumpAction, LoopAction, RecurAction,
     ThreadAction,
 };
 use crate::analysis::constraint::inter::exec_tree::analyze::FuncNodeLenEntry;
-use crate::analysis::constraint::inter::loc::SrcLoc;
-use crate::{
-    config::{get_trunc_cnt, is_debug_mode},
-    feedback::branches::constraints::UBConstraint,
+use crate::analysis::constraint::inter::exec_tree::binary::BinRecord;
+use crate::analysis::constraint::inter::exec_tree::guard_index::{
+    GuardIndex, GuardRecord, GuardSource,
 };
+use crate::analysis::constraint::inter::loc::SrcLoc;
+use crate::{config::get_trunc_cnt, feedback::branches::constraints::UBConstraint};
 
 pub trait FuncIter {
     fn iter_sub_funcs(&self) -> SubFuncIter;
+    /// sub functions along with roots of the threads spawned
+    fn iter_sub_funcs_cross_thread(&self) -> SubFuncIter;
 }
 
 pub type SharedFuncNodePtr = Rc<RefCell<ExecFuncNode>>;
@@ -39,6 +40,10 @@ impl FuncIter for SharedFuncNodePtr {
     fn iter_sub_funcs(&self) -> SubFuncIter {
         SubFuncIter::from_func_ptr(self.clone())
     }
+
+    fn iter_sub_funcs_cross_thread(&self) -> SubFuncIter {
+        SubFuncIter::from_func_ptr(self.clone()).cross_thread()
+    }
 }
 
 pub fn incre_dot_counter() -> usize {
@@ -66,38 +71,36 @@ pub enum FuncEntryType {
 
 pub struct SubFuncIter {
     parent_func_ptr: SharedFuncNodePtr,
-    cur_func_ptr: Option<SharedFuncNodePtr>,
+    /// index of the next action to check
+    act_idx: usize,
+    cross_thread: bool,
 }
 
 impl SubFuncIter {
     pub fn from_func_ptr(parent_func_ptr: SharedFuncNodePtr) -> Self {
         Self {
             parent_func_ptr,
-            cur_func_ptr: None,
+            act_idx: 0,
+            cross_thread: false,
         }
     }
+
+    /// also yield the roots of linked thread creation actions
+    pub fn cross_thread(mut self) -> Self {
+        self.cross_thread = true;
+        self
+    }
 }
 
 impl Iterator for SubFuncIter {
     type Item = SharedFuncNodePtr;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let prev_idx = if let Some(cur_ptr) = &self.cur_func_ptr {
-            let cur_func = cur_ptr.borrow();
-            cur_func
-                .get_parent_idx()
-                .expect("Current function pointer should have a parent index") as i64
-        } else {
-            -1
-        };
         let parent_func = self.parent_func_ptr.borrow();
-        for act in parent_func.iter_acts_at((prev_idx + 1) as usize) {
-            if let ExecAction::Func(func_act) = act {
-                // get child pointer
-                if let Some(child_ptr) = func_act.get_child_ptr() {
-                    self.cur_func_ptr = Some(child_ptr.clone());
-                    return Some(child_ptr.clone());
-                }
+        while let Some(act) = parent_func.get_act_at(self.act_idx) {
+            self.act_idx += 1;
+            if let Some(sub_ptr) = act.get_sub_node_ptr(self.cross_thread) {
+                return Some(sub_ptr);
             }
         }
         None
@@ -108,6 +111,8 @@ pub struct ExecFuncNode {
     // node_type field which contains func name
     node_type: FuncEntryType,
     pub data: Vec<ExecAction>,
+    /// actions of the node are not materialized
+    pruned: bool,
 }
 
 impl fmt::Debug for ExecFuncNode {
@@ -160,6 +165,7 @@ impl ExecFuncNode {
         Self {
             node_type: FuncEntryType::Init,
             data: vec![],
+            pruned: false,
         }
     }
 
@@ -182,6 +188,7 @@ impl ExecFuncNode {
                 parent_idx,
             },
             data: vec![],
+            pruned: false,
         }
     }
 
@@ -220,6 +227,10 @@ impl ExecFuncNode {
         matches!(self.node_type, FuncEntryType::Init)
     }
 
+    pub fn is_pruned(&self) -> bool {
+        self.pruned
+    }
+
     pub fn is_regular(&self) -> bool {
         matches!(self.node_type, FuncEntryType::Regular { .. })
     }
@@ -263,6 +274,10 @@ impl fmt::Debug for UBVHit {
 }
 
 impl UBVHit {
+    pub fn new(loc: SrcLoc) -> Self {
+        UBVHit { loc }
+    }
+
     pub fn get_src_path(&self) -> Option<&Path> {
         self.loc.get_src_path()
     }
@@ -286,6 +301,56 @@ impl UBVHit {
         Ok(UBVHit { loc })
     }
 }
+/// Damage of a guard file tolerated in recovery mode, e.g. left by crashing or timed-out
+/// executions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GuardDamage {
+    /// malformed records skipped
+    skipped_recs: usize,
+    /// function frames still open at the end of the file
+    closed_frames: usize,
+    /// reading stopped at a corrupted or truncated record
+    truncated: bool,
+}
+
+impl GuardDamage {
+    pub fn get_skipped_recs(&self) -> usize {
+        self.skipped_recs
+    }
+
+    pub fn get_closed_frames(&self) -> usize {
+        self.closed_frames
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.skipped_recs > 0 || self.closed_frames > 0 || self.truncated
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.skipped_recs += other.skipped_recs;
+        self.closed_frames += other.closed_frames;
+        self.truncated |= other.truncated;
+    }
+}
+
+impl fmt::Display for GuardDamage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} records skipped, {} frames closed",
+            self.skipped_recs, self.closed_frames
+        )?;
+        if self.truncated {
+            write!(f, ", truncated")?;
+        }
+        Ok(())
+    }
+}
+
 // pub type FuncBrStack = Vec<FuncEntry>;
 pub struct ThreadExecTree {
     tid: usize,
@@ -294,6 +359,15 @@ pub struct ThreadExecTree {
     cur_depth: usize,
     max_depth: usize,
     // data: Vec<FuncNode>,
+    /// constraint whose hits truncate the tree
+    trunc_cons: Option<UBConstraint>,
+    trunc_cnt: usize,
+    hit_cnt: usize,
+    /// operands recorded before a branch, waiting for the branch guard
+    pending_operands: Option<BrOperands>,
+    /// tolerate malformed records and truncated files instead of failing
+    recovery: bool,
+    damage: GuardDamage,
 }
 
 impl ThreadExecTree {
@@ -328,9 +402,47 @@ impl ThreadExecTree {
             root_ptr,
             cur_depth: 0,
             max_depth: 0,
+            trunc_cons: None,
+            trunc_cnt: 0,
+            hit_cnt: 0,
+            pending_operands: None,
+            recovery: false,
+            damage: GuardDamage::default(),
         })
     }
 
+    /// Stop reading records after the constraint branch is hit `trunc_cnt` times, 0 for no
+    /// truncation.
+    pub fn with_trunc(mut self, cons: &UBConstraint, trunc_cnt: usize) -> Self {
+        self.trunc_cons = Some(cons.clone());
+        self.trunc_cnt = trunc_cnt;
+        self
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.trunc_cons.is_some() && self.trunc_cnt > 0 && self.hit_cnt >= self.trunc_cnt
+    }
+
+    /// Skip malformed records, stop at corrupted ones and close the frames open at the end of
+    /// the file, so that partial guard files of crashing executions still produce trees.
+    pub fn with_recovery(mut self) -> Self {
+        self.recovery = true;
+        self
+    }
+
+    pub fn get_damage(&self) -> &GuardDamage {
+        &self.damage
+    }
+
+    /// the guard file is damaged and the tree misses some actions
+    pub fn is_incomplete(&self) -> bool {
+        self.damage.is_incomplete()
+    }
+
+    pub fn get_hit_cnt(&self) -> usize {
+        self.hit_cnt
+    }
+
     pub fn get_root_ptr(&self) -> SharedFuncNodePtr {
         self.root_ptr.clone()
     }
@@ -397,12 +509,7 @@ impl ThreadExecTree {
         // Thread Guard
         if let Some(thread_act) = GuardParseError::to_eyre(ThreadAction::parse_thread_guard(line))?
         {
-            // construct a thcp entry
-            let func_node_ptr = self.cur_node_ptr.clone();
-            let act_idx = func_node_ptr.borrow().get_len();
-            let tid = thread_act.get_thread_id();
-            let thcp_entry = (tid, ActionPoint::new(func_node_ptr, act_idx));
-
+            let thcp_entry = self.create_thcp_entry(&thread_act);
             return Ok((Some(ExecAction::Thread(thread_act)), Some(thcp_entry)));
         }
 
@@ -418,11 +525,14 @@ impl ThreadExecTree {
 
         loop {
             parse_res = self.parse_guard_impl(parse_content);
-            if let Err(GuardParseError::SkipError { data: _, skip_num }) = parse_res {
+            match parse_res {
                 // if SkipError, skip the number of characters and try again
-                parse_content = &parse_content[skip_num..];
-            } else {
-                break;
+                Err(GuardParseError::SkipError { data: _, skip_num })
+                    if skip_num > 0 && parse_content.is_char_boundary(skip_num) =>
+                {
+                    parse_content = &parse_content[skip_num..];
+                }
+                _ => break,
             }
         }
         GuardParseError::to_eyre_ultimate(parse_res)
@@ -447,7 +557,18 @@ impl ThreadExecTree {
 
         // possible to return skip error
         let (invoc_loc_op, func_name) = FuncAction::parse_call_guard(line)?;
+        Ok(self.create_call_act(invoc_loc_op, &func_name))
+    }
+
+    /// construct a thcp entry pointing to the position of the thread action to be added
+    fn create_thcp_entry(&self, thread_act: &ThreadAction) -> THCPEntry {
+        let func_node_ptr = self.cur_node_ptr.clone();
+        let act_idx = func_node_ptr.borrow().get_len();
+        let tid = thread_act.get_thread_id();
+        (tid, ActionPoint::new(func_node_ptr, act_idx))
+    }
 
+    fn create_call_act(&self, invoc_loc_op: Option<SrcLoc>, func_name: &str) -> FuncAction {
         /* get context information for newly created function node */
         // get index of Function Action which corresponds to new function node.
         let cur_act_len = {
@@ -467,8 +588,7 @@ impl ThreadExecTree {
             invoc_loc: invoc_loc_op,
         };
 
-        let func_act = FuncAction::new(act_type, func_name.to_owned());
-        return Ok(func_act);
+        FuncAction::new(act_type, func_name.to_owned())
     }
 
     // fn create_act(&self, line: &str) -> Result<ExecAction> {
@@ -486,93 +606,309 @@ impl ThreadExecTree {
         // cons_op: Option<&Constraint>,
         // hit_cnt: &mut usize,
     ) -> Result<Option<THCPEntry>> {
+        if let Some(operands) = GuardParseError::to_eyre(BrOperands::parse_operands_guard(line))? {
+            self.pending_operands = Some(operands);
+            return Ok(None);
+        }
         let (act_op, thcp_entry_op) = self.parse_guard(line)?;
 
         if let Some(act) = act_op {
-            // add action to current node
-            self.add_act(&act)?;
-
-            // update context information in case of function actions: current pointer and depth
-            if let ExecAction::Func(func_act) = act {
-                if func_act.is_call() {
-                    // update current node pointer to the new function node
-                    let child_ptr = func_act.get_child_ptr().ok_or_else(|| {
-                        eyre::eyre!(
-                            "Function action is a call but has no child pointer: {}",
-                            func_act.get_name()
-                        )
-                    })?;
-                    self.cur_node_ptr = child_ptr;
-                    self.cur_depth += 1;
-                    if self.cur_depth > self.max_depth {
-                        self.max_depth = self.cur_depth;
-                    }
-                } else if func_act.is_return() {
-                    // move up in the tree
-                    let parent_ptr =
-                        self.cur_node_ptr.borrow().get_parent_ptr().ok_or_else(|| {
-                            eyre::eyre!(
-                                "Current node has no parent, cannot return: {}",
-                                func_act.get_name()
-                            )
-                        })?;
-                    self.cur_node_ptr = parent_ptr;
-                    self.cur_depth -= 1;
+            self.push_act(act)?;
+        }
+
+        Ok(thcp_entry_op)
+    }
+
+    /// Read a record decoded from a binary guard file.
+    pub fn read_bin_record(&mut self, rec: BinRecord) -> Result<Option<THCPEntry>> {
+        match rec {
+            BinRecord::Raw(line) => self.read_line(&line),
+            BinRecord::Operands(operands) => {
+                self.pending_operands = Some(operands);
+                Ok(None)
+            }
+            BinRecord::Enter {
+                invoc_loc,
+                func_name,
+            } => {
+                let func_act = self.create_call_act(invoc_loc, &func_name);
+                self.push_act(ExecAction::Func(func_act))?;
+                Ok(None)
+            }
+            BinRecord::Act(act) => {
+                let thcp_entry_op = match &act {
+                    ExecAction::Thread(thread_act) => Some(self.create_thcp_entry(thread_act)),
+                    _ => None,
+                };
+                self.push_act(act)?;
+                Ok(thcp_entry_op)
+            }
+        }
+    }
+
+    /// count the action if it reaches the truncation constraint
+    fn count_hit(&mut self, act: &ExecAction) -> Result<()> {
+        let cons = match &self.trunc_cons {
+            Some(cons) => cons,
+            None => return Ok(()),
+        };
+        let is_hit = match act {
+            ExecAction::Value(val_hit) => cons.is_hit(val_hit)?,
+            ExecAction::Intra(jump_act) => match jump_act.get_val_loc() {
+                Some(val_loc) => cons.is_hit_loc(val_loc)?,
+                None => false,
+            },
+            _ => false,
+        };
+        if is_hit {
+            self.hit_cnt += 1;
+            log::debug!(
+                "Constraint hit {} in thread {}: {}",
+                self.hit_cnt,
+                self.tid,
+                cons
+            );
+        }
+        Ok(())
+    }
+
+    /// add action to current node and update the context
+    fn push_act(&mut self, mut act: ExecAction) -> Result<()> {
+        if let ExecAction::Intra(jump_act) = &mut act {
+            // operands belong to the branch evaluating the same value
+            let matched = self
+                .pending_operands
+                .as_ref()
+                .is_some_and(|operands| jump_act.get_val_loc() == Some(operands.get_val_loc()));
+            if matched {
+                jump_act.set_operands(self.pending_operands.take());
+            }
+        }
+        self.count_hit(&act)?;
+        // add action to current node
+        self.add_act(&act)?;
+
+        // update context information in case of function actions: current pointer and depth
+        if let ExecAction::Func(func_act) = act {
+            if func_act.is_call() {
+                // update current node pointer to the new function node
+                let child_ptr = func_act.get_child_ptr().ok_or_else(|| {
+                    eyre::eyre!(
+                        "Function action is a call but has no child pointer: {}",
+                        func_act.get_name()
+                    )
+                })?;
+                self.cur_node_ptr = child_ptr;
+                self.cur_depth += 1;
+                if self.cur_depth > self.max_depth {
+                    self.max_depth = self.cur_depth;
                 }
+            } else if func_act.is_return() {
+                // move up in the tree
+                let parent_ptr = self.cur_node_ptr.borrow().get_parent_ptr().ok_or_else(|| {
+                    eyre::eyre!(
+                        "Current node has no parent, cannot return: {}",
+                        func_act.get_name()
+                    )
+                })?;
+                self.cur_node_ptr = parent_ptr;
+                self.cur_depth -= 1;
             }
+        }
 
-            // let act = self.create_act(line)?;
-        }
-
-        // if let Some(val_hit) = value_hit_op {
-        //     // if is_debug_mode() && cons.same_src_file(&val_hit) {
-        //     //     log::debug!("Value Hit: {:?}", val_hit);
-        //     //     log::debug!("Constraint: {}", cons);
-        //     // }
-        //     if let Some(cons) = cons_op {
-        //         if is_debug_mode() && cons.near_hit(&val_hit) {
-        //             log::debug!("Value Hit: {:?}", val_hit);
-        //             log::debug!("Constraint: {}", cons);
-        //         }
-        //         if cons.is_hit(&val_hit)? {
-        //             *hit_cnt += 1;
-        //         }
-        //     }
-        // }
+        Ok(())
+    }
 
-        Ok(thcp_entry_op)
+    pub fn read_record(&mut self, rec: GuardRecord) -> Result<Option<THCPEntry>> {
+        match rec {
+            GuardRecord::Line(line) => self.read_line(&line),
+            GuardRecord::Bin(bin_rec) => self.read_bin_record(bin_rec),
+        }
     }
 
-    // pub fn from_guard_file_with_constraint<P: AsRef<Path>>(
-    //     fs_path: P,
-    //     cons: &Constraint,
-    // ) -> Result<(Self, THCPMAPPING)> {
-    //     Self::from_guard_file_impl(fs_path.as_ref(), Some(cons))
-    // }
+    /// Read the record, which is skipped if malformed in recovery mode.
+    fn read_record_at(
+        &mut self,
+        offset: u64,
+        rec: GuardRecord,
+        fs_path: &Path,
+    ) -> Result<Option<THCPEntry>> {
+        match self.read_record(rec) {
+            Err(e) if self.recovery => {
+                log::debug!(
+                    "Skip malformed record at {} of {:?}: {}",
+                    offset,
+                    fs_path,
+                    e
+                );
+                self.damage.skipped_recs += 1;
+                Ok(None)
+            }
+            res => res,
+        }
+    }
+
+    /// Next record of the source, the end of file is assumed at a corrupted record in recovery
+    /// mode.
+    fn next_record_from(
+        &mut self,
+        source: &mut GuardSource,
+        fs_path: &Path,
+    ) -> Result<Option<(u64, GuardRecord)>> {
+        match source.next_record() {
+            Err(e) if self.recovery => {
+                log::warn!("Stop reading corrupted guard file {:?}: {}", fs_path, e);
+                self.damage.truncated = true;
+                Ok(None)
+            }
+            res => res,
+        }
+    }
+
+    /// Close the function frames without return records at the end of the file.
+    fn close_open_frames(&mut self, fs_path: &Path) {
+        if !self.recovery || self.cur_depth == 0 || self.is_truncated() {
+            return;
+        }
+        log::debug!(
+            "Close {} open function frames at the end of {:?}",
+            self.cur_depth,
+            fs_path
+        );
+        self.damage.closed_frames += self.cur_depth;
+        self.cur_node_ptr = self.root_ptr.clone();
+        self.cur_depth = 0;
+    }
 
     pub fn from_guard_file<P: AsRef<Path>>(fs_path: P) -> Result<(Self, THCPMAPPING)> {
+        Self::from_guard_file_with_constraint(fs_path, None, false)
+    }
+
+    /// Build the tree, truncated after the `trunc_cnt`-th hit of the constraint if specified.
+    /// Damaged records are tolerated only in `recovery` mode.
+    pub fn from_guard_file_with_constraint<P: AsRef<Path>>(
+        fs_path: P,
+        cons_op: Option<&UBConstraint>,
+        recovery: bool,
+    ) -> Result<(Self, THCPMAPPING)> {
+        let mut exec_tree: ThreadExecTree = ThreadExecTree::new(fs_path.as_ref())?;
+        if let Some(cons) = cons_op {
+            exec_tree = exec_tree.with_trunc(cons, get_trunc_cnt());
+        }
+        if recovery {
+            exec_tree = exec_tree.with_recovery();
+        }
+        exec_tree.read_guard_file(fs_path)
+    }
+
+    /// Read all records of the guard file until the tree is truncated.
+    pub fn read_guard_file<P: AsRef<Path>>(mut self, fs_path: P) -> Result<(Self, THCPMAPPING)> {
+        let mut thcp_mapping = HashMap::new();
+
+        let fs_path = fs_path.as_ref();
+        let mut source = GuardSource::open(fs_path)?;
+        while let Some((offset, rec)) = self.next_record_from(&mut source, fs_path)? {
+            log::debug!("Processing record at {}: {:?}", offset, fs_path);
+            let thcp_entry_op = self.read_record_at(offset, rec, fs_path)?;
+            if let Some(thcp_entry) = thcp_entry_op {
+                thcp_mapping.insert(thcp_entry.0, thcp_entry.1);
+            }
+
+            if self.is_truncated() {
+                log::debug!("Truncated at {} of {:?}", offset, fs_path);
+                break;
+            }
+        }
+        self.close_open_frames(fs_path);
+
+        Ok((self, thcp_mapping))
+    }
+
+    /// Build the tree with only the calls leading to `target_func` and the calls inside it,
+    /// truncated after the `trunc_cnt`-th hit of the constraint if specified.
+    /// Damaged records are tolerated only in `recovery` mode.
+    pub fn from_guard_file_for_func<P: AsRef<Path>>(
+        fs_path: P,
+        target_func: &str,
+        cons_op: Option<&UBConstraint>,
+        recovery: bool,
+        node_budget: &mut usize,
+    ) -> Result<(Self, THCPMAPPING)> {
         let mut exec_tree: ThreadExecTree = ThreadExecTree::new(fs_path.as_ref())?;
+        if let Some(cons) = cons_op {
+            exec_tree = exec_tree.with_trunc(cons, get_trunc_cnt());
+        }
+        if recovery {
+            exec_tree = exec_tree.with_recovery();
+        }
+        exec_tree.read_guard_file_for_func(fs_path, target_func, node_budget)
+    }
+
+    /// Read records of the guard file related to `target_func`.
+    /// Other calls are kept as pruned nodes without actions, so that the action order of their
+    /// callers is preserved.
+    /// `node_budget` is the number of function nodes still allowed to be materialized.
+    pub fn read_guard_file_for_func<P: AsRef<Path>>(
+        mut self,
+        fs_path: P,
+        target_func: &str,
+        node_budget: &mut usize,
+    ) -> Result<(Self, THCPMAPPING)> {
+        let fs_path = fs_path.as_ref();
+        let index = GuardIndex::from_guard_file(fs_path, target_func)?;
+        log::debug!(
+            "{}/{} function entries needed for {} in {:?}",
+            index.count_needed(),
+            index.len(),
+            target_func,
+            fs_path
+        );
+
         let mut thcp_mapping = HashMap::new();
 
-        let file = File::open(fs_path.as_ref())?;
-        let reader = BufReader::new(file);
-        // let mut hit_cnt = 0;
-        for (idx, line_res) in reader.lines().enumerate() {
-            log::debug!("Processing line {}: {:?}", idx + 1, fs_path.as_ref());
-            let line = line_res?;
-            // let exec_act = ExecAction::from_line(&line)?;
-            let thcp_entry_op = exec_tree.read_line(&line)?;
+        let mut source = GuardSource::open(fs_path)?;
+        while let Some((offset, rec)) = self.next_record_from(&mut source, fs_path)? {
+            let skipped_recs = self.damage.skipped_recs;
+            let thcp_entry_op = self.read_record_at(offset, rec, fs_path)?;
             if let Some(thcp_entry) = thcp_entry_op {
                 thcp_mapping.insert(thcp_entry.0, thcp_entry.1);
             }
+            if self.is_truncated() {
+                log::debug!("Truncated at {} of {:?}", offset, fs_path);
+                break;
+            }
+            // a skipped entry does not enter a function node
+            if self.damage.skipped_recs > skipped_recs {
+                continue;
+            }
 
-            // truncate if hit count exceeds truncation count
-            // if hit_cnt >= get_trunc_cnt() {
-            //     break;
-            // }
+            let entry_idx = match index.find_entry(offset) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            if index.is_needed(entry_idx) {
+                if *node_budget > 0 {
+                    *node_budget -= 1;
+                    continue;
+                }
+                log::debug!(
+                    "Node budget exhausted at offset {} of {:?}",
+                    offset,
+                    fs_path
+                );
+            }
+
+            // skip the records of the entered function until its return
+            self.cur_node_ptr.borrow_mut().pruned = true;
+            if let Some(ret_offset) = index.get_ret_offset(entry_idx) {
+                source.skip_to(ret_offset)?;
+            }
+            // otherwise the function never returns, e.g. it exits or is unwound from, and the
+            // rest of the trace is still read into the pruned node
         }
+        self.close_open_frames(fs_path);
 
-        Ok((exec_tree, thcp_mapping))
+        Ok((self, thcp_mapping))
     }
 
     // single tree version
@@ -629,3 +965,163 @@ pub struct ExecForest {
 impl ExecForest {
     pub fn from_guard_dir<P: AsRef<Path>>(guard_dir: P) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::constraint::inter::exec_tree::action::OperandVal,
+        test_utils::{ub_cons, write_main_guard},
+    };
+
+    #[test]
+    fn test_from_guard_file_for_func() -> Result<()> {
+        let guard_dir = tempfile::tempdir()?;
+        let lines = [
+            "enter main",
+            "enter helper(int)",
+            "Merge Br Guard: /src/a.c:3:5 1 /src/a.c:4:5",
+            "return from helper(int)",
+            "Function Invocation: /src/a.c:10:3 enter target",
+            "enter leaf",
+            "return from leaf",
+            "Unconditional Branch Value: /src/a.c:20:7",
+            "return from target",
+            "return from main",
+        ];
+        let guard_fpath = write_main_guard(guard_dir.path(), &lines)?;
+
+        let mut node_budget = 10;
+        let (tree, _) = ThreadExecTree::new(&guard_fpath)?.read_guard_file_for_func(
+            &guard_fpath,
+            "target",
+            &mut node_budget,
+        )?;
+        // main, target and leaf are materialized
+        assert_eq!(node_budget, 7);
+
+        let main_ptr = tree.get_root_ptr().iter_sub_funcs().next().unwrap();
+        let mut sub_funcs = main_ptr.iter_sub_funcs();
+        let helper_ptr = sub_funcs.next().unwrap();
+        assert!(helper_ptr.borrow().is_pruned());
+        // only the return action is kept
+        assert_eq!(helper_ptr.borrow().get_len(), 1);
+
+        let target_ptr = sub_funcs.next().unwrap();
+        assert!(!target_ptr.borrow().is_pruned());
+        // call of leaf, value hit, return from target
+        assert_eq!(target_ptr.borrow().get_len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_for_func_without_return() -> Result<()> {
+        let guard_dir = tempfile::tempdir()?;
+        let lines = [
+            "enter main",
+            "enter target",
+            "return from target",
+            "enter helper",
+            "Thread Creation: /src/a.c:5:3 2",
+        ];
+        let guard_fpath = write_main_guard(guard_dir.path(), &lines)?;
+
+        let mut node_budget = 10;
+        let (tree, thcp_mapping) = ThreadExecTree::from_guard_file_for_func(
+            &guard_fpath,
+            "target",
+            None,
+            false,
+            &mut node_budget,
+        )?;
+        // records after the pruned entry are kept
+        assert!(thcp_mapping.contains_key(&2));
+        let main_ptr = tree.get_root_ptr().iter_sub_funcs().next().unwrap();
+        let helper_ptr = main_ptr.iter_sub_funcs().nth(1).unwrap();
+        assert!(helper_ptr.borrow().is_pruned());
+        assert_eq!(helper_ptr.borrow().get_len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trunc_by_constraint() -> Result<()> {
+        let guard_dir = tempfile::tempdir()?;
+        let lines = [
+            "enter target",
+            "Br Guard: /src/a.c:20:7 /src/a.c:20:3 1 /src/a.c:21:5",
+            "Unconditional Branch Value: /src/a.c:30:2",
+            "Unconditional Branch Value: /src/a.c:20:9",
+            "Merge Br Guard: /src/a.c:25:3 0 /src/a.c:27:5",
+            "return from target",
+        ];
+        let guard_fpath = write_main_guard(guard_dir.path(), &lines)?;
+
+        let cons = ub_cons(
+            "len > 4",
+            true,
+            "/src/a.c",
+            [20, 5, 20, 12],
+            "int target(int len)",
+        )?;
+        let (tree, _) = ThreadExecTree::new(&guard_fpath)?
+            .with_trunc(&cons, 2)
+            .read_guard_file(&guard_fpath)?;
+        assert!(tree.is_truncated());
+
+        // reading stops at the second hit
+        let target_ptr = tree.get_root_ptr().iter_sub_funcs().next().unwrap();
+        assert_eq!(target_ptr.borrow().get_len(), 3);
+
+        // 0 disables the truncation
+        let (tree, _) = ThreadExecTree::new(&guard_fpath)?
+            .with_trunc(&cons, 0)
+            .read_guard_file(&guard_fpath)?;
+        assert!(!tree.is_truncated());
+        let target_ptr = tree.get_root_ptr().iter_sub_funcs().next().unwrap();
+        assert_eq!(target_ptr.borrow().get_len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_br_operands() -> Result<()> {
+        let guard_dir = tempfile::tempdir()?;
+        let lines = [
+            "enter main",
+            "Br Operands: /src/a.c:5:9 slt s -3 16",
+            "Br Guard: /src/a.c:5:9 /src/a.c:5:5 1 /src/a.c:6:7",
+            "Br Operands: /src/a.c:9:9 ogt f 0.5 1",
+            "Br Guard: /src/a.c:8:9 /src/a.c:8:5 0 /src/a.c:10:3",
+            "Br Operands: /src/a.c:12:9 ne s32 -1 0",
+            "Br Guard: /src/a.c:12:9 /src/a.c:12:5 1 /src/a.c:13:3",
+            "return from main",
+        ];
+        let guard_fpath = write_main_guard(guard_dir.path(), &lines)?;
+
+        let (tree, _) = ThreadExecTree::from_guard_file(&guard_fpath)?;
+        let main_ptr = tree.get_root_ptr().iter_sub_funcs().next().unwrap();
+        let main_node = main_ptr.borrow();
+        let jump_acts: Vec<&JumpAction> = main_node
+            .iter_acts()
+            .filter_map(|act| match act {
+                ExecAction::Intra(jump_act) => Some(jump_act),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(jump_acts.len(), 3);
+
+        let operands = jump_acts[0].get_operands().unwrap();
+        assert_eq!(operands.get_pred(), "slt");
+        assert_eq!(operands.get_lhs(), OperandVal::Signed(-3));
+        assert_eq!(operands.get_rhs(), OperandVal::Signed(16));
+        assert_eq!(operands.get_width(), None);
+        // operands of another compare are not attached
+        assert!(jump_acts[1].get_operands().is_none());
+
+        // operands of eq and ne are sign-extended
+        let operands = jump_acts[2].get_operands().unwrap();
+        assert_eq!(operands.get_pred(), "ne");
+        assert_eq!(operands.get_width(), Some(32));
+        assert_eq!(operands.get_lhs().to_unsigned(32), Some(u32::MAX as u64));
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use color_eyre::eyre::Result;
 use eyre::bail;
 use std::{
+    collections::HashMap,
     fmt,
     path::{Path, PathBuf},
 };
@@ -37,6 +38,48 @@ impl fmt::Debug for SrcLoc {
     }
 }
 
+/// same format as the locations in guard files
+impl fmt::Display for SrcLoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SrcLoc::NullLoc => write!(f, "NullLoc"),
+            SrcLoc::Valid { fpath, line, col } => {
+                write!(f, "{}:{}:{}", fpath.display(), line, col)
+            }
+        }
+    }
+}
+
+/// Source files read once per path, for looking up the text of many locations.
+#[derive(Debug, Default)]
+pub struct SrcTextCache {
+    /// None if the file failed to read
+    contents: HashMap<PathBuf, Option<String>>,
+}
+
+impl SrcTextCache {
+    /// same as `SrcLoc::get_line_text`
+    pub fn get_line_text(&mut self, loc: &SrcLoc) -> Result<String> {
+        let (fpath, line) = match loc {
+            SrcLoc::NullLoc => bail!("Source location is null"),
+            SrcLoc::Valid { fpath, line, .. } => (fpath, *line),
+        };
+        let content = self.contents.entry(fpath.clone()).or_insert_with(|| {
+            match std::fs::read_to_string(fpath) {
+                Ok(content) => Some(content),
+                Err(e) => {
+                    log::debug!("Failed to read {:?}: {}", fpath, e);
+                    None
+                }
+            }
+        });
+        match content {
+            Some(content) => SrcLoc::extract_line(content, line, fpath),
+            None => bail!("Failed to read {:?}", fpath),
+        }
+    }
+}
+
 impl SrcLoc {
     pub fn get_src_path(&self) -> Option<&Path> {
         match self {
@@ -68,6 +111,24 @@ impl SrcLoc {
         }
     }
 
+    /// source line of the location without surrounding whitespaces
+    pub fn get_line_text(&self) -> Result<String> {
+        let (fpath, line) = match self {
+            SrcLoc::NullLoc => bail!("Source location is null"),
+            SrcLoc::Valid { fpath, line, .. } => (fpath, *line),
+        };
+        let content = std::fs::read_to_string(fpath)?;
+        Self::extract_line(&content, line, fpath)
+    }
+
+    fn extract_line(content: &str, line: usize, fpath: &Path) -> Result<String> {
+        let text = content
+            .lines()
+            .nth(line.saturating_sub(1))
+            .ok_or_else(|| eyre::eyre!("Line {} not found in {:?}", line, fpath))?;
+        Ok(text.trim().to_owned())
+    }
+
     pub fn inside_range(&self, rng: &Range, fpath: &Path) -> Result<bool> {
         if !self.is_valid() {
             return Ok(false);
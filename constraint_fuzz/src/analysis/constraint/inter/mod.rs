@@ -54,6 +54,15 @@ impl CodeCoverage {
         Ok(false)
     }
 
+    /// judge if the function containing the constraint is executed at least once
+    pub fn reaches_cons_func(&self, cons: &UBConstraint) -> Result<bool> {
+        let func_name = cons.get_func_name()?;
+        let flag = self
+            .iter_function_covs()
+            .any(|func| func.get_name() == func_name && func.count > 0);
+        Ok(flag)
+    }
+
     /// get all br regions inside the same function with specified constraint
     pub fn get_related_br_regions(&self, cons: &UBConstraint) -> Result<Vec<SrcRegion>> {
         let mut br_rgn_list = vec![];
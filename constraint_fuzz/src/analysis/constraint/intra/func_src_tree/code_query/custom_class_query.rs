@@ -237,6 +237,23 @@ impl Borrow<QLLoc> for CustomClass {
 
 pub type CustomClassSet = HashSet<CustomClass>;
 
+/// values of enum constants by their names
+pub type EnumValueMap = HashMap<String, i64>;
+
+pub fn get_enum_value_map(cc_set: &CustomClassSet) -> EnumValueMap {
+    let mut enum_values: EnumValueMap = HashMap::new();
+    for cc in cc_set.iter() {
+        if let CustomClassVariant::Enum { constants } = &cc.variants {
+            for constant in constants.iter() {
+                enum_values
+                    .entry(constant.name.clone())
+                    .or_insert(constant.value);
+            }
+        }
+    }
+    enum_values
+}
+
 impl CodeQLRunner {
     pub fn get_custom_class_set(&self) -> Result<CustomClassSet> {
         let sf_rec_vec: Vec<StructFieldRec> = self.run_query_and_parse(STRUCT_FIELD_QUERY)?;
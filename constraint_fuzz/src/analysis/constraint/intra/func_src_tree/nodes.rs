@@ -6,17 +6,19 @@ use std::{
 };
 
 use color_eyre::eyre::Result;
-use eyre::bail;
 use my_macros::EquivByLoc;
 
 use crate::analysis::constraint::{
-    inter::exec_tree::thread_tree::ExecFuncNode,
+    inter::{
+        exec_tree::{action::ExecAction, thread_tree::ExecFuncNode},
+        loc::SrcLoc,
+    },
     intra::func_src_tree::{
         code_query::{
             func_invoc_query::{FuncInvoc, FuncInvocMap},
             switch_query::CaseMap,
         },
-        nodes::cf_mod::{CFStruct, CasePtrMap},
+        nodes::cf_mod::{CFStruct, CasePtrMap, IfNode, SwitchNode},
         stmts::{BlockStmt, BlockType, ChildEntry, ForStmt, IfStmt, QLLoc, SwitchStmt, WhileStmt},
     },
 };
@@ -39,6 +41,14 @@ pub struct StmtNode {
 }
 
 impl StmtNode {
+    pub fn get_loc(&self) -> &QLLoc {
+        match &self.variants {
+            StmtNodeVariants::Block(block_node) => &block_node.loc,
+            StmtNodeVariants::Plain(plain_node) => plain_node.get_loc(),
+            StmtNodeVariants::CFStruct(cf_struct) => cf_struct.get_loc(),
+        }
+    }
+
     /**
      * Default Pointer Creation
      */
@@ -239,6 +249,17 @@ pub mod cf_mod {
         While(WhileNode),
         For(ForNode),
     }
+
+    impl CFStruct {
+        pub fn get_loc(&self) -> &QLLoc {
+            match self {
+                CFStruct::If(if_node) => &if_node.loc,
+                CFStruct::Switch(switch_node) => &switch_node.loc,
+                CFStruct::While(while_node) => &while_node.loc,
+                CFStruct::For(for_node) => &for_node.loc,
+            }
+        }
+    }
 }
 
 pub struct FuncSrcTree {
@@ -265,10 +286,278 @@ pub struct FuncSrcTreeIter {
     cur_ptr_op: Option<SharedStmtNodePtr>,
 }
 
+/// Result of steering the iterator at a control flow structure
+#[derive(Debug, PartialEq, Eq)]
+pub enum Selected {
+    /// entered the then/else arm or a switch case
+    Arm,
+    /// entered the loop body for the iteration of given count
+    LoopIter(usize),
+    /// left the loop
+    LoopOut,
+    /// no arm is taken, or no matching action is recorded
+    Skip,
+}
+
 impl FuncSrcTreeIter {
-    pub fn select(&mut self, cf_struct: &CFStruct, exec_node: &ExecFuncNode, exec_idx: &mut usize) {
-        // TODO: implement the selection logic
-        unimplemented!()
+    fn get_ptr_loc(ptr: &SharedStmtNodePtr) -> QLLoc {
+        ptr.borrow().get_loc().clone()
+    }
+
+    /// locations of the child statements of a control flow structure
+    fn get_body_locs(cf_struct: &CFStruct) -> Vec<QLLoc> {
+        match cf_struct {
+            CFStruct::If(if_node) => {
+                let mut locs = vec![Self::get_ptr_loc(&if_node.then_blk)];
+                if let Some(else_ptr) = &if_node.else_blk {
+                    locs.push(Self::get_ptr_loc(else_ptr));
+                }
+                locs
+            }
+            CFStruct::Switch(switch_node) => switch_node
+                .case_ptr_map
+                .values()
+                .flatten()
+                .map(Self::get_ptr_loc)
+                .collect(),
+            CFStruct::While(while_node) => vec![Self::get_ptr_loc(&while_node.body)],
+            CFStruct::For(for_node) => vec![Self::get_ptr_loc(&for_node.body)],
+        }
+    }
+
+    /// judge if the location belongs to the structure itself rather than its child statements
+    fn in_header(loc: &SrcLoc, cf_loc: &QLLoc, body_locs: &[QLLoc]) -> bool {
+        cf_loc.contains_loc(loc) && !body_locs.iter().any(|body_loc| body_loc.contains_loc(loc))
+    }
+
+    fn get_guard_loc(act: &ExecAction) -> Option<&SrcLoc> {
+        match act {
+            ExecAction::Intra(jump_act) => Some(jump_act.get_from_loc()),
+            ExecAction::Loop(loop_act) => Some(loop_act.get_header_loc()),
+            _ => None,
+        }
+    }
+
+    /// find index of the next guard action of the structure, starting from `start`.
+    /// Guards of unmodeled structures are skipped, and the search stops at guards after the structure.
+    fn find_header_act(
+        exec_node: &ExecFuncNode,
+        start: usize,
+        cf_loc: &QLLoc,
+        body_locs: &[QLLoc],
+    ) -> Option<usize> {
+        for (idx, act) in exec_node.iter_acts_at(start).enumerate() {
+            let loc = match Self::get_guard_loc(act) {
+                Some(loc) => loc,
+                None => continue,
+            };
+            if Self::in_header(loc, cf_loc, body_locs) {
+                return Some(start + idx);
+            }
+            if cf_loc.ends_before_loc(loc) {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// consume consecutive header guards starting at `start`, returns the index of the last one
+    fn consume_header_acts(
+        exec_node: &ExecFuncNode,
+        start: usize,
+        cf_loc: &QLLoc,
+        body_locs: &[QLLoc],
+    ) -> usize {
+        let mut last = start;
+        for (idx, act) in exec_node.iter_acts_at(start + 1).enumerate() {
+            match act {
+                ExecAction::Value(_) => continue,
+                ExecAction::Intra(jump_act)
+                    if Self::in_header(jump_act.get_from_loc(), cf_loc, body_locs) =>
+                {
+                    last = start + 1 + idx;
+                }
+                _ => break,
+            }
+        }
+        last
+    }
+
+    fn select_if(
+        &mut self,
+        if_node: &IfNode,
+        body_locs: &[QLLoc],
+        exec_node: &ExecFuncNode,
+        exec_idx: &mut usize,
+    ) -> Result<Selected> {
+        let first = match Self::find_header_act(exec_node, *exec_idx, &if_node.loc, body_locs) {
+            Some(idx) => idx,
+            None => return Ok(Selected::Skip),
+        };
+        let last = Self::consume_header_acts(exec_node, first, &if_node.loc, body_locs);
+        *exec_idx = last + 1;
+
+        let jump_act = match exec_node.get_act_at(last) {
+            Some(ExecAction::Intra(jump_act)) => jump_act,
+            _ => return Ok(Selected::Skip),
+        };
+        let dest_loc = jump_act.get_dest_loc();
+        let then_loc = Self::get_ptr_loc(&if_node.then_blk);
+        let else_loc_op = if_node.else_blk.as_ref().map(Self::get_ptr_loc);
+
+        let take_then = if dest_loc.is_valid() {
+            if then_loc.contains_loc(dest_loc) {
+                true
+            } else if else_loc_op
+                .as_ref()
+                .is_some_and(|else_loc| else_loc.contains_loc(dest_loc))
+            {
+                false
+            } else {
+                // jumps over the whole if statement
+                return Ok(Selected::Skip);
+            }
+        } else {
+            jump_act.get_cond_val()
+        };
+
+        let arm_op = if take_then {
+            Some(Rc::clone(&if_node.then_blk))
+        } else {
+            if_node.else_blk.as_ref().map(Rc::clone)
+        };
+        match arm_op {
+            Some(arm_ptr) => {
+                self.cur_ptr_op = Some(arm_ptr);
+                Ok(Selected::Arm)
+            }
+            None => Ok(Selected::Skip),
+        }
+    }
+
+    fn select_switch(
+        &mut self,
+        switch_node: &SwitchNode,
+        body_locs: &[QLLoc],
+        exec_node: &ExecFuncNode,
+        exec_idx: &mut usize,
+    ) -> Result<Selected> {
+        let idx = match Self::find_header_act(exec_node, *exec_idx, &switch_node.loc, body_locs) {
+            Some(idx) => idx,
+            None => return Ok(Selected::Skip),
+        };
+        *exec_idx = idx + 1;
+        let dest_loc = match exec_node.get_act_at(idx) {
+            Some(ExecAction::Intra(jump_act)) if jump_act.is_switch_guard() => {
+                jump_act.get_dest_loc()
+            }
+            _ => return Ok(Selected::Skip),
+        };
+
+        let mut cases: Vec<(&QLLoc, &Vec<SharedStmtNodePtr>)> =
+            switch_node.case_ptr_map.iter().collect();
+        cases.sort_by(|a, b| a.0.cmp(b.0));
+
+        // the case whose label or statements contain the destination, or the last label before it
+        let hit_pos = cases.iter().position(|(case_loc, ptrs)| {
+            case_loc.contains_loc(dest_loc)
+                || ptrs
+                    .iter()
+                    .any(|ptr| Self::get_ptr_loc(ptr).contains_loc(dest_loc))
+        });
+        let hit_pos = hit_pos.or_else(|| {
+            cases
+                .iter()
+                .rposition(|(case_loc, _)| case_loc.ends_before_loc(dest_loc))
+        });
+        let hit_pos = match hit_pos {
+            Some(pos) => pos,
+            None => return Ok(Selected::Skip),
+        };
+
+        // empty cases fall through to the next non-empty one
+        for (_, ptrs) in cases.iter().skip(hit_pos) {
+            if let Some(first_ptr) = ptrs.first() {
+                self.cur_ptr_op = Some(Rc::clone(first_ptr));
+                return Ok(Selected::Arm);
+            }
+        }
+        Ok(Selected::Skip)
+    }
+
+    fn select_loop(
+        &mut self,
+        cf_loc: &QLLoc,
+        body_ptr: &SharedStmtNodePtr,
+        body_locs: &[QLLoc],
+        exec_node: &ExecFuncNode,
+        exec_idx: &mut usize,
+    ) -> Result<Selected> {
+        let mut idx = *exec_idx;
+        let loop_act = loop {
+            idx = match Self::find_header_act(exec_node, idx, cf_loc, body_locs) {
+                Some(idx) => idx,
+                None => return Ok(Selected::Skip),
+            };
+            match exec_node.get_act_at(idx) {
+                Some(ExecAction::Loop(loop_act)) => break loop_act,
+                // branch guard of loop condition
+                _ => idx += 1,
+            }
+        };
+        *exec_idx = idx + 1;
+
+        if loop_act.is_loop_end() {
+            return Ok(Selected::LoopOut);
+        }
+
+        // the last header hit only evaluates the condition before leaving the loop
+        for (off, act) in exec_node.iter_acts_at(idx + 1).enumerate() {
+            match act {
+                ExecAction::Value(_) => continue,
+                ExecAction::Intra(jump_act)
+                    if Self::in_header(jump_act.get_from_loc(), cf_loc, body_locs) =>
+                {
+                    continue
+                }
+                ExecAction::Loop(end_act)
+                    if end_act.is_loop_end()
+                        && Self::in_header(end_act.get_header_loc(), cf_loc, body_locs) =>
+                {
+                    *exec_idx = idx + 1 + off + 1;
+                    return Ok(Selected::LoopOut);
+                }
+                _ => break,
+            }
+        }
+
+        self.cur_ptr_op = Some(Rc::clone(body_ptr));
+        Ok(Selected::LoopIter(loop_act.get_count().unwrap_or(1)))
+    }
+
+    /// Steer the iterator according to the actions of `exec_node` starting at `exec_idx`.
+    /// Should be invoked right after the iterator yields the node of `cf_struct`.
+    /// Consumed actions are skipped by advancing `exec_idx`.
+    pub fn select(
+        &mut self,
+        cf_struct: &CFStruct,
+        exec_node: &ExecFuncNode,
+        exec_idx: &mut usize,
+    ) -> Result<Selected> {
+        let cf_loc = cf_struct.get_loc();
+        let body_locs = Self::get_body_locs(cf_struct);
+        match cf_struct {
+            CFStruct::If(if_node) => self.select_if(if_node, &body_locs, exec_node, exec_idx),
+            CFStruct::Switch(switch_node) => {
+                self.select_switch(switch_node, &body_locs, exec_node, exec_idx)
+            }
+            CFStruct::While(while_node) => {
+                self.select_loop(cf_loc, &while_node.body, &body_locs, exec_node, exec_idx)
+            }
+            CFStruct::For(for_node) => {
+                self.select_loop(cf_loc, &for_node.body, &body_locs, exec_node, exec_idx)
+            }
+        }
     }
 
     fn get_next_child_ptr(
@@ -309,6 +598,8 @@ impl FuncSrcTreeIter {
                         Some(Rc::clone(&case_ptr_vec[idx + 1]))
                     }
                 }
+                // revisit the loop node after its body, so that the next iteration is selected
+                CFStruct::While(_) | CFStruct::For(_) => Some(Rc::clone(&par_ptr)),
                 _ => None,
             },
             _ => None,
@@ -323,10 +614,8 @@ impl FuncSrcTreeIter {
         let cur_node = cur_ptr.borrow();
         match &cur_node.variants {
             StmtNodeVariants::Block(block_node) => Ok(block_node.get_first_stmt()),
-            StmtNodeVariants::CFStruct(_) => {
-                bail!("Should not call get_next_ptr on CFStruct node directly")
-            }
-            StmtNodeVariants::Plain(_) => {
+            // control flow structures are stepped over unless `select` steers into them
+            StmtNodeVariants::CFStruct(_) | StmtNodeVariants::Plain(_) => {
                 let mut cur_ptr = cur_ptr.clone();
                 let mut par_ptr;
                 loop {
@@ -373,6 +662,20 @@ pub struct SrcExpr {
 }
 
 impl SrcExpr {
+    pub fn get_loc(&self) -> &QLLoc {
+        &self.loc
+    }
+
+    /// function invocations inside the expression, in evaluation order (inner calls first)
+    pub fn get_func_invocs(&self) -> Vec<&FuncInvoc> {
+        let mut invocs: Vec<&FuncInvoc> = self.func_invoc_vec.iter().collect();
+        invocs.sort_by_key(|invoc| {
+            let [sl, sc, el, ec] = invoc.loc.to_range();
+            (el, ec, std::cmp::Reverse((sl, sc)))
+        });
+        invocs
+    }
+
     pub fn get_expr_str(&self) -> Result<String> {
         self.loc.get_content()
     }
@@ -396,10 +699,14 @@ impl SrcExpr {
                 break;
             }
 
-            if loc.end_before(&invoc_loc) {
-                right = mid - 1;
-            } else if loc.start_after(&invoc_loc) {
+            // invocations are sorted by start, an enclosing call starts before `loc`
+            if invoc_loc.start_before(loc) {
                 left = mid + 1;
+            } else {
+                match mid.checked_sub(1) {
+                    Some(r) => right = r,
+                    None => break,
+                }
             }
         }
         match idx {
@@ -5,12 +5,13 @@ use std::{
     collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use eyre::bail;
 
 use crate::{
+    analysis::constraint::inter::loc::SrcLoc,
     analysis::constraint::intra::func_src_tree::code_query::{
         for_query::{ForCondMap, ForInitMap, ForRecord, ForUpdateMap},
         if_query::{ElseRecMap, ElseRecord, IfRecord},
@@ -29,6 +30,19 @@ pub struct QLLoc {
 }
 
 impl QLLoc {
+    /// location of `range` ([start_line, start_column, end_line, end_column]) without validation
+    #[cfg(test)]
+    pub fn new(file_path: PathBuf, range: [usize; 4]) -> Self {
+        let [start_line, start_column, end_line, end_column] = range;
+        Self {
+            file_path,
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+        }
+    }
+
     pub fn get_content(&self) -> Result<String> {
         let file = File::open(&self.file_path)?;
         let reader = BufReader::new(file);
@@ -104,8 +118,55 @@ impl QLLoc {
             || (self.end_line == other.end_line && self.end_column > other.end_column)
     }
 
+    /// boundaries included, a condition can be a single call expression
     pub fn contains(&self, other: &QLLoc) -> bool {
-        self.start_before(other) && self.end_after(other)
+        !other.start_before(self) && !other.end_after(self)
+    }
+
+    /// returns [start_line, start_column, end_line, end_column]
+    pub fn to_range(&self) -> [usize; 4] {
+        [
+            self.start_line,
+            self.start_column,
+            self.end_line,
+            self.end_column,
+        ]
+    }
+
+    /// judge if the range in `fpath` lies inside this location, boundaries included
+    pub fn covers_range(&self, fpath: &Path, range: &[usize; 4]) -> bool {
+        if self.file_path != fpath {
+            return false;
+        }
+        let [ls, cs, le, ce] = *range;
+        let start_ok = (self.start_line, self.start_column) <= (ls, cs);
+        let end_ok = (le, ce) <= (self.end_line, self.end_column);
+        start_ok && end_ok
+    }
+
+    /// judge if the source location lies inside this location, boundaries included
+    pub fn contains_loc(&self, loc: &SrcLoc) -> bool {
+        match loc {
+            SrcLoc::NullLoc => false,
+            SrcLoc::Valid { fpath, line, col } => {
+                self.covers_range(fpath, &[*line, *col, *line, *col])
+            }
+        }
+    }
+
+    /// judge if the source location lies in the same file after the end of this location
+    pub fn ends_before_loc(&self, loc: &SrcLoc) -> bool {
+        match loc {
+            SrcLoc::NullLoc => false,
+            SrcLoc::Valid { fpath, line, col } => {
+                &self.file_path == fpath && (self.end_line, self.end_column) < (*line, *col)
+            }
+        }
+    }
+
+    /// judge if this location starts after the start position of the range in `fpath`
+    pub fn passes_range(&self, fpath: &Path, range: &[usize; 4]) -> bool {
+        self.file_path == fpath && (self.start_line, self.start_column) > (range[0], range[1])
     }
 
     pub fn compare(&self, other: &QLLoc) -> std::cmp::Ordering {
@@ -0,0 +1,305 @@
+//! Loop-bound constraints: constraints on loop conditions stated as the number of header hits,
+//! derived from the loop counts recorded in the executions.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::File,
+    io::BufWriter,
+    path::Path,
+};
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    analysis::constraint::{
+        inter::{
+            exec_tree::{action::ExecAction, ExecForest},
+            loc::SrcLoc,
+        },
+        RevAnalyzer,
+    },
+    feedback::branches::constraints::UBConstraint,
+};
+
+/// Header hit counts of the loop instances at a loop header.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct LoopCountDist {
+    /// header hit count -> number of loop instances ending with it
+    counts: BTreeMap<usize, usize>,
+    /// loop instances exceeding the loop limit of the runtime
+    exceeded: usize,
+}
+
+impl LoopCountDist {
+    pub fn get_counts(&self) -> &BTreeMap<usize, usize> {
+        &self.counts
+    }
+
+    pub fn get_exceeded(&self) -> usize {
+        self.exceeded
+    }
+
+    pub fn get_min(&self) -> Option<usize> {
+        self.counts.keys().next().copied()
+    }
+
+    pub fn get_max(&self) -> Option<usize> {
+        self.counts.keys().next_back().copied()
+    }
+
+    /// number of loop instances ending normally
+    pub fn get_total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (count, num) in other.counts.iter() {
+            *self.counts.entry(*count).or_insert(0) += num;
+        }
+        self.exceeded += other.exceeded;
+    }
+}
+
+pub type LoopCountMap = HashMap<SrcLoc, LoopCountDist>;
+
+/// Collect the loop counts of every loop header in the forest.
+pub fn collect_loop_counts(forest: &ExecForest) -> LoopCountMap {
+    let mut dists: LoopCountMap = HashMap::new();
+    for func_node_ptr in forest.func_node_bfs_iter() {
+        let func_node = func_node_ptr.borrow();
+        for act in func_node.iter_acts() {
+            let loop_act = match act {
+                ExecAction::Loop(loop_act) => loop_act,
+                _ => continue,
+            };
+            if let Some(count) = loop_act.get_out_count() {
+                let dist = dists.entry(loop_act.get_header_loc().clone()).or_default();
+                *dist.counts.entry(count).or_insert(0) += 1;
+            } else if loop_act.is_limit_exceeded() {
+                let dist = dists.entry(loop_act.get_header_loc().clone()).or_default();
+                dist.exceeded += 1;
+            }
+        }
+    }
+    dists
+}
+
+/// Loop header of the constraint, the header inside the condition range is preferred over the
+/// one at the same line.
+fn find_loop_header<'a>(dists: &'a LoopCountMap, cons: &UBConstraint) -> Option<&'a SrcLoc> {
+    let mut same_line = None;
+    for header in dists.keys() {
+        if cons.is_hit_loc(header).unwrap_or(false) {
+            return Some(header);
+        }
+        if let SrcLoc::Valid { fpath, line, .. } = header {
+            if *fpath == cons.fpath && *line == cons.range[0] {
+                same_line = Some(header);
+            }
+        }
+    }
+    same_line
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum LoopBound {
+    /// the body branch is unselected, more header hits than observed are needed
+    MoreThan(usize),
+    /// the exit branch is unselected, the loop needs to end before the observed header hits
+    FewerThan(usize),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoopCons {
+    cons: UBConstraint,
+    header_loc: String,
+    dist: LoopCountDist,
+    bound: LoopBound,
+}
+
+impl LoopCons {
+    /// Derive the bound from the distribution, None if no loop instance ends at the header.
+    pub fn derive(cons: &UBConstraint, header_loc: &SrcLoc, dist: LoopCountDist) -> Option<Self> {
+        // the body arm is unselected if the constraint result is true
+        let bound = if cons.get_res() {
+            LoopBound::MoreThan(dist.get_max()?)
+        } else {
+            LoopBound::FewerThan(dist.get_min()?)
+        };
+        Some(Self {
+            cons: cons.clone(),
+            header_loc: header_loc.to_string(),
+            dist,
+            bound,
+        })
+    }
+
+    pub fn get_cons(&self) -> &UBConstraint {
+        &self.cons
+    }
+
+    pub fn get_dist(&self) -> &LoopCountDist {
+        &self.dist
+    }
+
+    pub fn get_bound(&self) -> LoopBound {
+        self.bound
+    }
+}
+
+impl fmt::Display for LoopCons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bound {
+            LoopBound::MoreThan(count) => write!(
+                f,
+                "loop at {} needs more than {} iterations",
+                self.header_loc, count
+            ),
+            LoopBound::FewerThan(count) => write!(
+                f,
+                "loop at {} needs fewer than {} iterations",
+                self.header_loc, count
+            ),
+        }
+    }
+}
+
+impl RevAnalyzer {
+    /// Loop-bound constraint from the loop counts of all related executions,
+    /// None if the constraint is not on a loop header.
+    pub fn derive_loop_cons(&self, cons: &UBConstraint) -> Result<Option<LoopCons>> {
+        let func_name = cons.get_func_name()?;
+        let mut dists: LoopCountMap = HashMap::new();
+        for exec in self.get_related_execs(cons)? {
+            // loop counts are recorded at the loop exits, after the hits of the constraint, so
+            // the forests are not truncated
+            let forest = match exec.build_exec_forest_for_func(&func_name) {
+                Ok(forest) => forest,
+                Err(e) => {
+                    log::warn!("Failed to build execution forest of {}: {}", exec, e);
+                    continue;
+                }
+            };
+            for (header, dist) in collect_loop_counts(&forest) {
+                dists.entry(header).or_default().merge(&dist);
+            }
+        }
+        let header = match find_loop_header(&dists, cons) {
+            Some(header) => header.clone(),
+            None => return Ok(None),
+        };
+        let dist = dists.remove(&header).unwrap_or_default();
+        Ok(LoopCons::derive(cons, &header, dist))
+    }
+
+    /// Derive the loop-bound constraints and write them into `out_path` as JSON.
+    pub fn write_loop_cons<P: AsRef<Path>>(&self, out_path: P) -> Result<Vec<LoopCons>> {
+        let mut loop_cons_list = vec![];
+        for cons in self.iter_ub_cons() {
+            match self.derive_loop_cons(cons) {
+                Ok(Some(loop_cons)) => {
+                    log::info!("{}", loop_cons);
+                    loop_cons_list.push(loop_cons);
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to derive loop bound of {}: {}", cons, e),
+            }
+        }
+        let writer = BufWriter::new(File::create(out_path.as_ref())?);
+        serde_json::to_writer_pretty(writer, &loop_cons_list)?;
+        log::info!(
+            "{} loop-bound constraints written to {}",
+            loop_cons_list.len(),
+            out_path.as_ref().display()
+        );
+        Ok(loop_cons_list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_expe_exec, build_guard_forest, cov_export, ub_cons};
+    use serde_json::json;
+
+    #[test]
+    fn test_loop_cons() -> Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let src_path = work_dir.path().join("a.c");
+        let src = src_path.display();
+        let mut guard_lines = vec!["enter main".to_owned()];
+        for count in [3, 5, 3] {
+            for hit in 1..=count {
+                guard_lines.push(format!("Loop Hit: {src}:3:17 at count {hit}"));
+            }
+            guard_lines.push(format!(
+                "Out of Loop: {src}:3:17 {src}:6:3 at count {count}"
+            ));
+        }
+        guard_lines.push("return from main".to_owned());
+        let forest = build_guard_forest(&work_dir.path().join("guards"), &guard_lines)?;
+
+        let dists = collect_loop_counts(&forest);
+        assert_eq!(dists.len(), 1);
+        let (header, dist) = dists.iter().next().unwrap();
+        assert_eq!(dist.get_counts(), &BTreeMap::from([(3, 2), (5, 1)]));
+        assert_eq!(dist.get_total(), 3);
+
+        let cons = ub_cons("i < n", false, &src_path, [3, 17, 3, 22], "int main(int n)")?;
+        assert_eq!(find_loop_header(&dists, &cons), Some(header));
+        let loop_cons = LoopCons::derive(&cons, header, dist.clone()).unwrap();
+        assert_eq!(loop_cons.get_bound(), LoopBound::FewerThan(3));
+
+        let cons = ub_cons("i < n", true, &src_path, [3, 17, 3, 22], "int main(int n)")?;
+        let loop_cons = LoopCons::derive(&cons, header, dist.clone()).unwrap();
+        assert_eq!(loop_cons.get_bound(), LoopBound::MoreThan(5));
+        assert!(loop_cons
+            .to_string()
+            .ends_with("needs more than 5 iterations"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_derive_loop_cons() -> Result<()> {
+        crate::config::Config::init_test("cJSON");
+        let expe_dir = tempfile::tempdir()?;
+        let src_path = expe_dir.path().join("a.c");
+        let cons = ub_cons("i < n", true, &src_path, [3, 17, 3, 22], "int main(int n)")?;
+        std::fs::write(
+            expe_dir.path().join("constraints.json"),
+            serde_json::to_string(&[cons])?,
+        )?;
+        let cov = cov_export(json!([{
+            "branches": [],
+            "filenames": [src_path],
+            "regions": [],
+            "count": 1,
+            "name": "main",
+        }]));
+
+        let src = src_path.display();
+        for (exec_name, count) in [("short", 2), ("long", 4)] {
+            let mut guard_lines = vec!["enter main".to_owned()];
+            for hit in 1..=count {
+                guard_lines.push(format!("Loop Hit: {src}:3:17 at count {hit}"));
+                guard_lines.push(format!("Br Guard: {src}:3:17 {src}:3:5 1 {src}:4:7"));
+            }
+            guard_lines.push(format!(
+                "Out of Loop: {src}:3:17 {src}:6:3 at count {count}"
+            ));
+            guard_lines.push("return from main".to_owned());
+            add_expe_exec(expe_dir.path(), exec_name, &cov, &guard_lines)?;
+        }
+        // an execution with a damaged guard file is skipped
+        add_expe_exec(expe_dir.path(), "broken", &cov, &["enter main", "garbage"])?;
+
+        let analyzer = RevAnalyzer::from_expe_dir(expe_dir.path())?;
+        let cons = analyzer.iter_ub_cons().next().unwrap();
+        let loop_cons = analyzer.derive_loop_cons(cons)?.unwrap();
+        assert_eq!(loop_cons.get_bound(), LoopBound::MoreThan(4));
+        assert_eq!(loop_cons.get_dist().get_total(), 2);
+        Ok(())
+    }
+}
@@ -1,15 +1,36 @@
-use std::path::{Path, PathBuf};
+use std::{cell::OnceCell, fs::File, io::BufWriter, path::Path};
 
 use crate::{
-    analysis::constraint::exec_rec::ExecRec, deopt::utils::buffer_read_to_bytes, execution::expe,
-    feedback::branches::constraints::UBConstraint,
+    analysis::constraint::{
+        exec_rec::ExecRec,
+        inter::exec_tree::ExecForest,
+        intra::func_src_tree::{
+            build_func_src_forest, builder::FuncSrcForest, code_query::CodeQLRunner,
+        },
+        stmt_collect::{StmtCollector, TraceStmt},
+    },
+    deopt::utils::buffer_read_to_bytes,
+    feedback::branches::constraints::{
+        source_check::{SwitchCons, SwitchResolver, UBType},
+        UBConstraint,
+    },
 };
 use color_eyre::eyre::Result;
+use eyre::bail;
+use regex::Regex;
+use serde::Serialize;
 
+pub mod byte_attr;
+pub mod ctx_cov;
+pub mod dyn_call;
 pub mod exec_rec;
 pub mod inter;
 pub mod intra;
 
+pub mod loop_bound;
+pub mod path_pred;
+pub mod report;
+pub mod smt;
 pub mod stmt_collect;
 
 /**
@@ -21,10 +42,42 @@ pub mod stmt_collect;
 pub type Statement = String; // TODO: define a proper type for statements
 pub type ConsDFInfo = Vec<Statement>;
 
+/// Analysis result of a constraint, written next to `constraints.json`.
+#[derive(Serialize)]
+pub struct ConsAnalysis {
+    cons: UBConstraint,
+    df_info: ConsDFInfo,
+    /// constraint on the switch controlling expression, only for case clauses
+    switch_cons: Option<SwitchCons>,
+    /// reason that the analysis failed
+    error: Option<String>,
+}
+
+impl ConsAnalysis {
+    pub fn get_cons(&self) -> &UBConstraint {
+        &self.cons
+    }
+
+    pub fn get_df_info(&self) -> &ConsDFInfo {
+        &self.df_info
+    }
+
+    pub fn get_switch_cons(&self) -> Option<&SwitchCons> {
+        self.switch_cons.as_ref()
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
 pub struct RevAnalyzer {
     ub_cons_list: Vec<UBConstraint>,
     // work_dir: PathBuf,
     exec_list: Vec<ExecRec>,
+    /// built lazily since CodeQL queries are expensive
+    src_forest_cell: OnceCell<FuncSrcForest>,
+    switch_resolver_cell: OnceCell<SwitchResolver>,
 }
 
 impl RevAnalyzer {
@@ -55,23 +108,190 @@ impl RevAnalyzer {
             ub_cons_list,
             // work_dir: expe_dir.as_ref().to_path_buf(),
             exec_list,
+            src_forest_cell: OnceCell::new(),
+            switch_resolver_cell: OnceCell::new(),
         })
     }
 
+    pub fn iter_ub_cons(&self) -> impl Iterator<Item = &UBConstraint> {
+        self.ub_cons_list.iter()
+    }
+
+    fn get_src_forest(&self) -> Result<&FuncSrcForest> {
+        if let Some(forest) = self.src_forest_cell.get() {
+            return Ok(forest);
+        }
+        let forest = build_func_src_forest()?;
+        Ok(self.src_forest_cell.get_or_init(|| forest))
+    }
+
+    fn get_switch_resolver(&self) -> Result<&SwitchResolver> {
+        if let Some(resolver) = self.switch_resolver_cell.get() {
+            return Ok(resolver);
+        }
+        let resolver = SwitchResolver::from_codeql_runner(&CodeQLRunner::new())?;
+        Ok(self.switch_resolver_cell.get_or_init(|| resolver))
+    }
+
+    /// resolve the switch constraint if the constraint is a case clause
+    fn resolve_switch_cons(&self, cons: &UBConstraint) -> Result<Option<SwitchCons>> {
+        if cons.source_check()? != UBType::Switch {
+            return Ok(None);
+        }
+        let switch_cons = cons.get_switch_cons(self.get_switch_resolver()?)?;
+        Ok(Some(switch_cons))
+    }
+
     /**
      * analyze start
      */
 
+    /// executions whose coverage reaches the function containing the constraint
+    fn get_related_execs(&self, cons: &UBConstraint) -> Result<Vec<&ExecRec>> {
+        let mut related = vec![];
+        for exec in self.iter_execs() {
+            if exec.get_cov().reaches_cons_func(cons)? {
+                log::debug!("{} reaches function of {}", exec, cons);
+                related.push(exec);
+            }
+        }
+        Ok(related)
+    }
+
+    /// judge if a statement assigns to, increments or takes address of the operand
+    fn defines_operand(stmt: &str, operand: &str) -> bool {
+        let op = regex::escape(operand);
+        let pattern = format!(
+            r"\b{op}\b(\s*(->|\.)\s*\w+|\s*\[[^\]]*\])*\s*([-+*/%&|^]|<<|>>)?=[^=]|(\+\+|--)\s*\b{op}\b|\b{op}\b\s*(\+\+|--)|(^|[(,=])\s*&\s*\b{op}\b"
+        );
+        Regex::new(&pattern)
+            .map(|re| re.is_match(stmt))
+            .unwrap_or(false)
+    }
+
+    /// keep statements producing the operands of the constraint, in execution order
+    fn filter_operand_stmts(cons: &UBConstraint, stmts: Vec<Statement>) -> ConsDFInfo {
+        let operands = cons.get_cond_operands();
+        stmts
+            .into_iter()
+            .filter(|stmt| {
+                operands
+                    .iter()
+                    .any(|operand| Self::defines_operand(stmt, operand))
+            })
+            .collect()
+    }
+
+    /// statements executed before reaching the constraint in an execution
+    fn collect_exec_stmts(
+        exec: &ExecRec,
+        src_forest: &FuncSrcForest,
+        cons: &UBConstraint,
+    ) -> Result<(ExecForest, Vec<TraceStmt>)> {
+        let exec_forest = exec
+            .build_exec_forest_for_cons(cons)
+            .map_err(|e| eyre::eyre!("Failed to build execution forest of {}: {}", exec, e))?;
+        let collector = StmtCollector::new(&exec_forest, src_forest, cons);
+        let stmts = collector
+            .collect()
+            .map_err(|e| eyre::eyre!("Failed to collect statements from {}: {}", exec, e))?;
+        Ok((exec_forest, stmts))
+    }
+
     /**
      * analyze procedure
      */
     pub fn analyze_constraint(&self, cons: &UBConstraint) -> Result<ConsDFInfo> {
-        todo!()
+        let related = self.get_related_execs(cons)?;
+        if related.is_empty() {
+            bail!("No execution reaches the function of {}", cons);
+        }
+        let src_forest = self.get_src_forest()?;
+
+        let mut df_info: ConsDFInfo = vec![];
+        for exec in related {
+            let (_, stmts) = match Self::collect_exec_stmts(exec, src_forest, cons) {
+                Ok(res) => res,
+                Err(e) => {
+                    log::warn!("{}", e);
+                    continue;
+                }
+            };
+            let stmts = stmts.into_iter().map(|trace_stmt| trace_stmt.stmt).collect();
+            for stmt in Self::filter_operand_stmts(cons, stmts) {
+                if !df_info.contains(&stmt) {
+                    df_info.push(stmt);
+                }
+            }
+        }
+        Ok(df_info)
     }
 
     pub fn build(&self, cons: &UBConstraint) -> Result<ConsDFInfo> {
         self.analyze_constraint(cons)
     }
+
+    /// Analyze every constraint, failures of single constraints are recorded in the results.
+    pub fn analyze_all(&self) -> Vec<ConsAnalysis> {
+        let total = self.ub_cons_list.len();
+        let mut res_list = vec![];
+        for (idx, cons) in self.iter_ub_cons().enumerate() {
+            log::info!("Analyzing constraint {}/{}: {}", idx + 1, total, cons);
+            let (df_info, error) = match self.analyze_constraint(cons) {
+                Ok(df_info) => (df_info, None),
+                Err(e) => {
+                    log::warn!("Failed to analyze {}: {}", cons, e);
+                    (vec![], Some(e.to_string()))
+                }
+            };
+            let switch_cons = match self.resolve_switch_cons(cons) {
+                Ok(switch_cons) => switch_cons,
+                Err(e) => {
+                    log::warn!("Failed to resolve switch constraint of {}: {}", cons, e);
+                    None
+                }
+            };
+            res_list.push(ConsAnalysis {
+                cons: cons.clone(),
+                df_info,
+                switch_cons,
+                error,
+            });
+        }
+        res_list
+    }
+
+    /// analyze every constraint and save the results as json
+    pub fn write_analysis<P: AsRef<Path>>(&self, out_path: P) -> Result<Vec<ConsAnalysis>> {
+        let res_list = self.analyze_all();
+        let writer = BufWriter::new(File::create(out_path.as_ref())?);
+        serde_json::to_writer_pretty(writer, &res_list)?;
+        log::info!(
+            "Analysis of {} constraints saved to {}",
+            res_list.len(),
+            out_path.as_ref().display()
+        );
+        Ok(res_list)
+    }
 }
 
-// TODO: add unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defines_operand() {
+        assert!(RevAnalyzer::defines_operand("len = buf_size - 4", "len"));
+        assert!(RevAnalyzer::defines_operand("ctx->width += step", "ctx"));
+        assert!(RevAnalyzer::defines_operand("arr[i] = 0", "arr"));
+        assert!(RevAnalyzer::defines_operand("i++", "i"));
+        assert!(RevAnalyzer::defines_operand("read_header(fp, &hdr)", "hdr"));
+        assert!(!RevAnalyzer::defines_operand("len == 4", "len"));
+        assert!(!RevAnalyzer::defines_operand("total = len", "len"));
+        assert!(!RevAnalyzer::defines_operand("length = 0", "len"));
+        assert!(RevAnalyzer::defines_operand("p = &len", "len"));
+        assert!(RevAnalyzer::defines_operand("&len", "len"));
+        assert!(!RevAnalyzer::defines_operand("mask = flags & len", "len"));
+        assert!(!RevAnalyzer::defines_operand("ok = (flags && len)", "len"));
+    }
+}
@@ -0,0 +1,386 @@
+//! Path predicates of constraints: the branches taken from the entry to the last visit of the
+//! constraint function, ending with the negated constraint.
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    analysis::constraint::{
+        exec_rec::ExecRec,
+        inter::{
+            exec_tree::{
+                action::{ExecAction, JumpAction},
+                thread_tree::{FuncIter, SharedFuncNodePtr},
+                ExecForest,
+            },
+            loc::SrcLoc,
+        },
+        RevAnalyzer,
+    },
+    feedback::{
+        branches::constraints::{Range, RangeTrait, UBConstraint},
+        clang_coverage::{BranchCount, CodeCoverage},
+    },
+};
+
+/// A branch on the path with the direction taken.
+#[derive(Debug, Clone, Serialize)]
+pub struct PathPred {
+    func_name: String,
+    loc: String,
+    taken: bool,
+    /// source text of the condition, None if it is not found in the coverage
+    cond_text: Option<String>,
+    /// source range of the condition, set along with the text
+    cond_fpath: Option<PathBuf>,
+    cond_range: Option<Range>,
+    /// compare operands recorded with the branch
+    operands: Option<String>,
+}
+
+impl PathPred {
+    /// The condition text is taken from the smallest coverage branch containing the location
+    /// of the condition value, or the branch itself for merged conditions.
+    fn find_cond(
+        func_name: &str,
+        loc: &SrcLoc,
+        cov: &CodeCoverage,
+    ) -> Option<(String, PathBuf, Range)> {
+        let cov_func = cov.get_function_cov(func_name)?;
+        let (fpath, rng) = cov_func
+            .iter_cov_branches()
+            .filter_map(|cov_br| {
+                let fpath = cov_func.get_source_file_path_by_cov_branch(cov_br).ok()?;
+                let rng = cov_br.get_range().ok()?;
+                loc.inside_range(&rng, &fpath)
+                    .unwrap_or(false)
+                    .then_some((fpath, rng))
+            })
+            .min_by_key(|(_, rng)| (rng[2] - rng[0], rng[3].abs_diff(rng[1])))?;
+        let text = rng
+            .get_range_text_from_file(&fpath)
+            .map_err(|e| log::debug!("Failed to read condition at {}: {}", loc, e))
+            .ok()?;
+        Some((text, fpath, rng))
+    }
+
+    fn from_jump_act(func_name: &str, jump_act: &JumpAction, cov: &CodeCoverage) -> Self {
+        let cond_loc = jump_act
+            .get_val_loc()
+            .filter(|loc| loc.is_valid())
+            .unwrap_or(jump_act.get_from_loc());
+        let cond = Self::find_cond(func_name, cond_loc, cov);
+        let (cond_text, cond_fpath, cond_range) = match cond {
+            Some((text, fpath, rng)) => (Some(text), Some(fpath), Some(rng)),
+            None => (None, None, None),
+        };
+        Self {
+            func_name: func_name.to_owned(),
+            loc: jump_act.get_from_loc().to_string(),
+            taken: jump_act.get_cond_val(),
+            cond_text,
+            cond_fpath,
+            cond_range,
+            operands: jump_act.get_operands().map(|operands| operands.to_string()),
+        }
+    }
+
+    /// the unselected arm of the constraint
+    pub(super) fn from_cons(cons: &UBConstraint) -> Result<Self> {
+        Ok(Self {
+            func_name: cons.get_func_name()?,
+            loc: format!(
+                "{}:{}:{}",
+                cons.fpath.display(),
+                cons.range[0],
+                cons.range[1]
+            ),
+            taken: cons.get_res(),
+            cond_text: Some(cons.get_cond_expr().to_owned()),
+            cond_fpath: Some(cons.fpath.clone()),
+            cond_range: Some(cons.range),
+            operands: None,
+        })
+    }
+
+    pub fn get_func_name(&self) -> &str {
+        &self.func_name
+    }
+
+    pub fn get_loc(&self) -> &str {
+        &self.loc
+    }
+
+    pub fn is_taken(&self) -> bool {
+        self.taken
+    }
+
+    pub fn get_cond_text(&self) -> Option<&str> {
+        self.cond_text.as_deref()
+    }
+
+    /// source file and range of the condition
+    pub fn get_cond_src(&self) -> Option<(&Path, &Range)> {
+        self.cond_fpath.as_deref().zip(self.cond_range.as_ref())
+    }
+}
+
+/// Path condition of a constraint in one execution.
+#[derive(Serialize)]
+pub struct PathCond {
+    cons: UBConstraint,
+    exec_name: Option<String>,
+    preds: Vec<PathPred>,
+    /// reason that the extraction failed
+    error: Option<String>,
+}
+
+impl PathCond {
+    pub fn get_cons(&self) -> &UBConstraint {
+        &self.cons
+    }
+
+    pub fn get_preds(&self) -> &[PathPred] {
+        &self.preds
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// function visits from the entry, each with the number of its actions on the path
+type PathFrames = Vec<(SharedFuncNodePtr, usize)>;
+
+/// last visit of the function in execution order, searched thread by thread
+fn find_last_visit(forest: &ExecForest, func_name: &str) -> Option<SharedFuncNodePtr> {
+    let mut last = None;
+    for tree in forest.iter_trees() {
+        let mut stack = vec![tree.get_root_ptr()];
+        while let Some(node_ptr) = stack.pop() {
+            if node_ptr.borrow().get_func_name() == Some(func_name) {
+                last = Some(node_ptr.clone());
+            }
+            let children: Vec<SharedFuncNodePtr> = node_ptr.iter_sub_funcs().collect();
+            stack.extend(children.into_iter().rev());
+        }
+    }
+    last
+}
+
+/// Frames from the entry to the node, following spawned threads back to their creation sites.
+fn get_path_frames(forest: &ExecForest, node_ptr: SharedFuncNodePtr) -> PathFrames {
+    let mut frames = vec![];
+    let mut cur = node_ptr;
+    let mut cut = cur.borrow().get_len();
+    // each thread is crossed at most once
+    let mut crossed = 0;
+    loop {
+        frames.push((cur.clone(), cut));
+        let parent = {
+            let node = cur.borrow();
+            node.get_parent_ptr().zip(node.get_parent_idx())
+        };
+        if let Some((parent_ptr, parent_idx)) = parent {
+            cur = parent_ptr;
+            cut = parent_idx;
+            continue;
+        }
+
+        let creation_point = forest
+            .iter_trees()
+            .find(|tree| Rc::ptr_eq(&tree.get_root_ptr(), &cur))
+            .and_then(|tree| forest.get_creation_point(tree.get_tid()));
+        match creation_point {
+            Some(point) if crossed < forest.len() => {
+                crossed += 1;
+                cur = point.get_func_node_ptr();
+                cut = point.get_act_idx();
+            }
+            _ => break,
+        }
+    }
+    frames.reverse();
+    frames
+}
+
+/// Branches taken from the entry to the last visit of the constraint function in the forest,
+/// ending with the unselected arm of the constraint. Inside the last visit, branches after the
+/// first hit of the constraint are excluded.
+pub fn extract_path_preds(
+    forest: &ExecForest,
+    cov: &CodeCoverage,
+    cons: &UBConstraint,
+) -> Result<Vec<PathPred>> {
+    let func_name = cons.get_func_name()?;
+    let node_ptr = find_last_visit(forest, &func_name)
+        .ok_or_else(|| eyre::eyre!("Function {} is not visited in the execution", func_name))?;
+    let frames = get_path_frames(forest, node_ptr);
+
+    let mut preds = vec![];
+    for (frame_idx, (node_ptr, cut)) in frames.iter().enumerate() {
+        let node = node_ptr.borrow();
+        let frame_func = match node.get_func_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let is_last = frame_idx + 1 == frames.len();
+        for act in node.iter_acts().take(*cut) {
+            let jump_act = match act {
+                ExecAction::Intra(jump_act) => jump_act,
+                _ => continue,
+            };
+            if is_last {
+                if let Some(val_loc) = jump_act.get_val_loc() {
+                    if cons.is_hit_loc(val_loc)? {
+                        break;
+                    }
+                }
+            }
+            preds.push(PathPred::from_jump_act(frame_func, jump_act, cov));
+        }
+    }
+    preds.push(PathPred::from_cons(cons)?);
+    Ok(preds)
+}
+
+impl RevAnalyzer {
+    fn extract_exec_path_preds(exec: &ExecRec, cons: &UBConstraint) -> Result<Vec<PathPred>> {
+        let forest = exec
+            .build_exec_forest_for_cons(cons)
+            .map_err(|e| eyre::eyre!("Failed to build execution forest of {}: {}", exec, e))?;
+        extract_path_preds(&forest, exec.get_cov(), cons)
+            .map_err(|e| eyre::eyre!("Failed to extract path of {}: {}", exec, e))
+    }
+
+    /// Path condition from the first related execution the extraction succeeds on.
+    pub fn extract_path_cond(&self, cons: &UBConstraint) -> PathCond {
+        let mut path_cond = PathCond {
+            cons: cons.clone(),
+            exec_name: None,
+            preds: vec![],
+            error: None,
+        };
+        let related = match self.get_related_execs(cons) {
+            Ok(related) => related,
+            Err(e) => {
+                path_cond.error = Some(e.to_string());
+                return path_cond;
+            }
+        };
+
+        let mut errors = vec![];
+        for exec in related {
+            match Self::extract_exec_path_preds(exec, cons) {
+                Ok(preds) => {
+                    path_cond.exec_name = Some(exec.get_exec_name().to_owned());
+                    path_cond.preds = preds;
+                    return path_cond;
+                }
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        if errors.is_empty() {
+            errors.push(format!("No execution reaches the function of {}", cons));
+        }
+        path_cond.error = Some(errors.join("; "));
+        path_cond
+    }
+
+    /// Extract the path conditions of all constraints and write them into `out_path` as JSON.
+    pub fn write_path_conds<P: AsRef<Path>>(&self, out_path: P) -> Result<Vec<PathCond>> {
+        let path_conds: Vec<PathCond> = self
+            .iter_ub_cons()
+            .map(|cons| self.extract_path_cond(cons))
+            .collect();
+        let writer = BufWriter::new(File::create(out_path.as_ref())?);
+        serde_json::to_writer_pretty(writer, &path_conds)?;
+        log::info!("Path conditions written to {}", out_path.as_ref().display());
+        Ok(path_conds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_expe_exec, cov_export, ub_cons};
+    use serde_json::json;
+
+    #[test]
+    fn test_extract_path_preds() -> Result<()> {
+        crate::config::Config::init_test("cJSON");
+        let work_dir = tempfile::tempdir()?;
+        let src_path = work_dir.path().join("a.c");
+        let src_lines = [
+            "int check(int len) {",
+            "  if (len > 4) {",
+            "    return 1;",
+            "  }",
+            "  return 0;",
+            "}",
+            "int main(int argc) {",
+            "  if (argc == 2) {",
+            "    check(argc);",
+            "  }",
+            "}",
+        ];
+        std::fs::write(&src_path, src_lines.join("\n") + "\n")?;
+        let src = src_path.display();
+
+        let guard_lines = [
+            "enter main".to_owned(),
+            format!("Br Operands: {src}:8:12 eq s 2 2"),
+            format!("Br Guard: {src}:8:12 {src}:8:7 1 {src}:9:5"),
+            "enter check".to_owned(),
+            format!("Br Guard: {src}:2:11 {src}:2:7 0 {src}:5:3"),
+            "return from check".to_owned(),
+            "return from main".to_owned(),
+        ];
+        let cov = cov_export(json!([{
+            "branches": [[8, 7, 8, 16, 1, 0, 0, 0, 4]],
+            "filenames": [src_path],
+            "regions": [[7, 20, 11, 2, 1, 0, 0, 0]],
+            "count": 1,
+            "name": "main",
+        }, {
+            "branches": [[2, 7, 2, 14, 0, 1, 0, 0, 4]],
+            "filenames": [src_path],
+            "regions": [[1, 19, 6, 2, 1, 0, 0, 0]],
+            "count": 1,
+            "name": "check",
+        }]));
+        add_expe_exec(work_dir.path(), "1", &cov, &guard_lines)?;
+        let cons = ub_cons(
+            "len > 4",
+            true,
+            &src_path,
+            [2, 7, 2, 14],
+            "int check(int len)",
+        )?;
+        std::fs::write(
+            work_dir.path().join("constraints.json"),
+            serde_json::to_string(std::slice::from_ref(&cons))?,
+        )?;
+
+        let analyzer = RevAnalyzer::from_expe_dir(work_dir.path())?;
+        let path_cond = analyzer.extract_path_cond(&cons);
+        assert!(!path_cond.is_failed());
+        let preds = path_cond.get_preds();
+        assert_eq!(preds.len(), 2);
+        assert_eq!(preds[0].get_func_name(), "main");
+        assert!(preds[0].is_taken());
+        assert_eq!(preds[0].get_cond_text(), Some("argc == 2"));
+        assert_eq!(preds[0].operands.as_deref(), Some("2 eq 2"));
+        // the constraint branch itself ends the path, negated
+        assert_eq!(preds[1].get_cond_text(), Some("len > 4"));
+        assert!(preds[1].is_taken());
+        Ok(())
+    }
+}
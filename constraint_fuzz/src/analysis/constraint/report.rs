@@ -0,0 +1,341 @@
+//! Static HTML reports of constraints, one page per constraint and an index page linking them.
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+
+use crate::{
+    analysis::constraint::{stmt_collect::TraceStmt, RevAnalyzer},
+    deopt::utils::write_bytes_to_file,
+    feedback::branches::constraints::UBConstraint,
+};
+
+const INDEX_FNAME: &str = "index.html";
+
+const REPORT_STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }
+pre { background: #f6f8fa; padding: 8px; overflow-x: auto; }
+.missing { color: #b00; }";
+
+fn escape_html(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            _ => res.push(ch),
+        }
+    }
+    res
+}
+
+fn get_cons_page_fname(idx: usize) -> String {
+    format!("cons_{}.html", idx)
+}
+
+fn wrap_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        REPORT_STYLE,
+        body
+    )
+}
+
+/// Everything shown on the report page of a constraint.
+pub struct ConsReport<'a> {
+    cons: &'a UBConstraint,
+    /// executions reaching the function of the constraint
+    related_execs: Vec<String>,
+    /// execution the call chain and statements are collected from
+    exec_name: Option<String>,
+    call_chain: Vec<String>,
+    stmts: Vec<TraceStmt>,
+    /// reasons that the analysis is incomplete
+    errors: Vec<String>,
+}
+
+impl<'a> ConsReport<'a> {
+    pub fn new(cons: &'a UBConstraint) -> Self {
+        Self {
+            cons,
+            related_execs: vec![],
+            exec_name: None,
+            call_chain: vec![],
+            stmts: vec![],
+            errors: vec![],
+        }
+    }
+
+    pub fn get_cons(&self) -> &UBConstraint {
+        self.cons
+    }
+
+    pub fn get_call_chain(&self) -> &[String] {
+        &self.call_chain
+    }
+
+    pub fn get_stmts(&self) -> &[TraceStmt] {
+        &self.stmts
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn get_func_name(&self) -> String {
+        self.cons
+            .get_func_name()
+            .unwrap_or_else(|_| self.cons.get_func_sig().to_owned())
+    }
+
+    fn get_loc_str(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.cons.fpath.display(),
+            self.cons.range[0],
+            self.cons.range[1]
+        )
+    }
+
+    fn write_call_chain(&self, body: &mut String) -> std::fmt::Result {
+        writeln!(body, "<h2>Call Chain</h2>")?;
+        if self.call_chain.is_empty() {
+            return writeln!(
+                body,
+                "<p class=\"missing\">Function of the constraint is not found in the executions.</p>"
+            );
+        }
+        if let Some(exec_name) = &self.exec_name {
+            writeln!(body, "<p>Collected from {}</p>", escape_html(exec_name))?;
+        }
+        writeln!(body, "<ol>")?;
+        for func_name in self.call_chain.iter() {
+            writeln!(body, "<li><code>{}</code></li>", escape_html(func_name))?;
+        }
+        writeln!(body, "</ol>")
+    }
+
+    fn write_macro_mapping(&self, body: &mut String) -> std::fmt::Result {
+        writeln!(body, "<h2>Macro Mapping</h2>")?;
+        let mut mappings: Vec<(&String, &String)> = self.cons.get_macro_mapping().iter().collect();
+        if mappings.is_empty() {
+            return writeln!(body, "<p>No macros.</p>");
+        }
+        mappings.sort();
+        writeln!(body, "<table>\n<tr><th>Macro</th><th>Expansion</th></tr>")?;
+        for (name, expansion) in mappings {
+            writeln!(
+                body,
+                "<tr><td><code>{}</code></td><td><code>{}</code></td></tr>",
+                escape_html(name),
+                escape_html(expansion)
+            )?;
+        }
+        writeln!(body, "</table>")
+    }
+
+    fn write_stmts(&self, body: &mut String) -> std::fmt::Result {
+        writeln!(body, "<h2>Collected Statements</h2>")?;
+        if self.stmts.is_empty() {
+            return writeln!(body, "<p class=\"missing\">No statements collected.</p>");
+        }
+        writeln!(
+            body,
+            "<table>\n<tr><th>Function</th><th>Statement</th></tr>"
+        )?;
+        for stmt in self.stmts.iter() {
+            writeln!(
+                body,
+                "<tr><td><code>{}</code></td><td><pre>{}</pre></td></tr>",
+                escape_html(&stmt.func_name),
+                escape_html(&stmt.to_string())
+            )?;
+        }
+        writeln!(body, "</table>")
+    }
+
+    fn write_body(&self, body: &mut String) -> std::fmt::Result {
+        writeln!(body, "<p><a href=\"{}\">Back to index</a></p>", INDEX_FNAME)?;
+        writeln!(
+            body,
+            "<h1><code>{}</code></h1>",
+            escape_html(self.cons.get_cond_expr())
+        )?;
+        writeln!(body, "<table>")?;
+        writeln!(
+            body,
+            "<tr><th>Location</th><td>{}</td></tr>",
+            escape_html(&self.get_loc_str())
+        )?;
+        writeln!(
+            body,
+            "<tr><th>Function</th><td><code>{}</code></td></tr>",
+            escape_html(self.cons.get_func_sig())
+        )?;
+        writeln!(
+            body,
+            "<tr><th>Result Value</th><td>{}</td></tr>",
+            self.cons.get_res()
+        )?;
+        writeln!(
+            body,
+            "<tr><th>Related Executions</th><td>{}</td></tr>",
+            escape_html(&self.related_execs.join(", "))
+        )?;
+        writeln!(body, "</table>")?;
+
+        for err in self.errors.iter() {
+            writeln!(body, "<p class=\"missing\">{}</p>", escape_html(err))?;
+        }
+
+        self.write_call_chain(body)?;
+        writeln!(body, "<h2>Source Slice</h2>")?;
+        writeln!(body, "<pre>{}</pre>", escape_html(self.cons.get_slice()))?;
+        self.write_macro_mapping(body)?;
+        self.write_stmts(body)
+    }
+
+    pub fn to_html(&self) -> Result<String> {
+        let mut body = String::new();
+        self.write_body(&mut body)?;
+        Ok(wrap_page(self.cons.get_cond_expr(), &body))
+    }
+}
+
+impl RevAnalyzer {
+    /// Analyze the constraint for its report, failures are recorded in the report.
+    pub fn build_cons_report<'a>(&self, cons: &'a UBConstraint) -> ConsReport<'a> {
+        let mut report = ConsReport::new(cons);
+        let related = match self.get_related_execs(cons) {
+            Ok(related) => related,
+            Err(e) => {
+                report.errors.push(e.to_string());
+                return report;
+            }
+        };
+        report.related_execs = related
+            .iter()
+            .map(|exec| exec.get_exec_name().to_owned())
+            .collect();
+        if related.is_empty() {
+            report
+                .errors
+                .push("No execution reaches the function of the constraint".to_owned());
+            return report;
+        }
+        let src_forest = match self.get_src_forest() {
+            Ok(forest) => forest,
+            Err(e) => {
+                report.errors.push(e.to_string());
+                return report;
+            }
+        };
+
+        let func_name = report.get_func_name();
+        for exec in related {
+            match Self::collect_exec_stmts(exec, src_forest, cons) {
+                Ok((exec_forest, stmts)) => {
+                    report.exec_name = Some(exec.get_exec_name().to_owned());
+                    report.call_chain = exec_forest.get_call_chain(&func_name).unwrap_or_default();
+                    report.stmts = stmts;
+                    report.errors.clear();
+                    break;
+                }
+                Err(e) => report.errors.push(e.to_string()),
+            }
+        }
+        report
+    }
+
+    fn index_html(reports: &[ConsReport]) -> Result<String> {
+        let mut body = String::new();
+        writeln!(body, "<h1>Constraints</h1>")?;
+        writeln!(
+            body,
+            "<table>\n<tr><th>#</th><th>Condition</th><th>Function</th><th>Location</th><th>Call Depth</th><th>Statements</th></tr>"
+        )?;
+        for (idx, report) in reports.iter().enumerate() {
+            let depth = if report.call_chain.is_empty() {
+                "<span class=\"missing\">-</span>".to_owned()
+            } else {
+                report.call_chain.len().to_string()
+            };
+            writeln!(
+                body,
+                "<tr><td>{}</td><td><a href=\"{}\"><code>{}</code></a></td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                idx,
+                get_cons_page_fname(idx),
+                escape_html(report.cons.get_cond_expr()),
+                escape_html(&report.get_func_name()),
+                escape_html(&report.get_loc_str()),
+                depth,
+                report.stmts.len()
+            )?;
+        }
+        writeln!(body, "</table>")?;
+        Ok(wrap_page("Constraints", &body))
+    }
+
+    /// Write a page for each constraint and an index of them into `report_dir`,
+    /// returns the path of the index page.
+    pub fn write_html_report<P: AsRef<Path>>(&self, report_dir: P) -> Result<PathBuf> {
+        let report_dir = report_dir.as_ref();
+        std::fs::create_dir_all(report_dir)?;
+
+        let mut reports = vec![];
+        for (idx, cons) in self.iter_ub_cons().enumerate() {
+            log::info!("Building report of {}", cons);
+            let report = self.build_cons_report(cons);
+            let page_path = report_dir.join(get_cons_page_fname(idx));
+            write_bytes_to_file(&page_path, report.to_html()?.as_bytes())?;
+            reports.push(report);
+        }
+
+        let index_path = report_dir.join(INDEX_FNAME);
+        write_bytes_to_file(&index_path, Self::index_html(&reports)?.as_bytes())?;
+        log::info!("Constraint report written to {}", index_path.display());
+        Ok(index_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cons_report_html() -> Result<()> {
+        let cons: UBConstraint = serde_json::from_value(json!({
+            "cond_expr": "len < HDR_SIZE",
+            "res": false,
+            "fpath": "/src/parse.c",
+            "range": [12, 9, 12, 23],
+            "func_sig": "int parse(const char *buf, int len)",
+            "slice": "if (len < HDR_SIZE) return -1;",
+            "macro_mapping": {"HDR_SIZE": "(sizeof(struct hdr) << 1)"},
+        }))?;
+        let mut report = ConsReport::new(&cons);
+        report.call_chain = vec!["main".to_owned(), "parse".to_owned()];
+        report.stmts.push(TraceStmt {
+            depth: 1,
+            func_name: "parse".to_owned(),
+            stmt: "len = buf[0] & 0x7f".to_owned(),
+        });
+
+        let html = report.to_html()?;
+        assert!(html.contains("<code>len &lt; HDR_SIZE</code>"));
+        assert!(html.contains("(sizeof(struct hdr) &lt;&lt; 1)"));
+        assert!(html.contains("/src/parse.c:12:9"));
+        assert!(html.contains("<li><code>parse</code></li>"));
+        assert!(html.contains("  len = buf[0] &amp; 0x7f"));
+        Ok(())
+    }
+}
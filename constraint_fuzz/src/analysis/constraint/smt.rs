@@ -0,0 +1,806 @@
+//! Translation of path predicates into SMT-LIB2 queries over bit-vectors.
+//!
+//! Conditions are located in the clang AST of their functions by the source ranges recorded in
+//! the path predicates. Variables become free bit-vector constants, while the fuzz driver
+//! inputs `data` and `size` are modeled as a byte array indexed by 64-bit offsets and its
+//! length. Other memory reads, e.g. `p->len`, are approximated by free constants named by their
+//! source text. Since values may change between conditions, variables and memory reads are
+//! versioned per condition, e.g. `parse::len@2`. Constructs which can not be translated are
+//! reported in the query. Definitions are not propagated back to the inputs, so free constants
+//! are not related to the input, e.g. `buf` and `len` in a library function. They are reported
+//! as well, and such queries are not complete.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use clang_ast::SourceRange;
+use color_eyre::eyre::Result;
+use eyre::bail;
+
+use crate::{
+    analysis::constraint::{
+        path_pred::{PathCond, PathPred},
+        RevAnalyzer,
+    },
+    ast::{BinaryOpcode, Clang, CommomHelper, Node, Type, UnaryOpcode},
+    deopt::{utils::write_bytes_to_file, Deopt},
+    execution::Executor,
+    feedback::branches::constraints::Range,
+};
+
+/// names of the fuzz driver inputs
+const INPUT_DATA: &str = "data";
+const INPUT_SIZE: &str = "size";
+/// symbol of the input bytes
+const INPUT_BYTES: &str = "data[]";
+const PTR_WIDTH: u32 = 64;
+
+pub fn get_smt_query_fname(idx: usize) -> String {
+    format!("cons_{}.smt2", idx)
+}
+
+/// width and signedness of an integer or pointer type
+fn get_int_type(ty: &Type) -> Option<(u32, bool)> {
+    let name = ty
+        .get_desugared_type()
+        .unwrap_or_else(|| ty.get_type_name());
+    let name = name
+        .replace("const ", "")
+        .replace("volatile ", "")
+        .trim()
+        .to_owned();
+    if name.contains('*') || name.contains('[') {
+        return Some((PTR_WIDTH, false));
+    }
+    if name.starts_with("enum ") {
+        return Some((32, true));
+    }
+    let res = match name.as_str() {
+        "_Bool" | "bool" | "unsigned char" | "uint8_t" => (8, false),
+        "char" | "signed char" | "int8_t" => (8, true),
+        "short" | "short int" | "signed short" | "int16_t" => (16, true),
+        "unsigned short" | "unsigned short int" | "uint16_t" => (16, false),
+        "int" | "signed int" | "signed" | "int32_t" => (32, true),
+        "unsigned int" | "unsigned" | "uint32_t" => (32, false),
+        "long" | "long int" | "long long" | "long long int" | "int64_t" | "ssize_t"
+        | "ptrdiff_t" | "intptr_t" | "off_t" => (64, true),
+        "unsigned long"
+        | "unsigned long int"
+        | "unsigned long long"
+        | "unsigned long long int"
+        | "uint64_t"
+        | "size_t"
+        | "uintptr_t" => (64, false),
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn get_node_type(node: &Node) -> Option<&Type> {
+    let ty = match &node.kind {
+        Clang::BinaryOperator(bo) => &bo.r#type,
+        Clang::UnaryOperator(uo) => &uo.r#type,
+        Clang::ParenExpr(pe) => &pe.r#type,
+        Clang::ImplicitCastExpr(ice) => &ice.r#type,
+        Clang::CStyleCastExpr(cce) => &cce.r#type,
+        Clang::DeclRefExpr(dre) => &dre.r#type,
+        Clang::IntegerLiteral(il) => &il.r#type,
+        Clang::CharacterLiteral(cl) => &cl.r#type,
+        Clang::ArraySubscriptExpr(ase) => &ase.r#type,
+        Clang::MemberExpr(me) => &me.r#type,
+        Clang::ConstantExpr(ce) => &ce.r#type,
+        _ => return None,
+    };
+    Some(ty)
+}
+
+fn get_node_int_type(node: &Node) -> Result<(u32, bool)> {
+    let ty = get_node_type(node)
+        .ok_or_else(|| eyre::eyre!("no type of expression {}", get_kind_name(node)))?;
+    get_int_type(ty).ok_or_else(|| eyre::eyre!("unsupported type {}", ty.get_type_name()))
+}
+
+fn is_ptr_node(node: &Node) -> bool {
+    get_node_type(node).is_some_and(|ty| {
+        let name = ty.get_type_name();
+        name.contains('*') || name.contains('[')
+    })
+}
+
+fn get_expr_range(node: &Node) -> Option<&SourceRange> {
+    match &node.kind {
+        Clang::BinaryOperator(bo) => Some(&bo.range),
+        Clang::UnaryOperator(uo) => Some(&uo.range),
+        Clang::ParenExpr(pe) => Some(&pe.range),
+        Clang::ImplicitCastExpr(ice) => Some(&ice.range),
+        Clang::CStyleCastExpr(cce) => Some(&cce.range),
+        Clang::DeclRefExpr(dre) => Some(&dre.range),
+        Clang::IntegerLiteral(il) => Some(&il.range),
+        Clang::CharacterLiteral(cl) => Some(&cl.range),
+        Clang::CallExpr(ce) => Some(&ce.range),
+        Clang::ConstantExpr(ce) => Some(&ce.range),
+        _ => None,
+    }
+}
+
+fn get_kind_name(node: &Node) -> String {
+    let debug = format!("{:?}", node.kind);
+    debug
+        .split(|ch: char| !ch.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// The outermost expression starting at the beginning of `rng`, in the expanded source.
+pub fn find_cond_node<'a>(func_ast: &'a Node, rng: &Range) -> Option<&'a Node> {
+    let mut stack = vec![func_ast];
+    while let Some(node) = stack.pop() {
+        let begin = get_expr_range(node).and_then(|sr| {
+            sr.begin
+                .expansion_loc
+                .as_ref()
+                .or(sr.begin.spelling_loc.as_ref())
+        });
+        if begin.is_some_and(|loc| loc.line == rng[0] && loc.col == rng[1]) {
+            return Some(node);
+        }
+        stack.extend(node.inner.iter().rev());
+    }
+    None
+}
+
+/// quoted SMT symbol
+fn to_symbol(name: &str) -> String {
+    format!("|{}|", name.replace(['|', '\\'], "_"))
+}
+
+fn bv_const(val: u128, width: u32) -> String {
+    let mask = if width >= 128 {
+        u128::MAX
+    } else {
+        (1u128 << width) - 1
+    };
+    format!("(_ bv{} {})", val & mask, width)
+}
+
+#[derive(Clone)]
+struct BvTerm {
+    expr: String,
+    width: u32,
+    signed: bool,
+}
+
+impl BvTerm {
+    /// extend or truncate to `width`, then take the signedness of the target type
+    fn resize(self, width: u32, signed: bool) -> Self {
+        let expr = if width > self.width {
+            let ext = if self.signed {
+                "sign_extend"
+            } else {
+                "zero_extend"
+            };
+            format!("((_ {} {}) {})", ext, width - self.width, self.expr)
+        } else if width < self.width {
+            format!("((_ extract {} 0) {})", width - 1, self.expr)
+        } else {
+            self.expr
+        };
+        Self {
+            expr,
+            width,
+            signed,
+        }
+    }
+}
+
+enum Term {
+    Bool(String),
+    Bv(BvTerm),
+}
+
+impl Term {
+    fn into_bool(self) -> String {
+        match self {
+            Term::Bool(expr) => expr,
+            Term::Bv(bv) => format!("(not (= {} {}))", bv.expr, bv_const(0, bv.width)),
+        }
+    }
+
+    fn into_bv(self, width: u32, signed: bool) -> BvTerm {
+        match self {
+            Term::Bool(expr) => BvTerm {
+                expr: format!(
+                    "(ite {} {} {})",
+                    expr,
+                    bv_const(1, width),
+                    bv_const(0, width)
+                ),
+                width,
+                signed,
+            },
+            Term::Bv(bv) => bv,
+        }
+    }
+}
+
+/// An SMT-LIB2 query built from the predicates of a path.
+#[derive(Default)]
+pub struct SmtQuery {
+    /// symbol to sort
+    decls: BTreeMap<String, String>,
+    asserts: Vec<String>,
+    /// side conditions of the expression being translated, e.g. bounds of input reads
+    side_conds: Vec<String>,
+    /// function of the condition being translated
+    cur_func: String,
+    /// number of conditions added, the version of the variables in the next condition
+    cond_cnt: usize,
+    /// predicates which are not translated, with the reasons
+    unsupported: Vec<String>,
+    /// free constants of the condition being translated, which are not bound to the input
+    unbound: BTreeSet<String>,
+    uses_array: bool,
+}
+
+impl SmtQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_unsupported(&self) -> &[String] {
+        &self.unsupported
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.unsupported.is_empty()
+    }
+
+    fn declare(&mut self, name: &str, sort: String) -> String {
+        let symbol = to_symbol(name);
+        self.decls.entry(symbol.clone()).or_insert(sort);
+        symbol
+    }
+
+    /// Variable of the current condition, the input size is shared by all conditions.
+    fn declare_var(&mut self, name: &str, width: u32, signed: bool) -> Term {
+        if name == INPUT_SIZE {
+            return self.declare_bv(name, width, signed);
+        }
+        let versioned = format!("{}::{}@{}", self.cur_func, name, self.cond_cnt);
+        self.unbound.insert(name.to_owned());
+        self.declare_bv(&versioned, width, signed)
+    }
+
+    fn declare_bv(&mut self, name: &str, width: u32, signed: bool) -> Term {
+        let expr = self.declare(name, format!("(_ BitVec {})", width));
+        Term::Bv(BvTerm {
+            expr,
+            width,
+            signed,
+        })
+    }
+
+    /// source text of lvalues, used as the names of approximated memory reads
+    fn render_lvalue(node: &Node) -> Result<String> {
+        let text = match &node.kind {
+            Clang::ParenExpr(_) => format!("({})", Self::render_lvalue(&node.inner[0])?),
+            Clang::ImplicitCastExpr(_) => Self::render_lvalue(&node.inner[0])?,
+            Clang::DeclRefExpr(dre) => dre.get_name_as_string(),
+            Clang::IntegerLiteral(il) => il.value.to_string(),
+            Clang::MemberExpr(me) => format!(
+                "{}{}{}",
+                Self::render_lvalue(&node.inner[0])?,
+                if me.is_arrow { "->" } else { "." },
+                me.get_name_as_string()
+            ),
+            Clang::ArraySubscriptExpr(ase) => format!(
+                "{}[{}]",
+                Self::render_lvalue(ase.get_lhs(node))?,
+                Self::render_lvalue(ase.get_rhs(node))?
+            ),
+            Clang::UnaryOperator(uo) if uo.opcode == UnaryOpcode::Deref => {
+                format!("*{}", Self::render_lvalue(&node.inner[0])?)
+            }
+            _ => bail!("unsupported memory access via {}", get_kind_name(node)),
+        };
+        Ok(text)
+    }
+
+    fn is_input_data(node: &Node) -> bool {
+        match &node.ignore_cast().ignore_parenexpr().kind {
+            Clang::DeclRefExpr(dre) => dre.get_name_as_string() == INPUT_DATA,
+            _ => false,
+        }
+    }
+
+    /// byte of the input at `idx`, guarded by the input size
+    fn read_input_byte(&mut self, node: &Node, idx: BvTerm) -> Result<Term> {
+        let (width, signed) = get_node_int_type(node)?;
+        if width != 8 {
+            bail!("input read of {} bits", width);
+        }
+        self.uses_array = true;
+        let bytes = self.declare(
+            INPUT_BYTES,
+            format!("(Array (_ BitVec {0}) (_ BitVec 8))", PTR_WIDTH),
+        );
+        let size = self.declare(INPUT_SIZE, format!("(_ BitVec {})", PTR_WIDTH));
+        let idx = idx.resize(PTR_WIDTH, false);
+        self.side_conds
+            .push(format!("(bvult {} {})", idx.expr, size));
+        Ok(Term::Bv(BvTerm {
+            expr: format!("(select {} {})", bytes, idx.expr),
+            width,
+            signed,
+        }))
+    }
+
+    fn read_memory(&mut self, node: &Node) -> Result<Term> {
+        let (width, signed) = get_node_int_type(node)?;
+        let name = Self::render_lvalue(node)?;
+        Ok(self.declare_var(&name, width, signed))
+    }
+
+    fn translate_bv(&mut self, node: &Node) -> Result<BvTerm> {
+        let (width, signed) = get_node_int_type(node)?;
+        Ok(self.translate(node)?.into_bv(width, signed))
+    }
+
+    fn translate_binary(&mut self, node: &Node, opcode: &BinaryOpcode) -> Result<Term> {
+        let (lhs_node, rhs_node) = (&node.inner[0], &node.inner[1]);
+        match opcode {
+            BinaryOpcode::LAnd | BinaryOpcode::LOr => {
+                let lhs = self.translate(lhs_node)?.into_bool();
+                // the rhs is evaluated depending on the lhs, so its side conditions stay in it
+                let outer_conds = std::mem::take(&mut self.side_conds);
+                let rhs = self.translate(rhs_node);
+                let rhs_conds = std::mem::replace(&mut self.side_conds, outer_conds);
+                let rhs = rhs?.into_bool();
+                let rhs = if rhs_conds.is_empty() {
+                    rhs
+                } else {
+                    format!("(and {} {})", rhs_conds.join(" "), rhs)
+                };
+                let op = if *opcode == BinaryOpcode::LAnd {
+                    "and"
+                } else {
+                    "or"
+                };
+                return Ok(Term::Bool(format!("({} {} {})", op, lhs, rhs)));
+            }
+            BinaryOpcode::LT
+            | BinaryOpcode::GT
+            | BinaryOpcode::LE
+            | BinaryOpcode::GE
+            | BinaryOpcode::EQ
+            | BinaryOpcode::NE => {
+                let lhs = self.translate_bv(lhs_node)?;
+                let rhs = self.translate_bv(rhs_node)?;
+                // operands are converted by implicit casts, widths only differ on pointers
+                let width = lhs.width.max(rhs.width);
+                let signed = lhs.signed;
+                let lhs = lhs.resize(width, signed);
+                let rhs = rhs.resize(width, signed);
+                let expr = match (opcode, signed) {
+                    (BinaryOpcode::EQ, _) => format!("(= {} {})", lhs.expr, rhs.expr),
+                    (BinaryOpcode::NE, _) => format!("(not (= {} {}))", lhs.expr, rhs.expr),
+                    (BinaryOpcode::LT, true) => format!("(bvslt {} {})", lhs.expr, rhs.expr),
+                    (BinaryOpcode::LT, false) => format!("(bvult {} {})", lhs.expr, rhs.expr),
+                    (BinaryOpcode::GT, true) => format!("(bvsgt {} {})", lhs.expr, rhs.expr),
+                    (BinaryOpcode::GT, false) => format!("(bvugt {} {})", lhs.expr, rhs.expr),
+                    (BinaryOpcode::LE, true) => format!("(bvsle {} {})", lhs.expr, rhs.expr),
+                    (BinaryOpcode::LE, false) => format!("(bvule {} {})", lhs.expr, rhs.expr),
+                    (BinaryOpcode::GE, true) => format!("(bvsge {} {})", lhs.expr, rhs.expr),
+                    (_, _) => format!("(bvuge {} {})", lhs.expr, rhs.expr),
+                };
+                return Ok(Term::Bool(expr));
+            }
+            _ => {}
+        }
+
+        if is_ptr_node(lhs_node) || is_ptr_node(rhs_node) {
+            bail!("pointer arithmetic");
+        }
+        let (width, signed) = get_node_int_type(node)?;
+        let op = match (opcode, signed) {
+            (BinaryOpcode::Add, _) => "bvadd",
+            (BinaryOpcode::Sub, _) => "bvsub",
+            (BinaryOpcode::Mul, _) => "bvmul",
+            (BinaryOpcode::Div, true) => "bvsdiv",
+            (BinaryOpcode::Div, false) => "bvudiv",
+            (BinaryOpcode::Rem, true) => "bvsrem",
+            (BinaryOpcode::Rem, false) => "bvurem",
+            (BinaryOpcode::Shl, _) => "bvshl",
+            (BinaryOpcode::Shr, true) => "bvashr",
+            (BinaryOpcode::Shr, false) => "bvlshr",
+            (BinaryOpcode::And, _) => "bvand",
+            (BinaryOpcode::Or, _) => "bvor",
+            (BinaryOpcode::Xor, _) => "bvxor",
+            _ => bail!("unsupported binary operator {:?}", opcode),
+        };
+        let lhs = self.translate_bv(lhs_node)?.resize(width, signed);
+        let rhs = self.translate_bv(rhs_node)?.resize(width, signed);
+        Ok(Term::Bv(BvTerm {
+            expr: format!("({} {} {})", op, lhs.expr, rhs.expr),
+            width,
+            signed,
+        }))
+    }
+
+    fn translate_unary(&mut self, node: &Node, opcode: &UnaryOpcode) -> Result<Term> {
+        let inner = &node.inner[0];
+        let op = match opcode {
+            UnaryOpcode::LNot => {
+                let inner = self.translate(inner)?.into_bool();
+                return Ok(Term::Bool(format!("(not {})", inner)));
+            }
+            UnaryOpcode::Plus => return self.translate(inner),
+            UnaryOpcode::Deref if Self::is_input_data(inner) => {
+                return self.read_input_byte(
+                    node,
+                    BvTerm {
+                        expr: bv_const(0, PTR_WIDTH),
+                        width: PTR_WIDTH,
+                        signed: false,
+                    },
+                );
+            }
+            UnaryOpcode::Deref => return self.read_memory(node),
+            UnaryOpcode::Minus => "bvneg",
+            UnaryOpcode::Not => "bvnot",
+            _ => bail!("unsupported unary operator {:?}", opcode),
+        };
+        let (width, signed) = get_node_int_type(node)?;
+        let inner = self.translate_bv(inner)?.resize(width, signed);
+        Ok(Term::Bv(BvTerm {
+            expr: format!("({} {})", op, inner.expr),
+            width,
+            signed,
+        }))
+    }
+
+    fn translate(&mut self, node: &Node) -> Result<Term> {
+        let term = match &node.kind {
+            Clang::ParenExpr(_) | Clang::ConstantExpr(_) => self.translate(&node.inner[0])?,
+            Clang::ImplicitCastExpr(_) | Clang::CStyleCastExpr(_) => {
+                let (width, signed) = get_node_int_type(node)?;
+                let inner = self.translate_bv(&node.inner[0])?;
+                Term::Bv(inner.resize(width, signed))
+            }
+            Clang::IntegerLiteral(il) => {
+                let (width, signed) = get_node_int_type(node)?;
+                let val: u128 = il
+                    .value
+                    .parse()
+                    .map_err(|_| eyre::eyre!("integer literal {}", il.value))?;
+                Term::Bv(BvTerm {
+                    expr: bv_const(val, width),
+                    width,
+                    signed,
+                })
+            }
+            Clang::CharacterLiteral(cl) => {
+                let (width, signed) = get_node_int_type(node)?;
+                Term::Bv(BvTerm {
+                    expr: bv_const(cl.value as u128, width),
+                    width,
+                    signed,
+                })
+            }
+            Clang::DeclRefExpr(dre) => {
+                if dre.referenced_decl.kind == clang_ast::Kind::EnumConstantDecl {
+                    bail!("enum constant {}", dre.get_name_as_string());
+                }
+                let (width, signed) = get_node_int_type(node)?;
+                self.declare_var(&dre.get_name_as_string(), width, signed)
+            }
+            Clang::ArraySubscriptExpr(ase) if Self::is_input_data(ase.get_lhs(node)) => {
+                let idx = self.translate_bv(ase.get_rhs(node))?;
+                self.read_input_byte(node, idx)?
+            }
+            Clang::ArraySubscriptExpr(_) | Clang::MemberExpr(_) => self.read_memory(node)?,
+            Clang::BinaryOperator(bo) => self.translate_binary(node, &bo.opcode)?,
+            Clang::UnaryOperator(uo) => self.translate_unary(node, &uo.opcode)?,
+            Clang::CallExpr(_) => bail!(
+                "function call {}",
+                Self::render_lvalue(&node.inner[0]).unwrap_or_default()
+            ),
+            _ => bail!("unsupported expression {}", get_kind_name(node)),
+        };
+        Ok(term)
+    }
+
+    /// Assert the condition of `func_name` with the direction taken, returns false if it is
+    /// unsupported. Operands not bound to the input are reported, while the condition is
+    /// still asserted.
+    pub fn add_cond(&mut self, cond_node: &Node, taken: bool, func_name: &str, desc: &str) -> bool {
+        self.side_conds.clear();
+        self.unbound.clear();
+        self.cur_func = func_name.to_owned();
+        let res = self.translate(cond_node);
+        self.cond_cnt += 1;
+        let cond = match res {
+            Ok(term) => term.into_bool(),
+            Err(e) => {
+                self.unsupported.push(format!("{}: {}", desc, e));
+                return false;
+            }
+        };
+        for name in std::mem::take(&mut self.unbound) {
+            self.unsupported.push(format!(
+                "{}: operand {} is not bound to the input",
+                desc, name
+            ));
+        }
+        // side conditions outside of the short-circuit operands hold in both directions
+        for side_cond in std::mem::take(&mut self.side_conds) {
+            self.asserts.push(format!("(assert {})", side_cond));
+        }
+        let cond = if taken {
+            cond
+        } else {
+            format!("(not {})", cond)
+        };
+        self.asserts.push(format!("; {}\n(assert {})", desc, cond));
+        true
+    }
+
+    pub fn add_unsupported(&mut self, desc: &str, reason: &str) {
+        self.unsupported.push(format!("{}: {}", desc, reason));
+    }
+
+    pub fn to_smt2(&self, header: &str) -> String {
+        let mut res = String::new();
+        for line in header.lines() {
+            let _ = writeln!(res, "; {}", line);
+        }
+        for item in self.unsupported.iter() {
+            let _ = writeln!(res, "; unsupported {}", item.replace('\n', " "));
+        }
+        let logic = if self.uses_array { "QF_ABV" } else { "QF_BV" };
+        let _ = writeln!(res, "(set-logic {})", logic);
+        for (symbol, sort) in self.decls.iter() {
+            let _ = writeln!(res, "(declare-const {} {})", symbol, sort);
+        }
+        for assert in self.asserts.iter() {
+            let _ = writeln!(res, "{}", assert);
+        }
+        res.push_str("(check-sat)\n(get-model)\n");
+        res
+    }
+}
+
+/// function ASTs of library sources, failures are cached as well
+struct FuncAstCache<'a> {
+    deopt: &'a Deopt,
+    asts: HashMap<(PathBuf, String), Option<Node>>,
+}
+
+impl<'a> FuncAstCache<'a> {
+    fn new(deopt: &'a Deopt) -> Self {
+        Self {
+            deopt,
+            asts: HashMap::new(),
+        }
+    }
+
+    fn get_func_ast(&mut self, fpath: &Path, func_name: &str) -> Option<&Node> {
+        let key = (fpath.to_owned(), func_name.to_owned());
+        let deopt = self.deopt;
+        self.asts
+            .entry(key)
+            .or_insert_with(|| {
+                Executor::extract_src_func_ast(fpath, deopt, func_name)
+                    .map_err(|e| log::warn!("{}", e))
+                    .ok()
+            })
+            .as_ref()
+    }
+}
+
+impl RevAnalyzer {
+    fn build_smt_query(cache: &mut FuncAstCache, preds: &[PathPred]) -> SmtQuery {
+        let mut query = SmtQuery::new();
+        for pred in preds {
+            let desc = format!(
+                "{} {} at {}",
+                pred.get_cond_text().unwrap_or("<unknown>"),
+                if pred.is_taken() {
+                    "taken"
+                } else {
+                    "not taken"
+                },
+                pred.get_loc()
+            );
+            let (fpath, rng) = match pred.get_cond_src() {
+                Some(src) => src,
+                None => {
+                    query.add_unsupported(&desc, "condition source not found");
+                    continue;
+                }
+            };
+            let func_ast = match cache.get_func_ast(fpath, pred.get_func_name()) {
+                Some(ast) => ast,
+                None => {
+                    query.add_unsupported(&desc, "function AST not available");
+                    continue;
+                }
+            };
+            match find_cond_node(func_ast, rng) {
+                Some(cond_node) => {
+                    query.add_cond(cond_node, pred.is_taken(), pred.get_func_name(), &desc);
+                }
+                None => query.add_unsupported(&desc, "condition not found in the AST"),
+            }
+        }
+        query
+    }
+
+    /// Write a query for each path condition into `smt_dir`, returns the number of complete
+    /// queries. Constraints without a path are queried alone.
+    pub fn write_smt_queries<P: AsRef<Path>>(
+        &self,
+        deopt: &Deopt,
+        path_conds: &[PathCond],
+        smt_dir: P,
+    ) -> Result<usize> {
+        let smt_dir = smt_dir.as_ref();
+        std::fs::create_dir_all(smt_dir)?;
+        let mut cache = FuncAstCache::new(deopt);
+
+        let mut complete = 0;
+        for (idx, path_cond) in path_conds.iter().enumerate() {
+            let cons = path_cond.get_cons();
+            let preds = if path_cond.get_preds().is_empty() {
+                vec![PathPred::from_cons(cons)?]
+            } else {
+                path_cond.get_preds().to_vec()
+            };
+            let query = Self::build_smt_query(&mut cache, &preds);
+            if query.is_complete() {
+                complete += 1;
+            }
+            let header = format!("constraint: {}", cons);
+            let query_path = smt_dir.join(get_smt_query_fname(idx));
+            write_bytes_to_file(&query_path, query.to_smt2(&header).as_bytes())?;
+        }
+        log::info!(
+            "{} of {} SMT queries are complete, written to {}",
+            complete,
+            path_conds.len(),
+            smt_dir.display()
+        );
+        Ok(complete)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loc(line: usize, col: usize) -> String {
+        format!(r#"{{"offset": 0, "file": "/src/a.c", "line": {line}, "col": {col}, "tokLen": 1}}"#)
+    }
+
+    /// JSON of an expression node at line 3, in the field order of clang dumps
+    fn expr(kind: &str, ty_name: &str, col: usize, extra: &str, inner: &[String]) -> String {
+        let loc = loc(3, col);
+        format!(
+            r#"{{"id": "0x1", "kind": "{kind}", "range": {{"begin": {loc}, "end": {loc}}}, "type": {{"qualType": "{ty_name}"}}{extra}, "inner": [{}]}}"#,
+            inner.join(", ")
+        )
+    }
+
+    fn decl_ref(name: &str, ty_name: &str, col: usize) -> String {
+        let decl = format!(
+            r#", "referencedDecl": {{"id": "0x2", "kind": "ParmVarDecl", "name": "{name}", "type": {{"qualType": "{ty_name}"}}}}"#
+        );
+        let dre = expr("DeclRefExpr", ty_name, col, &decl, &[]);
+        expr("ImplicitCastExpr", ty_name, col, "", &[dre])
+    }
+
+    fn int_lit(val: &str, col: usize) -> String {
+        expr(
+            "IntegerLiteral",
+            "int",
+            col,
+            &format!(r#", "value": "{val}""#),
+            &[],
+        )
+    }
+
+    #[test]
+    fn test_smt_query() -> Result<()> {
+        // data[1] > 4 && !len, as dumped by clang
+        let subscript = format!(
+            r#"{{"id": "0x3", "kind": "ArraySubscriptExpr", "range": {{"begin": {}, "end": {}}}, "type": {{"qualType": "const uint8_t", "desugaredQualType": "const unsigned char"}}, "inner": [{}, {}]}}"#,
+            loc(3, 7),
+            loc(3, 13),
+            decl_ref("data", "const uint8_t *", 7),
+            int_lit("1", 12)
+        );
+        let byte = expr("ImplicitCastExpr", "uint8_t", 7, "", &[subscript]);
+        let byte = expr("ImplicitCastExpr", "int", 7, "", &[byte]);
+        let cmp = expr(
+            "BinaryOperator",
+            "int",
+            7,
+            r#", "opcode": ">""#,
+            &[byte, int_lit("4", 17)],
+        );
+        let not = expr(
+            "UnaryOperator",
+            "int",
+            21,
+            r#", "isPostfix": false, "opcode": "!""#,
+            &[decl_ref("len", "size_t", 22)],
+        );
+        let cond = expr(
+            "BinaryOperator",
+            "int",
+            7,
+            r#", "opcode": "&&""#,
+            &[cmp.clone(), not.clone()],
+        );
+        let cmp_node: Node = serde_json::from_str(&cmp)?;
+        let func_ast: Node = serde_json::from_str(&format!(
+            r#"{{"id": "0x0", "kind": "CompoundStmt", "range": {{"begin": {}, "end": {}}}, "inner": [{cond}]}}"#,
+            loc(2, 1),
+            loc(4, 1)
+        ))?;
+
+        let cond_node = find_cond_node(&func_ast, &[3, 7, 3, 23]).unwrap();
+        let mut query = SmtQuery::new();
+        assert!(query.add_cond(&cmp_node, true, "f", "data[1] > 4"));
+        assert!(query.is_complete());
+        assert!(query.add_cond(cond_node, false, "f", "data[1] > 4 && !len"));
+        // the parameter is a free constant without relation to the input
+        assert!(!query.is_complete());
+        assert_eq!(
+            query.get_unsupported(),
+            ["data[1] > 4 && !len: operand len is not bound to the input"]
+        );
+
+        // the input read is guarded by the lhs of the short circuit
+        let cond: Node = serde_json::from_str(&expr(
+            "BinaryOperator",
+            "int",
+            21,
+            r#", "opcode": "&&""#,
+            &[not, cmp],
+        ))?;
+        assert!(query.add_cond(&cond, false, "f", "!len && data[1] > 4"));
+
+        let smt2 = query.to_smt2("test");
+        assert!(smt2.contains("(set-logic QF_ABV)"));
+        assert!(smt2.contains("(declare-const |f::len@1| (_ BitVec 64))"));
+        assert_eq!(
+            smt2.matches("(assert (bvult ((_ sign_extend 32) (_ bv1 32)) |size|))")
+                .count(),
+            2
+        );
+        assert!(smt2.contains(
+            "(assert (not (and (bvsgt ((_ zero_extend 24) (select |data[]| ((_ sign_extend 32) (_ bv1 32)))) (_ bv4 32)) (not (not (= |f::len@1| (_ bv0 64)))))))"
+        ));
+        assert!(smt2.contains(
+            "(assert (not (and (not (not (= |f::len@2| (_ bv0 64)))) (and (bvult ((_ sign_extend 32) (_ bv1 32)) |size|) (bvsgt ((_ zero_extend 24) (select |data[]| ((_ sign_extend 32) (_ bv1 32)))) (_ bv4 32))))))"
+        ));
+
+        // calls are reported instead of translated
+        let call = expr(
+            "CallExpr",
+            "int",
+            7,
+            "",
+            &[decl_ref("check", "int (*)(void)", 7)],
+        );
+        let call: Node = serde_json::from_str(&call)?;
+        assert!(!query.add_cond(&call, true, "f", "check()"));
+        assert_eq!(query.get_unsupported().len(), 3);
+        Ok(())
+    }
+}
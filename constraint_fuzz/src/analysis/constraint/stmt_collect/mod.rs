@@ -1,9 +1,15 @@
+use std::{collections::HashSet, fmt};
+
 use crate::{
     analysis::constraint::{
-        inter::exec_tree::{action::ExecAction, thread_tree::SharedFuncNodePtr, ExecForest},
+        inter::exec_tree::{
+            action::ExecAction,
+            thread_tree::{ExecFuncNode, SharedFuncNodePtr},
+            ExecForest,
+        },
         intra::func_src_tree::{
             builder::FuncSrcForest,
-            nodes::{FuncSrcTree, StmtNodeVariants},
+            nodes::{cf_mod::CFStruct, FuncSrcTree, Selected, SrcExpr, StmtNodeVariants},
         },
     },
     feedback::branches::constraints::UBConstraint,
@@ -14,10 +20,42 @@ use eyre::bail;
 
 pub type StmtStr = String;
 
+/// default budget of interprocedural collection
+const MAX_INLINE_DEPTH: usize = 16;
+const MAX_STMT_NUM: usize = 20000;
+
+/// A collected statement with the function it belongs to and its nesting depth
+#[derive(Clone)]
+pub struct TraceStmt {
+    pub depth: usize,
+    pub func_name: String,
+    pub stmt: StmtStr,
+}
+
+impl fmt::Display for TraceStmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", "  ".repeat(self.depth), self.stmt)
+    }
+}
+
+/// mutable state shared across the recursive collection
+#[derive(Default)]
+struct CollectState {
+    stmts: Vec<TraceStmt>,
+    /// constraint branch is reached, the collection should stop
+    reached: bool,
+    /// size budget is exhausted
+    truncated: bool,
+}
+
 pub struct StmtCollector<'a> {
     exec_forest: &'a ExecForest,
     func_src_forest: &'a FuncSrcForest,
     ub_cons: &'a UBConstraint,
+    /// function of the constraint, statements of other functions never reach it
+    cons_func_name: Option<String>,
+    max_depth: usize,
+    max_stmts: usize,
 }
 
 impl<'a> StmtCollector<'a> {
@@ -30,21 +68,157 @@ impl<'a> StmtCollector<'a> {
             exec_forest,
             func_src_forest,
             ub_cons,
+            cons_func_name: ub_cons.get_func_name().ok(),
+            max_depth: MAX_INLINE_DEPTH,
+            max_stmts: MAX_STMT_NUM,
+        }
+    }
+
+    /// set the maximum inlining depth and the maximum number of collected statements
+    pub fn with_budget(mut self, max_depth: usize, max_stmts: usize) -> Self {
+        self.max_depth = max_depth;
+        self.max_stmts = max_stmts;
+        self
+    }
+
+    /// expressions evaluated by the control flow structure itself for the selection.
+    /// `iterated` tells if the loop body already ran in this instance of the loop.
+    fn get_cf_exprs<'b>(
+        cf_struct: &'b CFStruct,
+        selected: &Selected,
+        iterated: bool,
+    ) -> Vec<&'b SrcExpr> {
+        let mut exprs = vec![];
+        match cf_struct {
+            CFStruct::If(if_node) => exprs.push(&if_node.cond_expr),
+            CFStruct::Switch(switch_node) => exprs.push(&switch_node.expr_loc),
+            CFStruct::While(while_node) => {
+                if *selected != Selected::Skip {
+                    exprs.push(&while_node.cond_expr)
+                }
+            }
+            CFStruct::For(for_node) => match selected {
+                Selected::LoopIter(_) | Selected::LoopOut if !iterated => {
+                    exprs.extend(for_node.init.iter().chain(&for_node.cond))
+                }
+                Selected::LoopIter(_) | Selected::LoopOut => {
+                    exprs.extend(for_node.update.iter().chain(&for_node.cond))
+                }
+                _ => {}
+            },
         }
+        exprs
+    }
+
+    /// judge if the constraint lies in the condition of the structure rather than its body
+    fn is_cons_struct(&self, cf_struct: &CFStruct) -> bool {
+        let cond_op = match cf_struct {
+            CFStruct::If(if_node) => Some(&if_node.cond_expr),
+            CFStruct::Switch(switch_node) => Some(&switch_node.expr_loc),
+            CFStruct::While(while_node) => Some(&while_node.cond_expr),
+            CFStruct::For(for_node) => for_node.cond.as_ref(),
+        };
+        cond_op.is_some_and(|cond| {
+            cond.get_loc()
+                .covers_range(&self.ub_cons.fpath, &self.ub_cons.range)
+        })
+    }
+
+    /// Find the call action matching the invocation of `callee` in `expr`.
+    /// Only the next call action is considered, since calls are recorded in execution order.
+    fn match_call_act(
+        exec_func: &ExecFuncNode,
+        exec_idx: &mut usize,
+        expr: &SrcExpr,
+        callee: &str,
+    ) -> Option<SharedFuncNodePtr> {
+        for (off, act) in exec_func.iter_acts_at(*exec_idx).enumerate() {
+            let func_act = match act.get_func_call_act() {
+                Some(func_act) => func_act,
+                None => continue,
+            };
+            let loc_matched = match func_act.get_invoc_loc() {
+                Some(invoc_loc) if invoc_loc.is_valid() => expr.get_loc().contains_loc(invoc_loc),
+                _ => true,
+            };
+            if func_act.get_name() != callee || !loc_matched {
+                // callee without instrumentation, e.g. library functions
+                return None;
+            }
+            *exec_idx += off + 1;
+            return func_act.get_child_ptr();
+        }
+        None
+    }
+
+    /// collect an evaluated expression, inlining the callees invoked by it
+    fn collect_expr(
+        &self,
+        expr: &SrcExpr,
+        exec_func: &ExecFuncNode,
+        exec_idx: &mut usize,
+        depth: usize,
+        state: &mut CollectState,
+    ) -> Result<()> {
+        for invoc in expr.get_func_invocs() {
+            if state.reached || state.truncated {
+                return Ok(());
+            }
+            let child_ptr = match Self::match_call_act(exec_func, exec_idx, expr, &invoc.func_name)
+            {
+                Some(ptr) => ptr,
+                None => continue,
+            };
+            if child_ptr.borrow().is_pruned() {
+                // callee not leading to the constraint function
+                continue;
+            }
+            if depth + 1 > self.max_depth {
+                log::debug!("Inline depth exceeded at call of {}", invoc.func_name);
+                continue;
+            }
+            self.collect_func(child_ptr, depth + 1, state)?;
+        }
+        if state.reached || state.truncated {
+            return Ok(());
+        }
+
+        if state.stmts.len() >= self.max_stmts {
+            log::warn!(
+                "Statement budget {} exhausted when collecting for {}",
+                self.max_stmts,
+                self.ub_cons
+            );
+            state.truncated = true;
+            return Ok(());
+        }
+        state.stmts.push(TraceStmt {
+            depth,
+            func_name: exec_func.get_func_name_or_init().to_owned(),
+            stmt: expr.get_expr_str()?,
+        });
+        Ok(())
     }
 
     fn collect_intra(
         &self,
         src_tree: &FuncSrcTree,
         exec_node_ptr: SharedFuncNodePtr,
-    ) -> Result<Vec<StmtStr>> {
+        in_cons_func: bool,
+        depth: usize,
+        state: &mut CollectState,
+    ) -> Result<()> {
         let exec_func = exec_node_ptr.borrow();
         let mut exec_idx: usize = 0;
-        let mut stmts = Vec::new();
+        // loops whose body ran since they were entered
+        let mut iterated_loops = HashSet::new();
 
         let mut iter = src_tree.iter();
         let mut stmt_ptr_op;
         loop {
+            if state.reached || state.truncated {
+                break;
+            }
             // iteration logic
             stmt_ptr_op = iter.next();
             let stmt_ptr = match stmt_ptr_op {
@@ -56,20 +230,73 @@ impl<'a> StmtCollector<'a> {
             match &stmt_node.variants {
                 StmtNodeVariants::Block(_) => continue,
                 StmtNodeVariants::CFStruct(cf_struct) => {
-                    // TODO: core logic: need to invoke `iter.select()` here
+                    // the constraint branch is reached, statements after it are irrelevant.
+                    // Structures enclosing it are entered, since their bodies define operands.
+                    if in_cons_func && self.is_cons_struct(cf_struct) {
+                        state.reached = true;
+                        break;
+                    }
+                    // calls in the header are recorded before the guard actions consumed by the
+                    // selection, so they are matched from the index before it
+                    let mut select_idx = exec_idx;
+                    let selected = iter.select(cf_struct, &exec_func, &mut select_idx)?;
+                    let cf_loc = cf_struct.get_loc();
+                    let iterated = match selected {
+                        Selected::LoopIter(cnt) => {
+                            !iterated_loops.insert(cf_loc.clone()) && cnt > 1
+                        }
+                        Selected::LoopOut => iterated_loops.remove(cf_loc),
+                        _ => false,
+                    };
+                    for expr in Self::get_cf_exprs(cf_struct, &selected, iterated) {
+                        self.collect_expr(expr, &exec_func, &mut exec_idx, depth, state)?;
+                    }
+                    exec_idx = exec_idx.max(select_idx);
                 }
                 StmtNodeVariants::Plain(plain_stmt) => {
+                    if in_cons_func
+                        && plain_stmt
+                            .get_loc()
+                            .passes_range(&self.ub_cons.fpath, &self.ub_cons.range)
+                    {
+                        state.reached = true;
+                        break;
+                    }
                     // handle function imigrate and plain string collection.
-                    let stmt_str = plain_stmt.get_expr_str()?;
-                    stmts.push(stmt_str);
+                    self.collect_expr(plain_stmt, &exec_func, &mut exec_idx, depth, state)?;
                 }
             }
         }
 
-        Ok(stmts)
+        Ok(())
     }
 
-    fn collect_recur(&self, func_node_ptr: SharedFuncNodePtr) -> Result<Vec<StmtStr>> {
+    fn collect_func(
+        &self,
+        func_node_ptr: SharedFuncNodePtr,
+        depth: usize,
+        state: &mut CollectState,
+    ) -> Result<()> {
+        let func_name = func_node_ptr
+            .borrow()
+            .get_func_name()
+            .ok_or_else(|| eyre::eyre!("Function node should have a function name, but got None"))?
+            .to_owned();
+
+        match self.func_src_forest.get_value(&func_name) {
+            Some(src_tree) => {
+                let in_cons_func = self.cons_func_name.as_deref() == Some(func_name.as_str());
+                self.collect_intra(src_tree, func_node_ptr, in_cons_func, depth, state)
+            }
+            None => {
+                // functions outside of the project are not analyzed
+                log::debug!("Function source tree not found for callee: {}", func_name);
+                Ok(())
+            }
+        }
+    }
+
+    fn collect_recur(&self, func_node_ptr: SharedFuncNodePtr) -> Result<Vec<TraceStmt>> {
         let func_node = func_node_ptr.borrow();
         if func_node.is_init() {
             assert!(
@@ -90,23 +317,320 @@ impl<'a> StmtCollector<'a> {
             }
         }
 
-        let func_name = func_node.get_func_name().ok_or_else(|| {
-            eyre::eyre!("Function node should have a function name, but got None")
-        })?;
+        let func_name = func_node
+            .get_func_name()
+            .ok_or_else(|| eyre::eyre!("Function node should have a function name, but got None"))?
+            .to_owned();
+        drop(func_node);
 
-        let src_tree = self.func_src_forest.get_value(func_name).ok_or_else(|| {
-            eyre::eyre!(
+        if self.func_src_forest.get_value(&func_name).is_none() {
+            bail!(
                 "Function source tree not found for function: {}. Available functions: {:?}",
                 func_name,
                 self.func_src_forest.get_all_func_names()
-            )
-        })?;
+            );
+        }
 
-        self.collect_intra(src_tree, func_node_ptr)
+        let mut state = CollectState::default();
+        self.collect_func(func_node_ptr, 0, &mut state)?;
+        if !state.reached {
+            log::debug!("Constraint not reached during collection: {}", self.ub_cons);
+        }
+        Ok(state.stmts)
     }
 
-    pub fn collect(&self) -> Result<Vec<StmtStr>> {
+    pub fn collect(&self) -> Result<Vec<TraceStmt>> {
         let root_ptr = self.exec_forest.get_main_root_ptr();
         self.collect_recur(root_ptr)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::Path};
+
+    use super::*;
+    use crate::{
+        analysis::constraint::intra::func_src_tree::{
+            builder::SrcForestBuilder,
+            code_query::{
+                block_query::BlockMap, for_query::ForSet, func_invoc_query::FuncInvoc,
+                if_query::IfSet, while_query::WhileSet,
+            },
+            nodes::FuncSrcTree,
+            stmts::{
+                BlockStmt, BlockType, ChildEntry, ForStmt, IfStmt, IfType, QLLoc, StmtType,
+                WhileStmt, WhileType,
+            },
+        },
+        test_utils::{build_guard_forest, ub_cons},
+    };
+
+    const SRC: &str = "int main() {
+  if (check(1))
+    check(2);
+  return 0;
+}
+int check(int v) {
+  return v;
+}
+";
+
+    fn entry(fpath: &Path, range: [usize; 4], stmt_type: StmtType) -> ChildEntry {
+        ChildEntry {
+            loc: QLLoc::new(fpath.to_path_buf(), range),
+            stmt_type,
+        }
+    }
+
+    fn build_src_forest(fpath: &Path) -> Result<FuncSrcForest> {
+        let loc = |range| QLLoc::new(fpath.to_path_buf(), range);
+        let invocs = vec![
+            FuncInvoc {
+                loc: loc([2, 7, 2, 15]),
+                func_name: "check".to_owned(),
+            },
+            FuncInvoc {
+                loc: loc([3, 5, 3, 13]),
+                func_name: "check".to_owned(),
+            },
+        ];
+        let invoc_map = HashMap::from([(fpath.to_path_buf(), invocs)]);
+
+        let mut block_map = BlockMap::new();
+        let block = |range, block_type| BlockStmt {
+            loc: loc(range),
+            block_type,
+        };
+        let main_blk = [1, 12, 5, 2];
+        let if_entry = entry(fpath, [2, 3, 3, 14], StmtType::If);
+        let ret_entry = entry(fpath, [4, 3, 4, 12], StmtType::Return);
+        block_map.insert(block(main_blk, BlockType::Function), if_entry);
+        block_map.insert(block(main_blk, BlockType::Function), ret_entry);
+        let then_blk = block([3, 5, 3, 14], BlockType::If);
+        let check_blk = block([6, 18, 8, 2], BlockType::Function);
+        block_map.insert(then_blk, entry(fpath, [3, 5, 3, 14], StmtType::Expr));
+        block_map.insert(check_blk, entry(fpath, [7, 3, 7, 12], StmtType::Return));
+        let if_set = IfSet::from([IfStmt {
+            loc: loc([2, 3, 3, 14]),
+            if_type: IfType::If,
+            cond_loc: loc([2, 7, 2, 15]),
+            then_entry: entry(fpath, [3, 5, 3, 14], StmtType::Block),
+            else_entry: None,
+        }]);
+
+        let mut src_forest = FuncSrcForest::new();
+        for (func_name, root_range) in [("main", [1, 12, 5, 2]), ("check", [6, 18, 8, 2])] {
+            let root_ptr = SrcForestBuilder::create_node_recur(
+                &entry(fpath, root_range, StmtType::Block),
+                &block_map,
+                Some(&if_set),
+                None,
+                None,
+                None,
+                &invoc_map,
+            )?;
+            src_forest.insert(func_name, FuncSrcTree::new(root_ptr));
+        }
+        Ok(src_forest)
+    }
+
+    #[test]
+    fn test_collect_header_call() -> Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let fpath = work_dir.path().join("a.c");
+        std::fs::write(&fpath, SRC)?;
+        let src_forest = build_src_forest(&fpath)?;
+
+        let f = fpath.display();
+        let lines = [
+            "enter main".to_owned(),
+            format!("Function Invocation: {}:2:7 enter check(int)", f),
+            "return from check".to_owned(),
+            format!("Br Guard: {f}:2:7 {f}:2:3 1 {f}:3:5"),
+            format!("Function Invocation: {}:3:5 enter check(int)", f),
+            "return from check".to_owned(),
+            "return from main".to_owned(),
+        ];
+        let exec_forest = build_guard_forest(&work_dir.path().join("guard"), &lines)?;
+
+        // the constraint lies before the body of check in the same file
+        let cons = ub_cons("0", true, &fpath, [4, 10, 4, 11], "int main()")?;
+        let stmts = StmtCollector::new(&exec_forest, &src_forest, &cons).collect()?;
+        let stmts: Vec<(usize, &str, &str)> = stmts
+            .iter()
+            .map(|stmt| (stmt.depth, stmt.func_name.as_str(), stmt.stmt.as_str()))
+            .collect();
+        assert_eq!(
+            stmts,
+            [
+                (1, "check", "return v;"),
+                (0, "main", "check(1)"),
+                (1, "check", "return v;"),
+                (0, "main", "check(2);"),
+                (0, "main", "return 0;"),
+            ]
+        );
+        Ok(())
+    }
+
+    const LOOP_SRC: &str = "int main(int n) {
+  int len = 0;
+  while (n--) {
+    len = n * 2;
+    if (len > 4)
+      return 1;
+  }
+  return 0;
+}
+";
+
+    #[test]
+    fn test_collect_in_loop_body() -> Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let fpath = work_dir.path().join("a.c");
+        std::fs::write(&fpath, LOOP_SRC)?;
+        let loc = |range| QLLoc::new(fpath.clone(), range);
+
+        let mut block_map = BlockMap::new();
+        let block = |range, block_type| BlockStmt {
+            loc: loc(range),
+            block_type,
+        };
+        let main_blk = [1, 17, 9, 2];
+        let body_blk = [3, 15, 7, 4];
+        let then_blk = [6, 7, 6, 16];
+        block_map.insert(
+            block(main_blk, BlockType::Function),
+            entry(&fpath, [2, 3, 2, 15], StmtType::Decl),
+        );
+        block_map.insert(
+            block(main_blk, BlockType::Function),
+            entry(&fpath, [3, 3, 7, 4], StmtType::While),
+        );
+        block_map.insert(
+            block(main_blk, BlockType::Function),
+            entry(&fpath, [8, 3, 8, 12], StmtType::Return),
+        );
+        block_map.insert(
+            block(body_blk, BlockType::While),
+            entry(&fpath, [4, 5, 4, 17], StmtType::Expr),
+        );
+        block_map.insert(
+            block(body_blk, BlockType::While),
+            entry(&fpath, [5, 5, 6, 16], StmtType::If),
+        );
+        block_map.insert(
+            block(then_blk, BlockType::If),
+            entry(&fpath, then_blk, StmtType::Return),
+        );
+        let if_set = IfSet::from([IfStmt {
+            loc: loc([5, 5, 6, 16]),
+            if_type: IfType::If,
+            cond_loc: loc([5, 9, 5, 16]),
+            then_entry: entry(&fpath, then_blk, StmtType::Block),
+            else_entry: None,
+        }]);
+        let while_set = WhileSet::from([WhileStmt {
+            loc: loc([3, 3, 7, 4]),
+            while_type: WhileType::While,
+            cond_loc: loc([3, 10, 3, 13]),
+            body_entry: entry(&fpath, body_blk, StmtType::Block),
+        }]);
+        let root_ptr = SrcForestBuilder::create_node_recur(
+            &entry(&fpath, main_blk, StmtType::Block),
+            &block_map,
+            Some(&if_set),
+            None,
+            Some(&while_set),
+            None,
+            &HashMap::new(),
+        )?;
+        let mut src_forest = FuncSrcForest::new();
+        src_forest.insert("main", FuncSrcTree::new(root_ptr));
+
+        let f = fpath.display();
+        let lines = [
+            "enter main".to_owned(),
+            format!("Loop Hit: {f}:3:3 at count 1"),
+            format!("Br Guard: {f}:5:9 {f}:5:5 0 {f}:3:3"),
+            format!("Loop Hit: {f}:3:3 at count 2"),
+            format!("Br Guard: {f}:5:9 {f}:5:5 1 {f}:6:7"),
+            "return from main".to_owned(),
+        ];
+        let exec_forest = build_guard_forest(&work_dir.path().join("guard"), &lines)?;
+
+        // the constraint is nested in the loop, whose body defines its operand
+        let cons = ub_cons("len > 4", true, &fpath, [5, 9, 5, 16], "int main(int n)")?;
+        let stmts = StmtCollector::new(&exec_forest, &src_forest, &cons).collect()?;
+        let stmts: Vec<&str> = stmts.iter().map(|stmt| stmt.stmt.as_str()).collect();
+        assert_eq!(stmts, ["int len = 0;", "n--", "len = n * 2;"]);
+        Ok(())
+    }
+
+    const FOR_SRC: &str = "int main(int n) {
+  int sum = 0;
+  for (int i = 0; i < n; i++)
+    sum += i;
+  if (sum > 4)
+    return 1;
+  return 0;
+}
+";
+
+    #[test]
+    fn test_collect_for_without_iteration() -> Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let fpath = work_dir.path().join("a.c");
+        std::fs::write(&fpath, FOR_SRC)?;
+        let loc = |range| QLLoc::new(fpath.clone(), range);
+
+        let mut block_map = BlockMap::new();
+        let main_blk = || BlockStmt {
+            loc: loc([1, 17, 8, 2]),
+            block_type: BlockType::Function,
+        };
+        for (range, stmt_type) in [
+            ([2, 3, 2, 15], StmtType::Decl),
+            ([3, 3, 4, 14], StmtType::For),
+            ([7, 3, 7, 12], StmtType::Return),
+        ] {
+            block_map.insert(main_blk(), entry(&fpath, range, stmt_type));
+        }
+        let for_set = ForSet::from([ForStmt {
+            loc: loc([3, 3, 4, 14]),
+            init_loc: Some(loc([3, 8, 3, 17])),
+            cond_loc: Some(loc([3, 19, 3, 24])),
+            update_loc: Some(loc([3, 26, 3, 29])),
+            body_entry: entry(&fpath, [4, 5, 4, 14], StmtType::Expr),
+        }]);
+        let root_ptr = SrcForestBuilder::create_node_recur(
+            &entry(&fpath, [1, 17, 8, 2], StmtType::Block),
+            &block_map,
+            None,
+            None,
+            None,
+            Some(&for_set),
+            &HashMap::new(),
+        )?;
+        let mut src_forest = FuncSrcForest::new();
+        src_forest.insert("main", FuncSrcTree::new(root_ptr));
+
+        let f = fpath.display();
+        let lines = [
+            "enter main".to_owned(),
+            format!("Loop Hit: {f}:3:3 at count 1"),
+            format!("Out of Loop: {f}:3:3 {f}:5:3 at count 1"),
+            format!("Br Guard: {f}:5:7 {f}:5:3 1 {f}:6:5"),
+            "return from main".to_owned(),
+        ];
+        let exec_forest = build_guard_forest(&work_dir.path().join("guard"), &lines)?;
+
+        // the loop is left at once, so the update never runs
+        let cons = ub_cons("sum > 4", true, &fpath, [5, 7, 5, 14], "int main(int n)")?;
+        let stmts = StmtCollector::new(&exec_forest, &src_forest, &cons).collect()?;
+        let stmts: Vec<&str> = stmts.iter().map(|stmt| stmt.stmt.as_str()).collect();
+        assert_eq!(stmts, ["int sum = 0;", "int i = 0", "i < n"]);
+        Ok(())
+    }
+}
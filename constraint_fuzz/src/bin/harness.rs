@@ -9,9 +9,19 @@ use std::sync::OnceLock;
 use chrono::prelude::*;
 use clap::{Parser, Subcommand, ValueEnum};
 use constraint_fuzz::analysis::adg::ADGBuilder;
+use constraint_fuzz::analysis::callgraph::{get_lib_call_graph, EdgeSource};
 use constraint_fuzz::analysis::cfg::CFGBuilder;
+use constraint_fuzz::analysis::constraint::byte_attr::ByteAttributor;
+use constraint_fuzz::analysis::constraint::ctx_cov::CtxBrCov;
+use constraint_fuzz::analysis::constraint::dyn_call::DynCallGraph;
+use constraint_fuzz::analysis::constraint::exec_rec::exec_trie::ExecTrie;
+use constraint_fuzz::analysis::constraint::exec_rec::ExecRec;
+use constraint_fuzz::analysis::constraint::RevAnalyzer;
 use constraint_fuzz::deopt::{self, Deopt};
 use constraint_fuzz::execution::{logger::ProgramError, Executor};
+use constraint_fuzz::feedback::branches::constraints::cons_db::{ConsDb, ConsStatus, DbLock};
+use constraint_fuzz::feedback::branches::constraints::cons_diff::ConsDiff;
+use constraint_fuzz::feedback::branches::constraints::UBConstraint;
 use constraint_fuzz::feedback::observer::Observer;
 use constraint_fuzz::minimize::minimize;
 use constraint_fuzz::program::infer::infer_constraints;
@@ -102,6 +112,78 @@ enum Commands {
         /// The path of target programs to build the ADG.
         target: Option<PathBuf>,
     },
+    /// Analyze the constraints of an experiment, results are written into the work dir.
+    Analyze {
+        /// work dir of the experiment, which contains `constraints.json`
+        work_dir: PathBuf,
+        /// also generate HTML reports of the constraints
+        #[arg(short, long, default_value = "false")]
+        report: bool,
+        /// also emit SMT-LIB2 queries of the path conditions
+        #[arg(long, default_value = "false")]
+        smt: bool,
+    },
+    /// Show the burndown of the constraint database, or mark the status of constraints in it.
+    ConsDb {
+        /// location of the constraints to mark, in the form of `path:line:col`
+        #[arg(long, requires = "status")]
+        mark: Option<String>,
+        /// status to mark the constraints with
+        #[arg(long)]
+        status: Option<ConsStatus>,
+    },
+    /// Diff the constraints of two experiments.
+    Diff {
+        /// work dir of the base experiment
+        base: PathBuf,
+        /// work dir of the target experiment
+        target: PathBuf,
+        /// also write the diff as json
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Cross-check the execution traces against the coverage of each case in an experiment.
+    Validate {
+        /// work dir of the experiment, which contains `exec_recs`
+        work_dir: PathBuf,
+    },
+    /// Attribute the input bytes of a case to the branches and constraints of an experiment.
+    Attribute {
+        /// work dir of the experiment, which contains `constraints.json` and `cov_fuzzer`
+        work_dir: PathBuf,
+        /// the case to attribute
+        case: PathBuf,
+        /// limit of mutated executions of the case
+        #[arg(long, default_value = "256")]
+        max_execs: usize,
+    },
+    /// Query the inputs reaching a function through the call-context trie of an experiment.
+    Trie {
+        /// work dir of the experiment, which contains `exec_recs`
+        work_dir: PathBuf,
+        /// function to query
+        func: String,
+        /// only count the calls under this function
+        #[arg(long)]
+        under: Option<String>,
+        /// rebuild the trie even if it is saved
+        #[arg(long, default_value = "false")]
+        rebuild: bool,
+    },
+    /// Report the branch directions covered under some calling contexts but not others.
+    CtxCov {
+        /// work dir of the experiment, which contains `exec_recs`
+        work_dir: PathBuf,
+        /// number of callers kept in a context
+        #[arg(long, default_value = "2")]
+        depth: usize,
+    },
+    /// Merge the calls observed in the traces into the library call graph and report the
+    /// indirect call sites.
+    DynCall {
+        /// work dir of the experiment, which contains `exec_recs`
+        work_dir: PathBuf,
+    },
 }
 
 #[derive(Debug, ValueEnum, Clone, Copy, Display, PartialEq, PartialOrd)]
@@ -354,6 +436,216 @@ fn run_expe(project: &'static str, program: &Path) -> Result<()> {
     Ok(())
 }
 
+fn analyze(project: &'static str, work_dir: &Path, report: bool, smt: bool) -> Result<()> {
+    let deopt = Deopt::new(project)?;
+    let analyzer = RevAnalyzer::from_expe_dir(work_dir)?;
+    let out_path = deopt.get_constraints_analysis_path(work_dir);
+    let res_list = analyzer.write_analysis(&out_path)?;
+    let failed = res_list.iter().filter(|res| res.is_failed()).count();
+    log::info!(
+        "{} of {} constraints analyzed successfully",
+        res_list.len() - failed,
+        res_list.len()
+    );
+    let path_conds = analyzer.write_path_conds(deopt.get_path_conds_path(work_dir))?;
+    let failed = path_conds.iter().filter(|cond| cond.is_failed()).count();
+    log::info!(
+        "{} of {} path conditions extracted",
+        path_conds.len() - failed,
+        path_conds.len()
+    );
+    let loop_cons_list = analyzer.write_loop_cons(deopt.get_loop_cons_path(work_dir))?;
+    log::info!("{} loop-bound constraints derived", loop_cons_list.len());
+    if smt {
+        let smt_dir = deopt.get_expe_smt_dir(work_dir)?;
+        analyzer.write_smt_queries(&deopt, &path_conds, &smt_dir)?;
+    }
+    if report {
+        let report_dir = deopt.get_expe_report_dir(work_dir)?;
+        analyzer.write_html_report(&report_dir)?;
+    }
+    Ok(())
+}
+
+/// parse a location in the form of `path:line:col`
+fn parse_src_loc(loc: &str) -> Result<(PathBuf, [usize; 2])> {
+    let mut parts = loc.rsplitn(3, ':');
+    let (col, line, fpath) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(col), Some(line), Some(fpath)) => (col, line, fpath),
+        _ => eyre::bail!("Invalid location {}, expected path:line:col", loc),
+    };
+    Ok((fpath.into(), [line.parse()?, col.parse()?]))
+}
+
+fn cons_db(project: &'static str, mark: &Option<String>, status: Option<ConsStatus>) -> Result<()> {
+    let deopt = Deopt::new(project)?;
+    let db_path = deopt.get_cons_db_path()?;
+    let _lock = DbLock::acquire(&db_path)?;
+    let mut db = ConsDb::load(&db_path)?;
+    if let (Some(loc), Some(status)) = (mark, status) {
+        let (fpath, loc) = parse_src_loc(loc)?;
+        let count = db.set_status(&fpath, loc, status);
+        if count == 0 {
+            eyre::bail!(
+                "No constraint found at {}:{}:{}",
+                fpath.display(),
+                loc[0],
+                loc[1]
+            );
+        }
+        db.save(&db_path)?;
+        log::info!("{} constraints marked as {}", count, status);
+    }
+
+    println!("time\trun\tseen\tnew\tsolved\treopened\topen");
+    for summary in db.get_runs() {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            summary.time,
+            summary.run,
+            summary.seen,
+            summary.new,
+            summary.solved,
+            summary.reopened,
+            summary.open
+        );
+    }
+    for status in [
+        ConsStatus::Open,
+        ConsStatus::Solved,
+        ConsStatus::Infeasible,
+        ConsStatus::Ignored,
+    ] {
+        println!("{}: {}", status, db.count_status(status));
+    }
+    Ok(())
+}
+
+fn diff_expe(
+    project: &'static str,
+    base: &Path,
+    target: &Path,
+    out: &Option<PathBuf>,
+) -> Result<()> {
+    let deopt = Deopt::new(project)?;
+    let executor = Executor::new(&deopt)?;
+    let diff = ConsDiff::from_expe_dirs(&executor, base, target)?;
+    print!("{}", diff);
+    if let Some(out) = out {
+        let json_str = serde_json::to_string(&diff)?;
+        deopt::utils::write_bytes_to_file(out, json_str.as_bytes())?;
+        log::info!("Constraint diff written to {:?}", out);
+    }
+    Ok(())
+}
+
+fn validate_traces(project: &'static str, work_dir: &Path) -> Result<()> {
+    let deopt = Deopt::new(project)?;
+    let mut check_list = vec![];
+    for exec in ExecRec::get_exec_list_from_expe_dir(work_dir)? {
+        let check = exec.check_trace_cov()?;
+        print!("{}", check);
+        check_list.push(check);
+    }
+    let inconsistent = check_list
+        .iter()
+        .filter(|check| !check.is_consistent())
+        .count();
+    log::info!(
+        "{} of {} executions have mismatches between trace and coverage",
+        inconsistent,
+        check_list.len()
+    );
+    let out_path = deopt.get_trace_check_path(work_dir);
+    let json_str = serde_json::to_string(&check_list)?;
+    deopt::utils::write_bytes_to_file(&out_path, json_str.as_bytes())?;
+    log::info!("Trace check written to {:?}", out_path);
+    Ok(())
+}
+
+fn attribute_bytes(
+    project: &'static str,
+    work_dir: &Path,
+    case: &Path,
+    max_execs: usize,
+) -> Result<()> {
+    let deopt = Deopt::new(project)?;
+    let executor = Executor::new(&deopt)?;
+    let buf = deopt::utils::buffer_read_to_bytes(deopt.get_constraints_path(work_dir))?;
+    let cons_list: Vec<UBConstraint> = serde_json::from_slice(&buf)?;
+    let fuzzer_binary = deopt.get_expe_cov_fuzzer_path(work_dir)?;
+    let attr = ByteAttributor::new(&executor, &fuzzer_binary)
+        .with_max_execs(max_execs)
+        .attribute(case)?;
+    let cons_bytes = attr.attach_to_cons(&cons_list);
+    log::info!(
+        "{} of {} constraints attributed to input bytes",
+        cons_bytes.len(),
+        cons_list.len()
+    );
+    let out_path = deopt.get_byte_attr_path(work_dir);
+    let json_str = serde_json::to_string(&serde_json::json!({
+        "attribution": attr,
+        "constraints": cons_bytes,
+    }))?;
+    deopt::utils::write_bytes_to_file(&out_path, json_str.as_bytes())?;
+    log::info!("Byte attribution written to {:?}", out_path);
+    Ok(())
+}
+
+fn query_trie(
+    project: &'static str,
+    work_dir: &Path,
+    func: &str,
+    under: Option<&str>,
+    rebuild: bool,
+) -> Result<()> {
+    let deopt = Deopt::new(project)?;
+    let trie_path = deopt.get_exec_trie_path(work_dir);
+    let trie = if trie_path.is_file() && !rebuild {
+        ExecTrie::load(&trie_path)?
+    } else {
+        let trie = ExecTrie::from_expe_dir(work_dir)?;
+        trie.save(&trie_path)?;
+        log::info!("Execution trie written to {:?}", trie_path);
+        trie
+    };
+    for id in trie.find_contexts(func, under) {
+        let hits = trie.get_node(id).map_or(0, |node| node.get_hits());
+        println!("{}: {} hits", trie.get_context(id).join(" -> "), hits);
+    }
+    let inputs = trie.find_inputs(func, under);
+    println!("{} inputs:", inputs.len());
+    for input in inputs {
+        println!("  {}", input);
+    }
+    Ok(())
+}
+
+fn ctx_cov(project: &'static str, work_dir: &Path, depth: usize) -> Result<()> {
+    let deopt = Deopt::new(project)?;
+    let ctx_cov = CtxBrCov::from_expe_dir(work_dir, depth)?;
+    let gaps = ctx_cov.write_ctx_gaps(deopt.get_ctx_cons_path(work_dir))?;
+    for gap in gaps.iter() {
+        println!("{}", gap);
+    }
+    Ok(())
+}
+
+fn dyn_call(project: &'static str, work_dir: &Path) -> Result<()> {
+    let deopt = Deopt::new(project)?;
+    let dyn_graph = DynCallGraph::from_expe_dir(work_dir)?;
+    let (_, report) = dyn_graph.merge_with(get_lib_call_graph());
+    for source in [EdgeSource::Static, EdgeSource::Dynamic, EdgeSource::Both] {
+        println!("{:?} edges: {}", source, report.get_edge_count(source));
+    }
+    for (loc, site) in report.get_indirect_sites() {
+        let targets: Vec<&str> = site.get_targets().keys().map(|s| s.as_str()).collect();
+        println!("{} in {}: {}", loc, site.get_caller(), targets.join(", "));
+    }
+    report.save(deopt.get_dyn_call_path(work_dir))
+}
+
 fn get_harn_config() -> &'static Config {
     static CONFIG: OnceLock<Config> = OnceLock::new();
     CONFIG.get_or_init(Config::parse)
@@ -435,6 +727,75 @@ fn main() -> Result<ExitCode> {
         Commands::Compile { kind, exploit } => {
             compile_fuzzer(project, kind.clone(), *exploit).unwrap()
         }
+        Commands::Analyze {
+            work_dir,
+            report,
+            smt,
+        } => {
+            let res = analyze(project, work_dir, *report, *smt);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                return Ok(ExitCode::from(46));
+            }
+        }
+        Commands::ConsDb { mark, status } => {
+            let res = cons_db(project, mark, *status);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                return Ok(ExitCode::from(47));
+            }
+        }
+        Commands::Diff { base, target, out } => {
+            let res = diff_expe(project, base, target, out);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                return Ok(ExitCode::from(48));
+            }
+        }
+        Commands::Validate { work_dir } => {
+            let res = validate_traces(project, work_dir);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                return Ok(ExitCode::from(49));
+            }
+        }
+        Commands::Attribute {
+            work_dir,
+            case,
+            max_execs,
+        } => {
+            let res = attribute_bytes(project, work_dir, case, *max_execs);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                return Ok(ExitCode::from(50));
+            }
+        }
+        Commands::Trie {
+            work_dir,
+            func,
+            under,
+            rebuild,
+        } => {
+            let res = query_trie(project, work_dir, func, under.as_deref(), *rebuild);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                return Ok(ExitCode::from(51));
+            }
+        }
+        Commands::CtxCov { work_dir, depth } => {
+            let res = ctx_cov(project, work_dir, *depth);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                return Ok(ExitCode::from(52));
+            }
+        }
+        Commands::DynCall { work_dir } => {
+            let res = dyn_call(project, work_dir);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                return Ok(ExitCode::from(53));
+            }
+        }
     };
     Ok(ExitCode::SUCCESS)
 }
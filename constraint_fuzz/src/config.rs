@@ -213,6 +213,7 @@ pub struct Config {
     /// Execution numbers during runtime information collection
     #[arg(short, long, default_value_t = 100)]
     pub info_coll_execs: usize,
+    /// Hits of the constraint branch after which execution trees are truncated, 0 for no truncation
     #[clap(default_value_t = 1)]
     pub trunc_cnt: usize,
     /// Generative model to generate codes.
@@ -253,6 +254,12 @@ pub struct Config {
     /// Run condensed fuzzers after the fuzz loop
     #[arg(long, default_value = "false")]
     pub fuzzer_run: bool,
+    /// Let the instrumented programs record guards in the binary trace format.
+    #[arg(long, default_value = "false")]
+    pub bin_trace: bool,
+    /// The maximum number of function nodes materialized when building execution trees for a constraint.
+    #[arg(long, default_value = "200000")]
+    pub exec_node_cap: usize,
 }
 
 impl Config {
@@ -275,13 +282,16 @@ impl Config {
             exponent_branch: false,
             recheck: false,
             fuzzer_run: false,
+            bin_trace: false,
+            exec_node_cap: 200000,
             disable_power_schedule: false,
             query_budget: 5.00,
         };
         unsafe {
             CONFIG_INSTANCE = Some(config);
         }
-        crate::init_debug_logger().unwrap();
+        // the logger is started only once for all tests in the process
+        let _ = crate::init_debug_logger();
     }
 
     pub fn init_test(target_proj: &str) {
@@ -471,6 +481,14 @@ pub fn is_debug_mode() -> bool {
     get_config().debug_mode
 }
 
+pub fn is_bin_trace() -> bool {
+    get_config().bin_trace
+}
+
+pub fn get_exec_node_cap() -> usize {
+    get_config().exec_node_cap
+}
+
 pub fn get_fuzz_time_out_as_secs() -> u64 {
     get_config().fuzz_time_out * 60
 }
@@ -67,6 +67,45 @@ impl Executor {
         );
     }
 
+    /// Extract the definition of `func` from a library source file.
+    pub fn extract_src_func_ast(src: &Path, deopt: &Deopt, func: &str) -> Result<Node> {
+        let ast_filter = format!("-ast-dump-filter={func}");
+        let include_path =
+            "-I".to_owned() + deopt.get_library_build_header_path()?.to_str().unwrap();
+        let mut binding = Command::new("clang");
+        let cmd = binding
+            .arg("-fsyntax-only")
+            .arg("-Xclang")
+            .arg("-ast-dump=json")
+            .arg("-Xclang")
+            .arg(ast_filter)
+            .arg(include_path)
+            .arg(src);
+        if let Some(src_dir) = src.parent() {
+            cmd.arg(format!("-I{}", src_dir.display()));
+        }
+
+        let output = cmd.stdout(Stdio::piped()).output()?;
+        // the source may have errors unrelated to the function, e.g. missing config headers
+        let json_output = output.stdout.as_slice();
+        ast_dump_find(json_output, |node| match &node.kind {
+            Clang::FunctionDecl(fd) => {
+                fd.get_name() == func
+                    && node
+                        .inner
+                        .iter()
+                        .any(|child| matches!(child.kind, Clang::CompoundStmt(_)))
+            }
+            _ => false,
+        })?
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "fail to find definition of {func} in {src:?}\n, {}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+        })
+    }
+
     pub fn extract_program_ast(program: &Path) -> Result<Node> {
         let mut binding = Command::new("clang++");
         let output = binding
@@ -120,6 +159,18 @@ impl Executor {
 
 /// Filter the ast and only retain the node with function name as `func`.
 fn ast_dump_filter(data: &[u8], func: &str) -> Result<Node> {
+    let node = ast_dump_find(data, |ast| match &ast.kind {
+        Clang::FunctionDecl(fd) => func == fd.get_name(),
+        _ => false,
+    })?;
+    match node {
+        Some(node) => Ok(node),
+        None => unreachable!(),
+    }
+}
+
+/// Find the first top-level node satisfying `pred` in the output of `-ast-dump-filter`.
+fn ast_dump_find<F: Fn(&Node) -> bool>(data: &[u8], pred: F) -> Result<Option<Node>> {
     let it = data.iter();
     let mut json: Vec<u8> = Vec::new();
     let mut last: u8 = 0_u8;
@@ -127,17 +178,14 @@ fn ast_dump_filter(data: &[u8], func: &str) -> Result<Node> {
         json.push(*cur);
         if *cur == b'}' && last == b'\n' {
             let ast: Node = serde_json::from_slice(json.as_slice())?;
-            if let Clang::FunctionDecl(fd) = &ast.kind {
-                let name = fd.get_name();
-                if func == name {
-                    return Ok(ast);
-                }
+            if pred(&ast) {
+                return Ok(Some(ast));
             }
             json.clear();
         }
         last = *cur;
     }
-    unreachable!()
+    Ok(None)
 }
 
 /// elimitate the irrelative asts that included in this file.
@@ -1,10 +1,12 @@
+use crate::analysis::callgraph::{get_lib_call_graph, get_lib_call_graph_path};
 use crate::deopt::utils::buffer_read_to_bytes;
 use crate::deopt::utils::get_file_parent_dir;
 use crate::execution::get_file_dirname;
-use crate::feedback::branches::constraints::UBConstraint;
+use crate::feedback::branches::constraints::{cons_db::ConsDb, rank::ConsRanker, UBConstraint};
 use crate::feedback::clang_coverage::CodeCoverage;
 use color_eyre::eyre::Result;
 use std::fs;
+use std::io::BufWriter;
 use std::path::PathBuf;
 
 use std::path::Path;
@@ -24,9 +26,60 @@ impl Executor {
         let fpath = self.save_cons_list(&cons_list, work_dir)?;
         // self.show_each_cons(&cons_list, work_dir)?;
         log::info!("Constraint Extraction done. Saved to {:?}", fpath);
+        self.save_ranked_cons_list(&cov, &cons_list, work_dir)?;
+        // the database is a side product of the experiment
+        if let Err(e) = self.update_cons_db(&cov, &cons_list, work_dir) {
+            log::warn!("Failed to update the constraint database: {}", e);
+        }
         Ok(cons_list)
     }
 
+    /// Merge the constraints of the experiment into the constraint database of the library.
+    fn update_cons_db(
+        &self,
+        cov: &CodeCoverage,
+        cons_list: &[UBConstraint],
+        work_dir: &Path,
+    ) -> Result<()> {
+        let db_path = self.deopt.get_cons_db_path()?;
+        let run = work_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| work_dir.to_string_lossy().to_string());
+        let summary = ConsDb::update_file(&db_path, &run, cov, cons_list)?;
+        log::info!(
+            "Constraint database updated: {} new, {} solved, {} reopened, {} open",
+            summary.new,
+            summary.solved,
+            summary.reopened,
+            summary.open
+        );
+        Ok(())
+    }
+
+    /// Rank constraints by their reachability and value, skipped if the call graph is absent.
+    fn save_ranked_cons_list(
+        &self,
+        cov: &CodeCoverage,
+        cons_list: &[UBConstraint],
+        work_dir: &Path,
+    ) -> Result<()> {
+        let call_graph_path = get_lib_call_graph_path(&self.deopt)?;
+        if !call_graph_path.is_file() {
+            log::warn!(
+                "Call graph not found at {:?}, constraint ranking skipped",
+                call_graph_path
+            );
+            return Ok(());
+        }
+        let ranked = ConsRanker::new(cov, get_lib_call_graph()).rank(cons_list);
+        let fpath = self.deopt.get_ranked_constraints_path(work_dir);
+        let writer = BufWriter::new(fs::File::create(&fpath)?);
+        serde_json::to_writer(writer, &ranked)?;
+        log::info!("Constraint ranking done. Saved to {:?}", fpath);
+        Ok(())
+    }
+
     // wrapper for constrainst extraction
     pub fn get_cons_from_cov(
         &self,
@@ -15,6 +15,12 @@ impl Deopt {
         Ok(expe_dir)
     }
 
+    /// constraint database shared by the experiments of the library
+    pub fn get_cons_db_path(&self) -> Result<PathBuf> {
+        let expe_dir = self.get_library_expe_dir()?;
+        Ok(expe_dir.join("constraints_db.json"))
+    }
+
     fn get_harn_name(program_path: &Path) -> Result<String> {
         if let Some(basename) = program_path.file_stem() {
             let basename = basename.to_str().unwrap_or_else(|| {
@@ -60,9 +66,57 @@ impl Deopt {
         work_dir.join("constraints.json")
     }
 
+    pub fn get_ranked_constraints_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("constraints_ranked.json")
+    }
+
+    pub fn get_constraints_analysis_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("constraints_analysis.json")
+    }
+
+    pub fn get_path_conds_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("constraints_paths.json")
+    }
+
+    pub fn get_loop_cons_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("constraints_loops.json")
+    }
+
+    pub fn get_trace_check_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("trace_cov_check.json")
+    }
+
+    pub fn get_byte_attr_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("constraints_bytes.json")
+    }
+
+    pub fn get_exec_trie_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("exec_trie.json")
+    }
+
+    pub fn get_ctx_cons_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("ctx_constraints.json")
+    }
+
+    pub fn get_dyn_call_path(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("dyn_callgraph.json")
+    }
+
+    pub fn get_expe_smt_dir(&self, work_dir: &Path) -> Result<PathBuf> {
+        let smt_dir = work_dir.join("smt");
+        create_dir_if_nonexist(&smt_dir)?;
+        Ok(smt_dir)
+    }
+
     pub fn get_expe_constraints_show_dir(&self, work_dir: &Path) -> Result<PathBuf> {
         let show_dir = work_dir.join("constraints_show");
         create_dir_if_nonexist(&show_dir)?;
         Ok(show_dir)
     }
+
+    pub fn get_expe_report_dir(&self, work_dir: &Path) -> Result<PathBuf> {
+        let report_dir = work_dir.join("report");
+        create_dir_if_nonexist(&report_dir)?;
+        Ok(report_dir)
+    }
 }
@@ -6,12 +6,14 @@ pub mod pch;
 pub mod sanitize;
 
 use self::logger::ProgramError;
-use crate::analysis::constraint::exec_rec::case_map::get_exec_name_from_case_path;
+use crate::analysis::constraint::exec_rec::case_map::{
+    get_exec_name_from_case_path, CaseEntry, CaseExit, CaseManifest,
+};
 use crate::analysis::constraint::exec_rec::ExecRec;
 use crate::ast::utils::show_cmd_args;
 use crate::config::{
     get_config, get_func_pass_lib_dir, get_fuzz_time_out_as_secs, get_info_coll_execs,
-    get_minimize_compile_flag, is_debug_mode,
+    get_minimize_compile_flag, is_bin_trace, is_debug_mode,
 };
 use crate::deopt::utils::{
     create_dir_if_nonexist, get_basename_str_from_path, get_file_parent_dir,
@@ -411,6 +413,10 @@ impl Executor {
                 OsStr::new("FUNC_STACK_OUT"),
                 case_fs_dir.as_os_str().to_os_string(),
             ),
+            (
+                OsStr::new("FUNC_STACK_FORMAT"),
+                OsString::from(if is_bin_trace() { "bin" } else { "text" }),
+            ),
             (OsStr::new("LD_LIBRARY_PATH"), OsString::from(ld_lib)),
         ])
     }
@@ -541,6 +547,8 @@ impl Executor {
 
         let err_execs = Arc::new(Mutex::new(Vec::<ProgramError>::new()));
         let fin_counter = Arc::new(AtomicUsize::new(0));
+        let manifest_path = ExecRec::get_case_manifest_path(fuzzer_dir)?;
+        let manifest = Arc::new(Mutex::new(CaseManifest::load(&manifest_path)?));
 
         let exec_num = if corpus_files.len() < get_info_coll_execs() {
             corpus_files.len()
@@ -570,6 +578,7 @@ impl Executor {
 
             let err_execs_ptr = err_execs.clone();
             let counter = fin_counter.clone();
+            let manifest_ptr = manifest.clone();
 
             pool.execute(move || {
                 let err_op = executor
@@ -581,6 +590,11 @@ impl Executor {
                         &case_cov,
                     )
                     .unwrap();
+                let exit = CaseExit::from_exec_err(err_op.as_ref());
+                match CaseEntry::from_case_path(&case_file, exit) {
+                    Ok(entry) => manifest_ptr.lock().unwrap().insert(exec_name, entry),
+                    Err(e) => log::warn!("Failed to record case {:?}: {}", case_file, e),
+                }
                 if let Some(err) = err_op {
                     err_execs_ptr.lock().unwrap().push(err);
                 }
@@ -599,6 +613,7 @@ impl Executor {
         pool.join();
 
         log::info!("All cov executions done");
+        manifest.lock().unwrap().save(&manifest_path)?;
         // error execution summary
         let err_execs_data = err_execs.lock().unwrap();
         Self::cov_exec_error_summary(&err_execs_data, err_execs_data.len(), exec_num);
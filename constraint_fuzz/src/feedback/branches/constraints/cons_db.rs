@@ -0,0 +1,448 @@
+//! Per-library database of unselected branch constraints, which tracks each constraint across
+//! experiments so that the burndown of unselected branches can be followed over runs.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Result;
+use eyre::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deopt::utils::{buffer_read_to_bytes, get_formatted_time},
+    feedback::{
+        branches::constraints::{Range, UBConstraint},
+        clang_coverage::CodeCoverage,
+    },
+};
+
+/// time to wait for the lock held by another experiment
+const LOCK_TIMEOUT: Duration = Duration::from_secs(60);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Lock file of the database shared by concurrent experiments, removed when dropped.
+pub struct DbLock {
+    lock_path: PathBuf,
+}
+
+impl DbLock {
+    pub fn acquire<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let lock_path = db_path.as_ref().with_extension("lock");
+        let start = Instant::now();
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(_) => return Ok(Self { lock_path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        bail!(
+                            "Timed out waiting for {:?}, remove it if no experiment is running",
+                            lock_path
+                        );
+                    }
+                    std::thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                Err(e) => bail!("Failed to create lock file {:?}: {}", lock_path, e),
+            }
+        }
+    }
+}
+
+impl Drop for DbLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.lock_path) {
+            log::warn!("Failed to remove lock file {:?}: {}", self.lock_path, e);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsStatus {
+    /// the unselected arm is not covered yet
+    Open,
+    /// the unselected arm is covered by a later run
+    Solved,
+    /// the unselected arm can not be covered, marked manually
+    Infeasible,
+    /// excluded from the analysis, marked manually
+    Ignored,
+}
+
+impl fmt::Display for ConsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConsStatus::Open => "open",
+            ConsStatus::Solved => "solved",
+            ConsStatus::Infeasible => "infeasible",
+            ConsStatus::Ignored => "ignored",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConsKey {
+    pub fpath: PathBuf,
+    pub range: Range,
+    pub func_sig: String,
+}
+
+impl From<&UBConstraint> for ConsKey {
+    fn from(cons: &UBConstraint) -> Self {
+        Self {
+            fpath: cons.fpath.clone(),
+            range: cons.range,
+            func_sig: cons.func_sig.clone(),
+        }
+    }
+}
+
+/// a seen point of a constraint, identified by the run name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunStamp {
+    pub run: String,
+    pub time: String,
+}
+
+impl RunStamp {
+    fn new(run: &str, time: &str) -> Self {
+        Self {
+            run: run.to_owned(),
+            time: time.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsRecord {
+    /// the latest version of the constraint
+    cons: UBConstraint,
+    status: ConsStatus,
+    first_seen: RunStamp,
+    last_seen: RunStamp,
+    /// number of runs reporting the constraint
+    seen_count: usize,
+    /// the run covering the unselected arm, only for the solved status
+    solved_by: Option<RunStamp>,
+}
+
+impl ConsRecord {
+    pub fn get_cons(&self) -> &UBConstraint {
+        &self.cons
+    }
+
+    pub fn get_status(&self) -> ConsStatus {
+        self.status
+    }
+
+    pub fn get_first_seen(&self) -> &RunStamp {
+        &self.first_seen
+    }
+
+    pub fn get_last_seen(&self) -> &RunStamp {
+        &self.last_seen
+    }
+
+    pub fn get_seen_count(&self) -> usize {
+        self.seen_count
+    }
+
+    pub fn get_solved_by(&self) -> Option<&RunStamp> {
+        self.solved_by.as_ref()
+    }
+}
+
+/// counts of a run after it is merged into the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub run: String,
+    pub time: String,
+    /// constraints reported by the run
+    pub seen: usize,
+    /// constraints first seen in the run
+    pub new: usize,
+    /// constraints solved by the run
+    pub solved: usize,
+    /// solved constraints reported again by the run
+    pub reopened: usize,
+    /// open constraints in the database after the run
+    pub open: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ConsDb {
+    records: Vec<ConsRecord>,
+    runs: Vec<RunSummary>,
+    #[serde(skip)]
+    index: HashMap<ConsKey, usize>,
+}
+
+impl ConsDb {
+    /// Load the database, an empty one is returned if the file does not exist.
+    pub fn load<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let db_path = db_path.as_ref();
+        if !db_path.is_file() {
+            return Ok(Self::default());
+        }
+        let bytes = buffer_read_to_bytes(db_path)?;
+        let mut db: Self = serde_json::from_slice(&bytes)
+            .map_err(|e| eyre::eyre!("Failed to parse constraint database {:?}: {}", db_path, e))?;
+        db.build_index();
+        Ok(db)
+    }
+
+    /// Written to a temporary file renamed over the database, so that readers never see a
+    /// partial one.
+    pub fn save<P: AsRef<Path>>(&self, db_path: P) -> Result<()> {
+        let db_path = db_path.as_ref();
+        let db_dir = match db_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut tmp = tempfile::NamedTempFile::new_in(db_dir)?;
+        serde_json::to_writer(&mut tmp, self)?;
+        tmp.flush()?;
+        tmp.persist(db_path)?;
+        Ok(())
+    }
+
+    /// Load, update and save the database under its lock.
+    pub fn update_file<P: AsRef<Path>>(
+        db_path: P,
+        run: &str,
+        cov: &CodeCoverage,
+        cons_list: &[UBConstraint],
+    ) -> Result<RunSummary> {
+        let db_path = db_path.as_ref();
+        let _lock = DbLock::acquire(db_path)?;
+        let mut db = Self::load(db_path)?;
+        let summary = db.update(run, cov, cons_list).clone();
+        db.save(db_path)?;
+        Ok(summary)
+    }
+
+    fn build_index(&mut self) {
+        self.index = self
+            .records
+            .iter()
+            .enumerate()
+            .map(|(idx, rec)| (ConsKey::from(&rec.cons), idx))
+            .collect();
+    }
+
+    pub fn get_record(&self, key: &ConsKey) -> Option<&ConsRecord> {
+        self.index.get(key).map(|idx| &self.records[*idx])
+    }
+
+    pub fn iter_records(&self) -> impl Iterator<Item = &ConsRecord> {
+        self.records.iter()
+    }
+
+    pub fn get_runs(&self) -> &[RunSummary] {
+        &self.runs
+    }
+
+    pub fn count_status(&self, status: ConsStatus) -> usize {
+        self.records
+            .iter()
+            .filter(|rec| rec.status == status)
+            .count()
+    }
+
+    /// Merge the constraints extracted by a run with its coverage.
+    /// Open constraints not reported by the run are solved if their unselected arms are covered.
+    /// Merging a run again replaces its summary without counting its constraints twice.
+    pub fn update(
+        &mut self,
+        run: &str,
+        cov: &CodeCoverage,
+        cons_list: &[UBConstraint],
+    ) -> &RunSummary {
+        let time = get_formatted_time();
+        let stamp = RunStamp::new(run, &time);
+        let mut summary = RunSummary {
+            run: run.to_owned(),
+            time,
+            seen: 0,
+            new: 0,
+            solved: 0,
+            reopened: 0,
+            open: 0,
+        };
+
+        let mut seen = vec![false; self.records.len()];
+        for cons in cons_list {
+            let key = ConsKey::from(cons);
+            if let Some(&idx) = self.index.get(&key) {
+                if seen[idx] {
+                    continue;
+                }
+                seen[idx] = true;
+                let rec = &mut self.records[idx];
+                rec.cons = cons.clone();
+                if rec.last_seen.run != run {
+                    rec.seen_count += 1;
+                }
+                if rec.first_seen.run == run {
+                    summary.new += 1;
+                }
+                rec.last_seen = stamp.clone();
+                if rec.status == ConsStatus::Solved {
+                    log::warn!("Solved constraint is reported again: {}", cons);
+                    rec.status = ConsStatus::Open;
+                    rec.solved_by = None;
+                    summary.reopened += 1;
+                }
+            } else {
+                self.index.insert(key, self.records.len());
+                seen.push(true);
+                self.records.push(ConsRecord {
+                    cons: cons.clone(),
+                    status: ConsStatus::Open,
+                    first_seen: stamp.clone(),
+                    last_seen: stamp.clone(),
+                    seen_count: 1,
+                    solved_by: None,
+                });
+                summary.new += 1;
+            }
+            summary.seen += 1;
+        }
+
+        for (rec, _) in self.records.iter_mut().zip(seen).filter(|(_, seen)| !seen) {
+            if rec.status == ConsStatus::Open && rec.cons.is_ub_arm_covered(cov) {
+                rec.status = ConsStatus::Solved;
+                rec.solved_by = Some(stamp.clone());
+            }
+        }
+
+        // counted from the records, so that merging the run again keeps them
+        summary.solved = self
+            .records
+            .iter()
+            .filter(|rec| rec.solved_by.as_ref().is_some_and(|by| by.run == run))
+            .count();
+        summary.open = self.count_status(ConsStatus::Open);
+        match self.runs.iter().position(|prev| prev.run == run) {
+            Some(pos) => {
+                summary.reopened += self.runs[pos].reopened;
+                self.runs[pos] = summary;
+                &self.runs[pos]
+            }
+            None => {
+                self.runs.push(summary);
+                self.runs.last().unwrap()
+            }
+        }
+    }
+
+    /// Set the status of the constraints starting at the location, returns the number of them.
+    pub fn set_status(&mut self, fpath: &Path, loc: [usize; 2], status: ConsStatus) -> usize {
+        let mut count = 0;
+        for rec in self
+            .records
+            .iter_mut()
+            .filter(|rec| rec.cons.fpath == fpath && rec.cons.range[..2] == loc)
+        {
+            rec.status = status;
+            if status != ConsStatus::Solved {
+                rec.solved_by = None;
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cov_export, ub_cons};
+    use serde_json::json;
+
+    fn get_cov(counts: [usize; 2]) -> Result<CodeCoverage> {
+        let cov = serde_json::from_value(cov_export(json!([{
+            "branches": [[10, 5, 10, 9, counts[0], counts[1], 0, 0, 4]],
+            "filenames": ["/src/lib.c"],
+            "regions": [[1, 1, 100, 1, 1, 0, 0, 0]],
+            "count": 1,
+            "name": "api",
+        }])))?;
+        Ok(cov)
+    }
+
+    fn cons_at(line: usize) -> Result<UBConstraint> {
+        ub_cons(
+            "flag",
+            false,
+            "/src/lib.c",
+            [line, 5, line, 9],
+            "int api(int flag)",
+        )
+    }
+
+    #[test]
+    fn test_cons_db_update() -> Result<()> {
+        let mut db = ConsDb::default();
+        let cons = cons_at(10)?;
+        let other = cons_at(20)?;
+        let summary = db.update("run-1", &get_cov([3, 0])?, &[cons.clone(), other.clone()]);
+        assert_eq!((summary.seen, summary.new, summary.open), (2, 2, 2));
+
+        // the false arm of line 10 is covered, line 20 is not reported nor covered
+        let summary = db.update("run-2", &get_cov([3, 1])?, &[]);
+        assert_eq!((summary.solved, summary.open), (1, 1));
+        let rec = db.get_record(&ConsKey::from(&cons)).unwrap();
+        assert_eq!(rec.get_status(), ConsStatus::Solved);
+        assert_eq!(rec.get_solved_by().unwrap().run, "run-2");
+        assert_eq!(rec.get_first_seen().run, "run-1");
+
+        let summary = db.update("run-3", &get_cov([3, 0])?, std::slice::from_ref(&cons));
+        assert_eq!((summary.new, summary.reopened, summary.open), (0, 1, 2));
+        assert_eq!(
+            db.get_record(&ConsKey::from(&cons))
+                .unwrap()
+                .get_seen_count(),
+            2
+        );
+
+        assert_eq!(
+            db.set_status(Path::new("/src/lib.c"), [20, 5], ConsStatus::Infeasible),
+            1
+        );
+        assert_eq!(db.count_status(ConsStatus::Open), 1);
+        assert_eq!(db.get_runs().len(), 3);
+
+        // merging run-3 again changes nothing
+        let summary = db
+            .update("run-3", &get_cov([3, 0])?, std::slice::from_ref(&cons))
+            .clone();
+        assert_eq!((summary.new, summary.reopened, summary.open), (0, 1, 1));
+        assert_eq!(
+            db.get_record(&ConsKey::from(&cons))
+                .unwrap()
+                .get_seen_count(),
+            2
+        );
+        assert_eq!(db.get_runs().len(), 3);
+
+        let db_dir = tempfile::tempdir()?;
+        let db_path = db_dir.path().join("cons_db.json");
+        let summary = ConsDb::update_file(&db_path, "run-1", &get_cov([3, 0])?, &[other])?;
+        assert_eq!(summary.new, 1);
+        ConsDb::update_file(&db_path, "run-1", &get_cov([3, 0])?, &[])?;
+        let db = ConsDb::load(&db_path)?;
+        assert_eq!(db.get_runs().len(), 1);
+        assert!(!db_path.with_extension("lock").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Difference of the unselected branch constraints between two experiments, e.g. runs with
+//! different drivers or library versions.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deopt::{utils::buffer_read_to_bytes, Deopt},
+    execution::Executor,
+    feedback::{
+        branches::constraints::{Range, UBConstraint},
+        clang_coverage::CodeCoverage,
+    },
+};
+
+/// remove whitespaces, so that reformatted sources still match
+fn normalize(text: &str) -> String {
+    text.chars().filter(|ch| !ch.is_whitespace()).collect()
+}
+
+/// constraints of the same condition in the same function, regardless of the location
+fn get_match_key(cons: &UBConstraint) -> (String, String, bool) {
+    (
+        normalize(cons.get_cond_expr()),
+        normalize(cons.get_func_sig()),
+        cons.get_res(),
+    )
+}
+
+/// constraints of the same condition and result at the same location
+fn get_loc_key(cons: &UBConstraint) -> (PathBuf, Range, String, String, bool) {
+    (
+        cons.fpath.clone(),
+        cons.range,
+        cons.func_sig.clone(),
+        normalize(cons.get_cond_expr()),
+        cons.get_res(),
+    )
+}
+
+fn sort_by_loc(cons_list: &mut [&UBConstraint]) {
+    cons_list.sort_by(|a, b| (&a.fpath, a.range).cmp(&(&b.fpath, b.range)));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GoneKind {
+    /// the unselected arm is covered in the target experiment
+    Solved,
+    /// the function of the constraint is not executed in the target experiment
+    Unreached,
+    /// coverage of the target experiment is absent or does not explain it
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoneCons {
+    pub cons: UBConstraint,
+    pub kind: GoneKind,
+}
+
+/// the same constraint at different locations because of source edits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovedCons {
+    pub base: UBConstraint,
+    pub target: UBConstraint,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ConsDiff {
+    /// constraints at the same location in both experiments
+    unchanged: usize,
+    /// constraints of the base experiment only
+    disappeared: Vec<GoneCons>,
+    /// constraints of the target experiment only, i.e. the new frontier
+    appeared: Vec<UBConstraint>,
+    moved: Vec<MovedCons>,
+}
+
+impl ConsDiff {
+    pub fn get_unchanged(&self) -> usize {
+        self.unchanged
+    }
+
+    pub fn get_disappeared(&self) -> &[GoneCons] {
+        &self.disappeared
+    }
+
+    pub fn get_appeared(&self) -> &[UBConstraint] {
+        &self.appeared
+    }
+
+    pub fn get_moved(&self) -> &[MovedCons] {
+        &self.moved
+    }
+
+    fn classify_gone(cons: &UBConstraint, target_cov: Option<&CodeCoverage>) -> GoneKind {
+        let cov = match target_cov {
+            Some(cov) => cov,
+            None => return GoneKind::Unknown,
+        };
+        if cons.is_ub_arm_covered(cov) {
+            return GoneKind::Solved;
+        }
+        match cov.reaches_cons_func(cons) {
+            Ok(false) => GoneKind::Unreached,
+            _ => GoneKind::Unknown,
+        }
+    }
+
+    /// Constraints are matched by location, condition and result first, the rest are matched by
+    /// their normalized conditions and function signatures.
+    pub fn diff(
+        base: &[UBConstraint],
+        target: &[UBConstraint],
+        target_cov: Option<&CodeCoverage>,
+    ) -> Self {
+        let mut diff = Self::default();
+        let mut target_locs: HashMap<_, Vec<&UBConstraint>> = HashMap::new();
+        for cons in target {
+            target_locs.entry(get_loc_key(cons)).or_default().push(cons);
+        }
+        let mut base_left: Vec<&UBConstraint> = vec![];
+        for cons in base {
+            match target_locs
+                .get_mut(&get_loc_key(cons))
+                .and_then(|group| group.pop())
+            {
+                Some(_) => diff.unchanged += 1,
+                None => base_left.push(cons),
+            }
+        }
+
+        // pair the moved constraints in the order of their locations
+        let mut target_left: Vec<&UBConstraint> = target_locs.into_values().flatten().collect();
+        sort_by_loc(&mut target_left);
+        let mut target_groups: HashMap<(String, String, bool), Vec<&UBConstraint>> = HashMap::new();
+        for cons in target_left {
+            target_groups
+                .entry(get_match_key(cons))
+                .or_default()
+                .push(cons);
+        }
+        for group in target_groups.values_mut() {
+            group.reverse();
+        }
+        sort_by_loc(&mut base_left);
+        for cons in base_left {
+            match target_groups
+                .get_mut(&get_match_key(cons))
+                .and_then(|group| group.pop())
+            {
+                Some(moved) => diff.moved.push(MovedCons {
+                    base: cons.clone(),
+                    target: moved.clone(),
+                }),
+                None => diff.disappeared.push(GoneCons {
+                    cons: cons.clone(),
+                    kind: Self::classify_gone(cons, target_cov),
+                }),
+            }
+        }
+
+        // the unpaired ones are new
+        let mut appeared: Vec<&UBConstraint> = target_groups.into_values().flatten().collect();
+        sort_by_loc(&mut appeared);
+        diff.appeared = appeared.into_iter().cloned().collect();
+        diff
+    }
+
+    fn load_cons_list(work_dir: &Path, deopt: &Deopt) -> Result<Vec<UBConstraint>> {
+        let cons_path = deopt.get_constraints_path(work_dir);
+        let bytes = buffer_read_to_bytes(&cons_path)
+            .map_err(|e| eyre::eyre!("Failed to read {:?}: {}", cons_path, e))?;
+        let cons_list = serde_json::from_slice(&bytes)?;
+        Ok(cons_list)
+    }
+
+    /// Diff the constraints of two experiment work dirs, the coverage of the target experiment
+    /// tells why constraints disappear.
+    pub fn from_expe_dirs(executor: &Executor, base_dir: &Path, target_dir: &Path) -> Result<Self> {
+        let deopt = &executor.deopt;
+        let base = Self::load_cons_list(base_dir, deopt)?;
+        let target = Self::load_cons_list(target_dir, deopt)?;
+
+        let profdata = Deopt::get_coverage_file_by_dir(target_dir);
+        let target_cov = if profdata.is_file() {
+            Some(executor.obtain_cov_from_profdata(&profdata)?)
+        } else {
+            log::warn!(
+                "Coverage not found at {:?}, disappeared constraints are not classified",
+                profdata
+            );
+            None
+        };
+        Ok(Self::diff(&base, &target, target_cov.as_ref()))
+    }
+}
+
+impl fmt::Display for ConsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "unchanged: {}, disappeared: {}, appeared: {}, moved: {}",
+            self.unchanged,
+            self.disappeared.len(),
+            self.appeared.len(),
+            self.moved.len()
+        )?;
+        for gone in self.disappeared.iter() {
+            writeln!(f, "- [{:?}] {}", gone.kind, gone.cons)?;
+        }
+        for cons in self.appeared.iter() {
+            writeln!(f, "+ {}", cons)?;
+        }
+        for moved in self.moved.iter() {
+            writeln!(
+                f,
+                "~ {} moved to {:?} with range {:?}",
+                moved.base, moved.target.fpath, moved.target.range
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ub_cons;
+
+    fn cons_at(cond_expr: &str, line: usize) -> Result<UBConstraint> {
+        ub_cons(
+            cond_expr,
+            false,
+            "/src/lib.c",
+            [line, 5, line, 9],
+            "int api(int flag)",
+        )
+    }
+
+    #[test]
+    fn test_cons_diff() -> Result<()> {
+        let base = vec![
+            cons_at("flag", 10)?,
+            cons_at("len > 0", 20)?,
+            cons_at("buf == NULL", 30)?,
+        ];
+        let target = vec![
+            cons_at("flag", 10)?,
+            cons_at("len>0", 22)?,
+            cons_at("len < max", 40)?,
+        ];
+        let diff = ConsDiff::diff(&base, &target, None);
+        assert_eq!(diff.get_unchanged(), 1);
+        assert_eq!(diff.get_moved().len(), 1);
+        assert_eq!(diff.get_moved()[0].target.range[0], 22);
+        assert_eq!(diff.get_disappeared().len(), 1);
+        assert_eq!(diff.get_disappeared()[0].kind, GoneKind::Unknown);
+        assert_eq!(diff.get_appeared()[0].get_cond_expr(), "len < max");
+
+        // the other arm of the same branch is a different constraint
+        let mut other_arm = cons_at("flag", 10)?;
+        other_arm.res = true;
+        let diff = ConsDiff::diff(&base[..1], &[other_arm], None);
+        assert_eq!(diff.get_unchanged(), 0);
+        assert_eq!(diff.get_disappeared().len(), 1);
+        assert_eq!(diff.get_appeared().len(), 1);
+        Ok(())
+    }
+}
@@ -10,6 +10,7 @@ use std::{
 use crate::{
     analysis::constraint::exec_rec::ExecRec,
     analysis::constraint::inter::exec_tree::thread_tree::UBVHit,
+    analysis::constraint::inter::loc::SrcLoc,
     config::{get_config, is_debug_mode},
     execution::max_cpu_count,
     feedback::clang_coverage::{
@@ -25,6 +26,9 @@ use threadpool::ThreadPool;
 
 use super::{Branch, BranchTrait};
 
+pub mod cons_db;
+pub mod cons_diff;
+pub mod rank;
 pub mod source_check;
 
 pub type Loc = [usize; 2];
@@ -279,7 +283,11 @@ impl UBConstraint {
 
     /// judge if a CovFunction is related to this constraint
     pub fn is_hit(&self, val_hit: &UBVHit) -> Result<bool> {
-        let loc = val_hit.get_loc();
+        self.is_hit_loc(val_hit.get_loc())
+    }
+
+    /// judge if a location, e.g. value location of a branch guard, is inside the constraint
+    pub fn is_hit_loc(&self, loc: &SrcLoc) -> Result<bool> {
         loc.inside_range(&self.range, &self.fpath)
             .map_err(|e| eyre::eyre!("Failed to check if loc is inside range: {}", e))
     }
@@ -304,6 +312,90 @@ impl UBConstraint {
         })
     }
 
+    pub fn get_cond_expr(&self) -> &str {
+        &self.cond_expr
+    }
+
+    /// result value of the condition expression
+    pub fn get_res(&self) -> bool {
+        self.res
+    }
+
+    pub fn get_slice(&self) -> &str {
+        &self.slice
+    }
+
+    pub fn get_macro_mapping(&self) -> &MacMapping {
+        &self.macro_mapping
+    }
+
+    /// coverage branch of this constraint in the function coverage
+    pub fn find_cov_branch<'a>(&self, func: &'a CovFunction) -> Option<&'a CovBranch> {
+        func.iter_cov_branches().find(|cov_br| {
+            cov_br[..4] == self.range
+                && func
+                    .get_source_file_path_by_cov_branch(cov_br)
+                    .is_ok_and(|fpath| fpath == self.fpath)
+        })
+    }
+
+    /// whether the unselected arm of this constraint is executed in the coverage
+    pub fn is_ub_arm_covered(&self, cov: &CodeCoverage) -> bool {
+        let func_name = match self.get_func_name() {
+            Ok(name) => name,
+            Err(_) => return false,
+        };
+        let cov_br_op = cov
+            .get_function_cov(&func_name)
+            .and_then(|func| self.find_cov_branch(func));
+        match cov_br_op {
+            Some(cov_br) if self.res => *cov_br.get_true_count() > 0,
+            Some(cov_br) => *cov_br.get_false_count() > 0,
+            None => false,
+        }
+    }
+
+    /// Get variable names used as operands in the condition expression.
+    /// Callee names and C keywords are excluded, macros are replaced by identifiers in their expansion.
+    pub fn get_cond_operands(&self) -> Vec<String> {
+        const C_KEYWORDS: [&str; 12] = [
+            "sizeof", "int", "char", "long", "short", "unsigned", "signed", "const", "struct",
+            "void", "NULL", "defined",
+        ];
+        let ident_re = Regex::new(r"\b[A-Za-z_]\w*(\s*\()?").unwrap();
+
+        let mut operands: Vec<String> = vec![];
+        let mut expanded: Vec<&str> = vec![];
+        let mut worklist = vec![self.cond_expr.clone()];
+        while let Some(expr) = worklist.pop() {
+            for cap in ident_re.captures_iter(&expr) {
+                // function invocation
+                if cap.get(1).is_some() {
+                    continue;
+                }
+                let ident = cap[0].to_owned();
+                if C_KEYWORDS.contains(&ident.as_str()) || operands.contains(&ident) {
+                    continue;
+                }
+                // preceded by member access, the base variable is collected instead
+                let start = cap.get(0).unwrap().start();
+                let prefix = expr[..start].trim_end();
+                if prefix.ends_with('.') || prefix.ends_with("->") {
+                    continue;
+                }
+                if let Some((name, expansion)) = self.macro_mapping.get_key_value(&ident) {
+                    if !expanded.contains(&name.as_str()) {
+                        expanded.push(name);
+                        worklist.push(expansion.to_owned());
+                    }
+                    continue;
+                }
+                operands.push(ident);
+            }
+        }
+        operands
+    }
+
     fn get_cond_expr_in_fname(&self) -> String {
         self.cond_expr
             .chars()
@@ -624,7 +716,7 @@ impl CodeCoverage {
 
 #[cfg(test)]
 mod tests {
-    use crate::{deopt::Deopt, init_report_utils_for_tests};
+    use crate::{deopt::Deopt, init_report_utils_for_tests, test_utils::ub_cons};
     use color_eyre::eyre::Result;
 
     use super::*;
@@ -654,4 +746,17 @@ aom_codec_err_t aom_codec_enc_init_ver(aom_codec_ctx_t *ctx,
         log::debug!("func_sig: {}", form_func_sig);
         Ok(())
     }
+
+    #[test]
+    fn test_get_cond_operands() -> Result<()> {
+        let cons = ub_cons(
+            "(flags & 0x1F) > 10u && hdr->len < max_len(buf)",
+            true,
+            "/src/a.c",
+            [9, 9, 9, 40],
+            "int parse(int flags)",
+        )?;
+        assert_eq!(cons.get_cond_operands(), ["flags", "hdr", "buf"]);
+        Ok(())
+    }
 }
@@ -0,0 +1,354 @@
+//! Ranking of unselected branch constraints, so that the analysis budget is spent on the
+//! constraints which are easy to reach and guard much uncovered code.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analysis::callgraph::CallGraph,
+    feedback::{
+        branches::constraints::{CovRegionTrait, LocTrait, Range, RangeTrait, UBConstraint},
+        clang_coverage::{BranchCount, CodeCoverage, CovFunction},
+    },
+};
+
+/// weights of the features in the score
+const DOWNSTREAM_WEIGHT: f64 = 1.0;
+const SIBLING_HIT_WEIGHT: f64 = 0.5;
+const ENTRY_DIST_WEIGHT: f64 = 0.5;
+/// distance used when the constraint function is not in the call graph
+const UNKNOWN_ENTRY_DIST: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RankFeatures {
+    /// call distance from the nearest covered entry function, None if not in the call graph
+    pub entry_dist: Option<usize>,
+    /// execution count of the covered arm of the branch
+    pub sibling_hits: usize,
+    /// branch arms of the uncovered functions reachable from the calls in the unselected arm,
+    /// or from the whole constraint function if the arm is not found
+    pub downstream_ub: usize,
+}
+
+impl RankFeatures {
+    /// higher is more valuable
+    pub fn score(&self) -> f64 {
+        let entry_dist = self.entry_dist.unwrap_or(UNKNOWN_ENTRY_DIST);
+        DOWNSTREAM_WEIGHT * (1.0 + self.downstream_ub as f64).ln()
+            + SIBLING_HIT_WEIGHT * (1.0 + self.sibling_hits as f64).ln()
+            - ENTRY_DIST_WEIGHT * entry_dist as f64
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedCons {
+    pub cons: UBConstraint,
+    pub features: RankFeatures,
+    pub score: f64,
+}
+
+pub struct ConsRanker<'a> {
+    cov: &'a CodeCoverage,
+    call_graph: &'a CallGraph,
+    /// call distances of covered functions from the covered entry functions
+    entry_dists: HashMap<&'a str, usize>,
+    /// uncovered function -> number of its branch arms
+    uncovered_arms: HashMap<&'a str, usize>,
+    /// function -> uncovered functions reachable from it, excluding itself
+    reach_cache: RefCell<HashMap<String, HashSet<&'a str>>>,
+}
+
+impl<'a> ConsRanker<'a> {
+    pub fn new(cov: &'a CodeCoverage, call_graph: &'a CallGraph) -> Self {
+        let entry_dists = Self::compute_entry_dists(cov, call_graph);
+        let uncovered_arms = cov
+            .iter_function_covs()
+            .filter(|func| func.count == 0)
+            .map(|func| (func.get_name(), func.branches.len() * 2))
+            .collect();
+        Self {
+            cov,
+            call_graph,
+            entry_dists,
+            uncovered_arms,
+            reach_cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Entry functions are covered functions without covered callers, e.g. APIs called by the
+    /// fuzz driver. Distances are computed along covered functions only.
+    fn compute_entry_dists(
+        cov: &'a CodeCoverage,
+        call_graph: &'a CallGraph,
+    ) -> HashMap<&'a str, usize> {
+        let covered: HashSet<&str> = cov
+            .iter_function_covs()
+            .filter(|func| func.count > 0)
+            .map(|func| func.get_name())
+            .filter(|name| call_graph.contains_func(name))
+            .collect();
+
+        let mut dists: HashMap<&str, usize> = HashMap::new();
+        let mut queue: VecDeque<(&str, usize)> = covered
+            .iter()
+            .filter(|name| {
+                call_graph
+                    .get_direct_callers(name)
+                    .iter()
+                    .all(|caller| !covered.contains(caller))
+            })
+            .map(|name| (*name, 0))
+            .collect();
+        while let Some((name, dist)) = queue.pop_front() {
+            if dists.contains_key(name) {
+                continue;
+            }
+            dists.insert(name, dist);
+            for callee in call_graph.get_direct_callees(name) {
+                if let Some(callee) = covered.get(callee) {
+                    queue.push_back((callee, dist + 1));
+                }
+            }
+        }
+        dists
+    }
+
+    fn get_uncovered_reachable(&self, func_name: &str) -> HashSet<&'a str> {
+        self.reach_cache
+            .borrow_mut()
+            .entry(func_name.to_owned())
+            .or_insert_with(|| {
+                self.call_graph
+                    .get_reachable_funcs(func_name)
+                    .into_keys()
+                    .filter(|name| *name != func_name)
+                    .filter_map(|name| self.uncovered_arms.get_key_value(name))
+                    .map(|(name, _)| *name)
+                    .collect()
+            })
+            .clone()
+    }
+
+    /// The first uncovered code region after the condition, which is the body of the
+    /// unselected arm, or the code following the branch if the arm has no body.
+    fn find_unselected_arm(func_cov: &CovFunction, cons: &UBConstraint) -> Option<Range> {
+        let [_, cond_end] = cons.range.extract_locs().ok()?;
+        func_cov
+            .regions
+            .iter()
+            .skip(1)
+            .filter(|rgn| rgn.is_code_region() && rgn[4] == 0)
+            .filter(|rgn| {
+                func_cov
+                    .filenames
+                    .get(rgn.get_file_id())
+                    .map(String::as_str)
+                    == cons.fpath.to_str()
+            })
+            .filter_map(|rgn| Range::from_slice(rgn.as_slice()).ok())
+            .filter(|rng| cond_end.is_less_equal(&[rng[0], rng[1]]))
+            .min()
+    }
+
+    /// Direct callees of the function invoked from the source text of the unselected arm.
+    fn find_arm_callees(&self, func_name: &str, cons: &UBConstraint) -> Option<Vec<&'a str>> {
+        let func_cov = self.cov.get_function_cov(func_name)?;
+        let arm = Self::find_unselected_arm(func_cov, cons)?;
+        let arm_text = match arm.get_range_text_from_file(&cons.fpath) {
+            Ok(text) => text,
+            Err(e) => {
+                log::debug!("Failed to read the unselected arm of {}: {}", cons, e);
+                return None;
+            }
+        };
+        let callees = self
+            .call_graph
+            .get_direct_callees(func_name)
+            .into_iter()
+            .filter(|callee| {
+                Regex::new(&format!(r"\b{}\s*\(", regex::escape(callee)))
+                    .is_ok_and(|re| re.is_match(&arm_text))
+            })
+            .collect();
+        Some(callees)
+    }
+
+    fn count_downstream_ub(&self, func_name: &str, cons: &UBConstraint) -> usize {
+        let reachable: HashSet<&str> = match self.find_arm_callees(func_name, cons) {
+            Some(callees) => callees
+                .into_iter()
+                .flat_map(|callee| {
+                    let mut funcs = self.get_uncovered_reachable(callee);
+                    if self.uncovered_arms.contains_key(callee) {
+                        funcs.insert(callee);
+                    }
+                    funcs
+                })
+                .filter(|name| *name != func_name)
+                .collect(),
+            None => self.get_uncovered_reachable(func_name),
+        };
+        reachable
+            .into_iter()
+            .map(|name| self.uncovered_arms[name])
+            .sum()
+    }
+
+    pub fn get_features(&self, cons: &UBConstraint) -> RankFeatures {
+        let func_name = match cons.get_func_name() {
+            Ok(name) => name,
+            Err(e) => {
+                log::warn!("{}", e);
+                return RankFeatures {
+                    entry_dist: None,
+                    sibling_hits: 0,
+                    downstream_ub: 0,
+                };
+            }
+        };
+
+        let cov_br_op = self
+            .cov
+            .get_function_cov(&func_name)
+            .and_then(|func| cons.find_cov_branch(func));
+        // the constraint result is the value of the unselected arm
+        let sibling_hits = match cov_br_op {
+            Some(cov_br) if cons.get_res() => *cov_br.get_false_count(),
+            Some(cov_br) => *cov_br.get_true_count(),
+            None => {
+                log::debug!("Coverage branch not found for {}", cons);
+                0
+            }
+        };
+
+        RankFeatures {
+            entry_dist: self.entry_dists.get(func_name.as_str()).copied(),
+            sibling_hits,
+            downstream_ub: self.count_downstream_ub(&func_name, cons),
+        }
+    }
+
+    /// constraints in descending order of their scores
+    pub fn rank(&self, cons_list: &[UBConstraint]) -> Vec<RankedCons> {
+        let mut ranked: Vec<RankedCons> = cons_list
+            .iter()
+            .map(|cons| {
+                let features = self.get_features(cons);
+                RankedCons {
+                    cons: cons.clone(),
+                    score: features.score(),
+                    features,
+                }
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cov_export, ub_cons};
+    use color_eyre::eyre::Result;
+    use serde_json::json;
+    use std::path::Path;
+
+    fn cov_func(
+        name: &str,
+        count: usize,
+        branches: serde_json::Value,
+        fpath: &Path,
+        arms: &[[usize; 2]],
+    ) -> serde_json::Value {
+        let mut regions = vec![json!([1, 1, 100, 1, count, 0, 0, 0])];
+        regions.extend(
+            arms.iter()
+                .map(|[ls, le]| json!([ls, 1, le, 30, 0, 0, 0, 0])),
+        );
+        json!({
+            "branches": branches,
+            "filenames": [fpath],
+            "regions": regions,
+            "count": count,
+            "name": name,
+        })
+    }
+
+    fn cons_at(line: usize, func_sig: &str, fpath: &Path) -> Result<UBConstraint> {
+        ub_cons("flag", false, fpath, [line, 5, line, 9], func_sig)
+    }
+
+    #[test]
+    fn test_rank_constraints() -> Result<()> {
+        let src_dir = tempfile::tempdir()?;
+        let fpath = src_dir.path().join("lib.c");
+        let mut lines = vec![""; 9];
+        lines.extend([
+            "    if (flag)",
+            "        decode(flag);",
+            "    if (flag)",
+            "        helper(flag);",
+        ]);
+        std::fs::write(&fpath, lines.join("\n") + "\n")?;
+
+        let cov: CodeCoverage = serde_json::from_value(cov_export(json!([
+            cov_func(
+                "api",
+                1,
+                json!([[10, 5, 10, 9, 7, 0, 0, 0, 4], [12, 5, 12, 9, 7, 0, 0, 0, 4]]),
+                &fpath,
+                &[[11, 11], [13, 13]],
+            ),
+            cov_func(
+                "helper",
+                3,
+                json!([[20, 5, 20, 9, 2, 0, 0, 0, 4]]),
+                &fpath,
+                &[]
+            ),
+            cov_func(
+                "decode",
+                0,
+                json!([[30, 5, 30, 9, 0, 0, 0, 0, 4]; 3].to_vec()),
+                &fpath,
+                &[],
+            ),
+        ])))?;
+        let call_graph =
+            CallGraph::from_edges([("api", "helper"), ("api", "decode"), ("helper", "log")]);
+        let ranker = ConsRanker::new(&cov, &call_graph);
+
+        let api_cons = cons_at(10, "int api(int flag)", &fpath)?;
+        let helper_cons = cons_at(20, "static void helper(int flag)", &fpath)?;
+        assert_eq!(
+            ranker.get_features(&api_cons),
+            RankFeatures {
+                entry_dist: Some(0),
+                sibling_hits: 7,
+                downstream_ub: 6,
+            }
+        );
+        // the unselected arm only calls the covered helper
+        let api_helper_cons = cons_at(12, "int api(int flag)", &fpath)?;
+        assert_eq!(ranker.get_features(&api_helper_cons).downstream_ub, 0);
+        // no uncovered arm in helper, all its callees are counted
+        assert_eq!(
+            ranker.get_features(&helper_cons),
+            RankFeatures {
+                entry_dist: Some(1),
+                sibling_hits: 2,
+                downstream_ub: 0,
+            }
+        );
+
+        let ranked = ranker.rank(&[helper_cons, api_helper_cons, api_cons]);
+        assert_eq!(ranked[0].cons.range[0], 10);
+        Ok(())
+    }
+}
@@ -1,26 +1,239 @@
 use std::{
+    fmt,
     fs::File,
     io::{BufRead, BufReader},
     path::Path,
 };
 
 use crate::{
-    analysis::constraint::inter::loc::SrcLoc,
-    feedback::branches::constraints::{Loc, LocTrait, Range, RangeTrait, UBConstraint},
+    analysis::constraint::{
+        inter::loc::SrcLoc,
+        intra::func_src_tree::{
+            code_query::{
+                custom_class_query::{get_enum_value_map, EnumValueMap},
+                switch_query::{CaseMap, SwitchPool},
+                CodeQLRunner,
+            },
+            stmts::SwitchStmt,
+        },
+    },
+    feedback::branches::constraints::{Loc, LocTrait, MacMapping, Range, RangeTrait, UBConstraint},
 };
 use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
 
-enum UBType {
+const CASE_PREFIX: &str = "case";
+const DEFAULT_PREFIX: &str = "default";
+/// limit of nested macro expansions when resolving a case value
+const MAX_EXPANSION_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UBType {
     // selection or loop
     SelorLoop,
     Switch,
 }
 
+/// label of a case clause, e.g. `AOM_CODEC_X` of `case AOM_CODEC_X:`, None for `default:`
+fn get_case_label(clause: &str) -> Option<String> {
+    let clause = clause.trim();
+    let clause = clause.strip_suffix(':').unwrap_or(clause);
+    let label = clause.strip_prefix(CASE_PREFIX)?;
+    Some(label.trim().to_owned())
+}
+
+fn strip_parens(text: &str) -> &str {
+    let mut text = text.trim();
+    while let Some(inner) = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        text = inner.trim();
+    }
+    text
+}
+
+fn parse_char_literal(text: &str) -> Option<i64> {
+    let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+    let ch = match inner {
+        "\\n" => '\n',
+        "\\t" => '\t',
+        "\\r" => '\r',
+        "\\0" => '\0',
+        "\\\\" => '\\',
+        "\\'" => '\'',
+        _ => {
+            let mut chars = inner.chars();
+            let ch = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            ch
+        }
+    };
+    Some(ch as i64)
+}
+
+/// parse integer literals of C, including char literals
+fn parse_int_literal(text: &str) -> Option<i64> {
+    if let Some(val) = parse_char_literal(text) {
+        return Some(val);
+    }
+    let (neg, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, text),
+    };
+    let text = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let val = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = text.strip_prefix("0b").or(text.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if text.len() > 1 && text.starts_with('0') {
+        i64::from_str_radix(&text[1..], 8).ok()?
+    } else {
+        text.parse().ok()?
+    };
+    Some(if neg { -val } else { val })
+}
+
+/// Resolve the value of a case label through macro expansions and enum constants.
+pub fn resolve_case_value(
+    label: &str,
+    mac_mapping: &MacMapping,
+    enum_values: &EnumValueMap,
+) -> Option<i64> {
+    let mut text = label.to_owned();
+    for _ in 0..MAX_EXPANSION_DEPTH {
+        let stripped = strip_parens(&text);
+        if let Some(val) = parse_int_literal(stripped) {
+            return Some(val);
+        }
+        if let Some(val) = enum_values.get(stripped) {
+            return Some(*val);
+        }
+        text = mac_mapping.get(stripped)?.to_owned();
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseValue {
+    pub label: String,
+    /// None if the label can not be resolved to a constant
+    pub value: Option<i64>,
+}
+
+impl CaseValue {
+    pub fn resolve(label: &str, mac_mapping: &MacMapping, enum_values: &EnumValueMap) -> Self {
+        Self {
+            label: label.to_owned(),
+            value: resolve_case_value(label, mac_mapping, enum_values),
+        }
+    }
+}
+
+impl fmt::Display for CaseValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            Some(val) if val.to_string() != self.label => write!(f, "{} ({})", self.label, val),
+            _ => write!(f, "{}", self.label),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwitchArm {
+    Case(CaseValue),
+    /// the default arm, with values of the other cases in the switch
+    Default(Vec<CaseValue>),
+}
+
+/// Constraint of a case clause, stated on the switch controlling expression.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwitchCons {
+    /// start location of the switch statement
+    pub switch_start: Loc,
+    /// controlling expression of the switch statement
+    pub expr: String,
+    pub arm: SwitchArm,
+    /// result value of the constraint, true if the arm is never taken
+    pub res: bool,
+}
+
+impl fmt::Display for SwitchCons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.arm, self.res) {
+            (SwitchArm::Case(case), true) => {
+                write!(f, "value {} never observed for expr {}", case, self.expr)
+            }
+            (SwitchArm::Case(case), false) => {
+                write!(f, "expr {} always observed as value {}", self.expr, case)
+            }
+            (SwitchArm::Default(cases), res) => {
+                let cases: Vec<String> = cases.iter().map(|case| case.to_string()).collect();
+                if res {
+                    write!(
+                        f,
+                        "no value other than [{}] observed for expr {}",
+                        cases.join(", "),
+                        self.expr
+                    )
+                } else {
+                    write!(
+                        f,
+                        "none of values [{}] observed for expr {}",
+                        cases.join(", "),
+                        self.expr
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// CodeQL results needed to resolve switch constraints.
+pub struct SwitchResolver {
+    switch_pool: SwitchPool,
+    enum_values: EnumValueMap,
+}
+
+impl SwitchResolver {
+    pub fn new(switch_pool: SwitchPool, enum_values: EnumValueMap) -> Self {
+        Self {
+            switch_pool,
+            enum_values,
+        }
+    }
+
+    pub fn from_codeql_runner(runner: &CodeQLRunner) -> Result<Self> {
+        let switch_pool = runner.get_switch_pool()?;
+        let cc_set = runner.get_custom_class_set()?;
+        Ok(Self::new(switch_pool, get_enum_value_map(&cc_set)))
+    }
+
+    pub fn get_enum_values(&self) -> &EnumValueMap {
+        &self.enum_values
+    }
+
+    /// switch statement having the case clause of the constraint
+    fn find_switch(&self, cons: &UBConstraint) -> Result<(&SwitchStmt, &CaseMap)> {
+        let func_name = cons.get_func_name()?;
+        let switch_map = self
+            .switch_pool
+            .get_value(&func_name)
+            .ok_or_else(|| eyre::eyre!("No switch statement found in function {}", func_name))?;
+        switch_map
+            .iter()
+            .find(|(switch_stmt, case_map)| {
+                switch_stmt.loc.file_path == cons.fpath
+                    && case_map
+                        .keys()
+                        .any(|case_loc| case_loc.to_range()[..2] == cons.range[..2])
+            })
+            .ok_or_else(|| eyre::eyre!("No switch statement has the case clause of {}", cons))
+    }
+}
+
 impl UBConstraint {
     fn is_switch_case_clause(fpath: &Path, range: Range) -> Result<bool> {
-        const CASE_PREFIX: &str = "case";
-        const DEFAULT_PREFIX: &str = "default";
-
         let file = File::open(fpath)?;
         let reader = BufReader::new(file);
         let [s_loc, e_loc] = range.extract_locs()?;
@@ -67,7 +280,98 @@ impl UBConstraint {
 
     /// return start location switch structure
     /// Assume as switch UB
-    pub fn get_switch_start(&self) -> Result<SrcLoc> {
-        todo!()
+    pub fn get_switch_start(&self, resolver: &SwitchResolver) -> Result<SrcLoc> {
+        let (switch_stmt, _) = resolver.find_switch(self)?;
+        let rng = switch_stmt.loc.to_range();
+        Ok(SrcLoc::from_cov_loc(&[rng[0], rng[1]], &self.fpath))
+    }
+
+    /// Constraint on the controlling expression of the switch, assume as switch UB.
+    pub fn get_switch_cons(&self, resolver: &SwitchResolver) -> Result<SwitchCons> {
+        let (switch_stmt, case_map) = resolver.find_switch(self)?;
+        let expr = switch_stmt.expr_loc.get_content()?;
+        let enum_values = resolver.get_enum_values();
+
+        let arm = match get_case_label(self.get_cond_expr()) {
+            Some(label) => SwitchArm::Case(CaseValue::resolve(
+                &label,
+                self.get_macro_mapping(),
+                enum_values,
+            )),
+            None => {
+                let mut case_locs: Vec<_> = case_map.keys().collect();
+                case_locs.sort();
+                let mut cases = vec![];
+                for case_loc in case_locs {
+                    if let Some(label) = get_case_label(&case_loc.get_content()?) {
+                        cases.push(CaseValue::resolve(
+                            &label,
+                            self.get_macro_mapping(),
+                            enum_values,
+                        ));
+                    }
+                }
+                SwitchArm::Default(cases)
+            }
+        };
+
+        let rng = switch_stmt.loc.to_range();
+        Ok(SwitchCons {
+            switch_start: [rng[0], rng[1]],
+            expr,
+            arm,
+            res: self.get_res(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_resolve_case_value() {
+        let mac_mapping: MacMapping = HashMap::from([
+            ("CODEC_ERR".to_owned(), "(AOM_CODEC_ERROR)".to_owned()),
+            ("HDR_TAG".to_owned(), "0x1f".to_owned()),
+        ]);
+        let enum_values: EnumValueMap = HashMap::from([("AOM_CODEC_ERROR".to_owned(), 1)]);
+        assert_eq!(
+            get_case_label("case CODEC_ERR:"),
+            Some("CODEC_ERR".to_owned())
+        );
+        assert_eq!(get_case_label("default:"), None);
+        assert_eq!(
+            resolve_case_value("CODEC_ERR", &mac_mapping, &enum_values),
+            Some(1)
+        );
+        assert_eq!(
+            resolve_case_value("HDR_TAG", &mac_mapping, &enum_values),
+            Some(31)
+        );
+        assert_eq!(
+            resolve_case_value("'a'", &mac_mapping, &enum_values),
+            Some(97)
+        );
+        assert_eq!(
+            resolve_case_value("-010", &mac_mapping, &enum_values),
+            Some(-8)
+        );
+        assert_eq!(
+            resolve_case_value("UNKNOWN", &mac_mapping, &enum_values),
+            None
+        );
+
+        let switch_cons = SwitchCons {
+            switch_start: [10, 5],
+            expr: "ctx->err".to_owned(),
+            arm: SwitchArm::Case(CaseValue::resolve("CODEC_ERR", &mac_mapping, &enum_values)),
+            res: true,
+        };
+        assert_eq!(
+            switch_cons.to_string(),
+            "value CODEC_ERR (1) never observed for expr ctx->err"
+        );
     }
 }
@@ -10,6 +10,8 @@ pub mod minimize;
 pub mod mutation;
 pub mod program;
 pub mod request;
+#[cfg(test)]
+mod test_utils;
 use async_openai::error::OpenAIError;
 use color_eyre::eyre::Result;
 use config::get_library_name;
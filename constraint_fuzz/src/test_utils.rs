@@ -0,0 +1,76 @@
+//! Fixtures shared by the unit tests.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::Result;
+use serde_json::{json, Value};
+
+use crate::{
+    analysis::constraint::{exec_rec::ExecRec, inter::exec_tree::ExecForest},
+    feedback::branches::constraints::{Range, UBConstraint},
+};
+
+/// Write `lines` as the guard file of the main thread in `guard_dir`, created if absent.
+pub fn write_main_guard<S: AsRef<str>>(guard_dir: &Path, lines: &[S]) -> Result<PathBuf> {
+    std::fs::create_dir_all(guard_dir)?;
+    let guard_fpath = guard_dir.join("1_main");
+    let content: Vec<&str> = lines.iter().map(AsRef::as_ref).collect();
+    std::fs::write(&guard_fpath, content.join("\n") + "\n")?;
+    Ok(guard_fpath)
+}
+
+/// Build the forest of a guard directory with only the main thread.
+pub fn build_guard_forest<S: AsRef<str>>(guard_dir: &Path, lines: &[S]) -> Result<ExecForest> {
+    write_main_guard(guard_dir, lines)?;
+    ExecForest::from_guard_dir(guard_dir)
+}
+
+/// Coverage export of `functions` with empty totals.
+pub fn cov_export(functions: Value) -> Value {
+    let cov_data = json!({"count": 0, "covered": 0, "percent": 0.0});
+    json!({
+        "data": [{
+            "functions": functions,
+            "totals": {
+                "branches": cov_data,
+                "functions": cov_data,
+                "lines": cov_data,
+                "regions": cov_data,
+            },
+        }],
+    })
+}
+
+/// Add an execution to the experiment directory with its coverage and the guards of the main
+/// thread.
+pub fn add_expe_exec<S: AsRef<str>>(
+    expe_dir: &Path,
+    exec_name: &str,
+    cov: &Value,
+    lines: &[S],
+) -> Result<ExecRec> {
+    let cov_path = ExecRec::get_exec_cov_dir(expe_dir)?.join(exec_name);
+    std::fs::write(&cov_path, cov.to_string())?;
+    write_main_guard(&ExecRec::get_sg_guard_dir(expe_dir, exec_name)?, lines)?;
+    ExecRec::from_cov_path(&cov_path)
+}
+
+/// Constraint on `cond_expr` without slice and macros.
+pub fn ub_cons<P: AsRef<Path>>(
+    cond_expr: &str,
+    res: bool,
+    fpath: P,
+    range: Range,
+    func_sig: &str,
+) -> Result<UBConstraint> {
+    let cons = serde_json::from_value(json!({
+        "cond_expr": cond_expr,
+        "res": res,
+        "fpath": fpath.as_ref(),
+        "range": range,
+        "func_sig": func_sig,
+        "slice": "",
+        "macro_mapping": {},
+    }))?;
+    Ok(cons)
+}
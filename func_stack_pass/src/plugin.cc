@@ -236,6 +236,22 @@ FunctionCallee get_thread_rec_func_decl(Module &M) {
   return thread_guard_func_cl;
 }
 
+FunctionCallee get_br_operands_func_decl(Module &M) {
+  LLVMContext &ctx = M.getContext();
+
+  Type *void_ty = Type::getVoidTy(ctx);
+  Type *i8_ty = Type::getInt8Ty(ctx);
+  Type *i8_ptr_ty = PointerType::getUnqual(i8_ty);
+  Type *i64_ty = Type::getInt64Ty(ctx);
+  FunctionType *br_operands_func_ty =
+      FunctionType::get(void_ty,
+                        {i8_ptr_ty, i8_ptr_ty, i8_ty, i8_ty, i64_ty, i64_ty},
+                        false);
+  FunctionCallee br_operands_func_cl =
+      M.getOrInsertFunction("br_operands_rec", br_operands_func_ty);
+  return br_operands_func_cl;
+}
+
 /**
   Br Instruction operations
 */
@@ -354,6 +370,78 @@ br_rec:
   irb.CreateCall(rec_log_func_cl, {rec_str_ptr});
 }
 
+// convert a compare operand to its 64-bit representation, null if unsupported
+Value *get_operand_bits(IRBuilderBase &irb, Value *val, bool is_signed) {
+  Type *ty = val->getType();
+  Type *i64_ty = irb.getInt64Ty();
+  if (ty->isIntegerTy()) {
+    if (ty->getIntegerBitWidth() > 64) {
+      return nullptr;
+    }
+    return is_signed ? irb.CreateSExt(val, i64_ty) : irb.CreateZExt(val, i64_ty);
+  }
+  if (ty->isPointerTy()) {
+    return irb.CreatePtrToInt(val, i64_ty);
+  }
+  if (ty->isFloatingPointTy()) {
+    if (ty->getPrimitiveSizeInBits() > 64) {
+      return nullptr;
+    }
+    Value *dbl_val = irb.CreateFPExt(val, irb.getDoubleTy());
+    return irb.CreateBitCast(dbl_val, i64_ty);
+  }
+  // vectors and others
+  return nullptr;
+}
+
+// record the operand values of the compare feeding the branch
+void instr_br_operands(Module &M, BranchInst *br_inst) {
+  Value *cond = br_inst->getCondition();
+  CmpInst *cmp_inst = dyn_cast<CmpInst>(cond);
+  if (!cmp_inst) {
+    return;
+  }
+
+  Value *lhs = cmp_inst->getOperand(0);
+  char kind;
+  bool is_signed = false;
+  if (isa<FCmpInst>(cmp_inst)) {
+    kind = 'f';
+  } else if (lhs->getType()->isPointerTy()) {
+    kind = 'p';
+  } else if (cmp_inst->isSigned() || cmp_inst->isEquality()) {
+    // eq and ne have no signedness, their values are sign-extended and read
+    // back as unsigned ones with the bit width if needed
+    kind = 's';
+    is_signed = true;
+  } else {
+    kind = 'u';
+  }
+  // 0 for non-integer operands
+  unsigned width =
+      lhs->getType()->isIntegerTy() ? lhs->getType()->getIntegerBitWidth() : 0;
+
+  InstrumentationIRBuilder irb(br_inst);
+  Value *lhs_bits = get_operand_bits(irb, lhs, is_signed);
+  Value *rhs_bits = get_operand_bits(irb, cmp_inst->getOperand(1), is_signed);
+  if (!lhs_bits || !rhs_bits) {
+    return;
+  }
+
+  std::string src_path = get_src_path(M);
+  std::stringstream ss;
+  ss << get_src_loc_with_path(cmp_inst, src_path);
+  std::string val_loc = ss.str();
+
+  FunctionCallee br_operands_func_cl = get_br_operands_func_decl(M);
+  auto val_loc_ptr = irb.CreateGlobalStringPtr(val_loc);
+  auto pred_ptr = irb.CreateGlobalStringPtr(
+      CmpInst::getPredicateName(cmp_inst->getPredicate()));
+  irb.CreateCall(br_operands_func_cl,
+                 {val_loc_ptr, pred_ptr, irb.getInt8(kind), irb.getInt8(width),
+                  lhs_bits, rhs_bits});
+}
+
 // void output_cond_instruction(BranchInst *br_inst, Module &M) {
 //   Value *cond = br_inst->getCondition();
 //   assert(cond && "Branch instruction has no condition");
@@ -381,6 +469,7 @@ bool instr_br_inst(Instruction *term, Module &M) {
       // locate a conditional br instruction
 
       const char *prmpt = is_merge_br(br_inst) ? "Merge Br Guard" : "Br Guard";
+      instr_br_operands(M, br_inst);
 
       BasicBlock *true_dest = br_inst->getSuccessor(0);
       BasicBlock *false_dest = br_inst->getSuccessor(1);
@@ -0,0 +1,248 @@
+#include "runtime/bin_trace.h"
+#include "config.h"
+#include <cstdlib>
+
+namespace {
+
+void write_u8(std::ostream &out, uint8_t val) {
+  out.put(static_cast<char>(val));
+}
+
+void write_u32(std::ostream &out, uint32_t val) {
+  for (int i = 0; i < 4; i++) {
+    out.put(static_cast<char>((val >> (8 * i)) & 0xff));
+  }
+}
+
+void write_u64(std::ostream &out, uint64_t val) {
+  for (int i = 0; i < 8; i++) {
+    out.put(static_cast<char>((val >> (8 * i)) & 0xff));
+  }
+}
+
+void write_event(std::ostream &out, const BinEvent &ev) {
+  write_u8(out, ev.kind);
+  write_u8(out, ev.flag);
+  write_u32(out, ev.a);
+  write_u32(out, ev.b);
+  write_u32(out, ev.c);
+  write_u64(out, ev.num);
+}
+
+void write_raw(std::ostream &out, std::string_view text) {
+  write_u8(out, KIND_RAW);
+  write_u32(out, text.size());
+  out.write(text.data(), text.size());
+}
+
+// get id of the string, define it first if unseen
+uint32_t intern(std::ostream &out, BinTraceCtx &ctx, std::string_view str) {
+  if (str.empty()) {
+    return 0;
+  }
+  auto it = ctx.str_ids.find(str);
+  if (it != ctx.str_ids.end()) {
+    return it->second;
+  }
+  uint32_t id = ctx.strs.size() + 1;
+  const std::string &key = ctx.strs.emplace_back(str);
+  ctx.str_ids.emplace(key, id);
+
+  write_u8(out, KIND_STR_DEF);
+  write_u32(out, id);
+  write_u32(out, str.size());
+  out.write(str.data(), str.size());
+  return id;
+}
+
+bool consume_prefix(std::string_view &sv, std::string_view prefix) {
+  if (sv.substr(0, prefix.size()) != prefix) {
+    return false;
+  }
+  sv.remove_prefix(prefix.size());
+  return true;
+}
+
+void trim_front(std::string_view &sv) {
+  while (!sv.empty() && sv.front() == ' ') {
+    sv.remove_prefix(1);
+  }
+}
+
+std::string_view next_token(std::string_view &sv) {
+  trim_front(sv);
+  auto pos = sv.find(' ');
+  std::string_view tok = sv.substr(0, pos);
+  sv.remove_prefix(pos == std::string_view::npos ? sv.size() : pos);
+  return tok;
+}
+
+bool parse_cond(std::string_view tok, uint8_t &flag) {
+  if (tok == "1") {
+    flag = 1;
+  } else if (tok == "0") {
+    flag = 0;
+  } else {
+    return false;
+  }
+  return true;
+}
+
+// "<from> <0|1> <dest>"
+bool parse_jump(std::ostream &out, BinTraceCtx &ctx, std::string_view sv,
+                BinEvent &ev) {
+  std::string_view from = next_token(sv);
+  std::string_view cond = next_token(sv);
+  std::string_view dest = next_token(sv);
+  if (from.empty() || dest.empty() || !parse_cond(cond, ev.flag)) {
+    return false;
+  }
+  ev.b = intern(out, ctx, from);
+  ev.c = intern(out, ctx, dest);
+  return true;
+}
+
+// guards formatted by the pass, the other events are written directly
+bool encode_line(std::ostream &out, BinTraceCtx &ctx, std::string_view line,
+                 BinEvent &ev) {
+  std::string_view sv = line;
+  if (consume_prefix(sv, "Br Guard: ")) {
+    ev.kind = KIND_BR_GUARD;
+    std::string_view val_loc = next_token(sv);
+    ev.a = intern(out, ctx, val_loc);
+    return !val_loc.empty() && parse_jump(out, ctx, sv, ev);
+  }
+  if (consume_prefix(sv, "Merge Br Guard: ")) {
+    ev.kind = KIND_MERGE_BR_GUARD;
+    return parse_jump(out, ctx, sv, ev);
+  }
+  if (consume_prefix(sv, "Switch Guard: ")) {
+    ev.kind = KIND_SWITCH_GUARD;
+    return parse_jump(out, ctx, sv, ev);
+  }
+  if (consume_prefix(sv, "IndirectBr Guard: ")) {
+    ev.kind = KIND_INDIRECT_GUARD;
+    return parse_jump(out, ctx, sv, ev);
+  }
+  if (consume_prefix(sv, "Unconditional Branch Value: ")) {
+    ev.kind = KIND_VALUE;
+    std::string_view val_loc = next_token(sv);
+    ev.a = intern(out, ctx, val_loc);
+    return !val_loc.empty();
+  }
+  return false;
+}
+
+// write out the text left without a newline before a direct event, a function
+// invocation prefix is taken as the location of an entry if `take_invoc` is set
+uint32_t flush_line_buf(std::ostream &out, BinTraceCtx &ctx, bool take_invoc) {
+  if (ctx.line_buf.empty()) {
+    return 0;
+  }
+  uint32_t invoc_id = 0;
+  std::string_view sv = ctx.line_buf;
+  if (take_invoc && consume_prefix(sv, "Function Invocation: ")) {
+    std::string_view loc = next_token(sv);
+    trim_front(sv);
+    if (!loc.empty() && sv.empty()) {
+      invoc_id = intern(out, ctx, loc);
+    }
+  }
+  if (invoc_id == 0) {
+    write_raw(out, ctx.line_buf);
+  }
+  ctx.line_buf.clear();
+  return invoc_id;
+}
+
+} // namespace
+
+bool is_bin_format() {
+  static const bool flag = [] {
+    const char *fmt = std::getenv(FORMAT_ENV_VAR);
+    return fmt && std::string_view(fmt) == "bin";
+  }();
+  return flag;
+}
+
+void write_bin_header(std::ostream &out) {
+  out.write(BIN_TRACE_MAGIC, sizeof(BIN_TRACE_MAGIC));
+  write_u32(out, BIN_TRACE_VERSION);
+}
+
+void write_bin_content(std::ostream &out, BinTraceCtx &ctx,
+                       std::string_view content) {
+  ctx.line_buf.append(content);
+
+  std::size_t start = 0;
+  std::size_t pos;
+  while ((pos = ctx.line_buf.find('\n', start)) != std::string::npos) {
+    std::string_view line(ctx.line_buf.data() + start, pos - start);
+    start = pos + 1;
+    if (line.empty()) {
+      continue;
+    }
+
+    BinEvent ev;
+    if (encode_line(out, ctx, line, ev)) {
+      write_event(out, ev);
+    } else {
+      // keep the text so that the reader can still try to parse it
+      write_raw(out, line);
+    }
+  }
+  ctx.line_buf.erase(0, start);
+}
+
+void write_bin_operands(std::ostream &out, BinTraceCtx &ctx,
+                        std::string_view val_loc, std::string_view pred,
+                        char kind, uint8_t width, uint64_t lhs, uint64_t rhs) {
+  flush_line_buf(out, ctx, false);
+  BinEvent ev;
+  ev.kind = KIND_BR_OPERANDS;
+  ev.flag = static_cast<uint8_t>(kind);
+  ev.a = intern(out, ctx, val_loc);
+  ev.b = intern(out, ctx, pred);
+  ev.c = width;
+  ev.num = lhs;
+  write_event(out, ev);
+  write_u64(out, rhs);
+}
+
+void write_bin_func(std::ostream &out, BinTraceCtx &ctx, BinKind kind,
+                    std::string_view func_name) {
+  BinEvent ev;
+  ev.kind = kind;
+  ev.b = flush_line_buf(out, ctx, kind == KIND_ENTER);
+  ev.a = intern(out, ctx, func_name);
+  write_event(out, ev);
+}
+
+void write_bin_loop(std::ostream &out, BinTraceCtx &ctx, BinKind kind,
+                    std::string_view header_loc, std::string_view out_loc,
+                    uint64_t count) {
+  flush_line_buf(out, ctx, false);
+  BinEvent ev;
+  ev.kind = kind;
+  ev.a = intern(out, ctx, header_loc);
+  ev.b = intern(out, ctx, out_loc);
+  ev.num = count;
+  write_event(out, ev);
+}
+
+void write_bin_event(std::ostream &out, BinTraceCtx &ctx, BinKind kind) {
+  flush_line_buf(out, ctx, false);
+  BinEvent ev;
+  ev.kind = kind;
+  write_event(out, ev);
+}
+
+void write_bin_thread(std::ostream &out, BinTraceCtx &ctx, std::string_view loc,
+                      uint64_t tid) {
+  flush_line_buf(out, ctx, false);
+  BinEvent ev;
+  ev.kind = KIND_THREAD;
+  ev.a = intern(out, ctx, loc);
+  ev.num = tid;
+  write_event(out, ev);
+}
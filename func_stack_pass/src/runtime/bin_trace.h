@@ -0,0 +1,107 @@
+#ifndef _BIN_TRACE_H
+#define _BIN_TRACE_H
+
+#include <cstdint>
+#include <deque>
+#include <ostream>
+#include <string>
+#include <string_view>
+#include <unordered_map>
+
+/**
+Binary Trace Format
+
+File := Header Record*
+Header := "FSTB" u32(version)
+Record := StrDef | Event | Operands | Raw
+StrDef := u8(KIND_STR_DEF) u32(id) u32(len) bytes[len]
+Event := u8(kind) u8(flag) u32(a) u32(b) u32(c) u64(num)
+Operands := Event(kind = KIND_BR_OPERANDS) u64(rhs)
+Raw := u8(KIND_RAW) u32(len) bytes[len]
+
+All integers are little-endian. String id 0 stands for no string. Locations
+and function names are interned per thread file, each string is defined by a
+StrDef record before its first use. Raw records keep the text the encoder
+failed to recognize inline, so that they never grow the string table.
+*/
+
+constexpr char BIN_TRACE_MAGIC[4] = {'F', 'S', 'T', 'B'};
+constexpr uint32_t BIN_TRACE_VERSION = 2;
+
+enum BinKind : uint8_t {
+  KIND_STR_DEF = 0x01,
+  // function events: a = function name
+  KIND_ENTER = 0x10, // b = invocation location
+  KIND_RETURN = 0x11,
+  KIND_UNWIND = 0x12,
+  // jump events: b = from location, c = dest location, flag = cond value
+  KIND_BR_GUARD = 0x20, // a = value location
+  KIND_MERGE_BR_GUARD = 0x21,
+  KIND_SWITCH_GUARD = 0x22,
+  KIND_INDIRECT_GUARD = 0x23,
+  KIND_VALUE = 0x24, // a = value location
+  // a = value location, b = predicate, c = integer bit width or 0,
+  // flag = operand kind, num = lhs
+  KIND_BR_OPERANDS = 0x25,
+  // loop events: a = header location, b = out location, num = count
+  KIND_LOOP_HIT = 0x30,
+  KIND_LOOP_EXCEED = 0x31,
+  KIND_LOOP_OUT = 0x32,
+  KIND_LOOP_NO_START = 0x33,
+  KIND_RECUR_LOCKED = 0x40,
+  KIND_RECUR_RELEASED = 0x41,
+  // a = creation location, num = thread id
+  KIND_THREAD = 0x50,
+  // text not recognized by the encoder, stored inline
+  KIND_RAW = 0x60,
+};
+
+struct BinEvent {
+  uint8_t kind = KIND_RAW;
+  uint8_t flag = 0;
+  uint32_t a = 0;
+  uint32_t b = 0;
+  uint32_t c = 0;
+  uint64_t num = 0;
+};
+
+/// per-thread encoding state
+struct BinTraceCtx {
+  /// text not terminated by a newline yet, e.g. the function invocation part
+  std::string line_buf;
+  /// interned strings, a deque keeps them in place for the views below
+  std::deque<std::string> strs;
+  std::unordered_map<std::string_view, uint32_t> str_ids;
+};
+
+bool is_bin_format();
+
+void write_bin_header(std::ostream &out);
+
+/// encode the guards formatted by the pass in `content` into binary records
+void write_bin_content(std::ostream &out, BinTraceCtx &ctx,
+                       std::string_view content);
+
+/// write a function event, an entry takes the invocation location of the
+/// pending "Function Invocation: <loc> " content
+void write_bin_func(std::ostream &out, BinTraceCtx &ctx, BinKind kind,
+                    std::string_view func_name);
+
+/// write a loop event, `out_loc` is empty for loop entries
+void write_bin_loop(std::ostream &out, BinTraceCtx &ctx, BinKind kind,
+                    std::string_view header_loc, std::string_view out_loc,
+                    uint64_t count);
+
+/// write an event without arguments, i.e. the recursion lock events
+void write_bin_event(std::ostream &out, BinTraceCtx &ctx, BinKind kind);
+
+void write_bin_thread(std::ostream &out, BinTraceCtx &ctx, std::string_view loc,
+                      uint64_t tid);
+
+/// write the operand values of a branch compare, `lhs` and `rhs` are the
+/// 64-bit representations of the values
+void write_bin_operands(std::ostream &out, BinTraceCtx &ctx,
+                        std::string_view val_loc, std::string_view pred,
+                        char kind, uint8_t width, uint64_t lhs, uint64_t rhs);
+
+#endif
@@ -1,6 +1,7 @@
 
 #include "runtime/func_stack.h"
 #include "config.h"
+#include "runtime/bin_trace.h"
 #include "utils.h"
 #include <algorithm>
 #include <cassert>
@@ -8,6 +9,7 @@
 #include <cstddef>
 #include <cstdio>
 #include <cstdlib>
+#include <cstring>
 #include <cxxabi.h>
 #include <filesystem>
 #include <fstream>
@@ -27,7 +29,9 @@
 
 namespace fs = std::filesystem;
 
-void print_func_rec_to_file(const char *prmp, const char *func_name);
+void print_func_rec_to_file(BinKind kind, const char *func_name);
+void print_recur_rec_to_file(BinKind kind);
+bool is_out_guarded();
 using Tid = std::thread::id;
 
 static std::unordered_map<Tid, std::ofstream> of_map;
@@ -88,11 +92,14 @@ std::ofstream &create_of(const Tid &tid) {
   fs::path fname(fname_str);
   fs::path fpath = out_dir / fname;
 
-  std::ofstream out(fpath);
+  std::ofstream out(fpath, std::ios::out | std::ios::binary);
   if (!out.is_open()) {
     std::cerr << "Failed to open file: " << fpath << "\n";
     std::exit(1);
   }
+  if (is_bin_format()) {
+    write_bin_header(out);
+  }
 
   of_map[tid] = std::move(out);
 
@@ -112,6 +119,26 @@ std::ofstream &get_of() {
   }
   return create_of(tid);
 }
+
+/**
+  Binary Trace Context
+*/
+
+std::unordered_map<Tid, BinTraceCtx> bin_ctx_map;
+std::mutex bin_ctx_mutex;
+
+// the map is only locked at the first use in each thread
+BinTraceCtx &get_bin_ctx() {
+  thread_local BinTraceCtx *ctx = nullptr;
+  if (ctx) {
+    return *ctx;
+  }
+  Tid tid = std::this_thread::get_id();
+
+  std::lock_guard<std::mutex> lock(bin_ctx_mutex);
+  ctx = &bin_ctx_map[tid];
+  return *ctx;
+}
 /**
   Loop Context Implementation
 */
@@ -195,7 +222,7 @@ struct RecurLock {
       // already locked which means in nested recursion -> do not update
       return false;
     }
-    print_rec_to_file_with_guard("Recur Lock locked");
+    print_recur_rec_to_file(KIND_RECUR_LOCKED);
     // update
     value = true;
     frame = RecurFrame(func_name, idx);
@@ -214,7 +241,7 @@ struct RecurLock {
   void release() {
     value = false;
     frame.reset(); // reset the frame
-    print_rec_to_file_with_guard("Recur Lock released");
+    print_recur_rec_to_file(KIND_RECUR_RELEASED);
   }
 
   // invoked before pop
@@ -263,12 +290,41 @@ RecurLock &get_recur_lock() {
 //   }
 // }
 
-void print_func_rec_to_file(const char *prmp, const char *func_name) {
+const char *get_func_prompt(BinKind kind) {
+  switch (kind) {
+  case KIND_ENTER:
+    return "enter";
+  case KIND_RETURN:
+    return "return from";
+  default:
+    return "unwind from";
+  }
+}
+
+void print_func_rec_to_file(BinKind kind, const char *func_name) {
+  if (is_out_guarded()) {
+    return;
+  }
   std::string deman = demangle(func_name);
-  std::stringstream ss;
-  ss << prmp << " " << deman;
-  std::string rec = ss.str();
-  print_rec_to_file_with_guard(rec.c_str());
+  std::ofstream &out = get_of();
+  if (is_bin_format()) {
+    write_bin_func(out, get_bin_ctx(), kind, deman);
+    return;
+  }
+  out << get_func_prompt(kind) << " " << deman << "\n";
+}
+
+void print_recur_rec_to_file(BinKind kind) {
+  if (is_out_guarded()) {
+    return;
+  }
+  std::ofstream &out = get_of();
+  if (is_bin_format()) {
+    write_bin_event(out, get_bin_ctx(), kind);
+    return;
+  }
+  out << "Recur Lock "
+      << (kind == KIND_RECUR_LOCKED ? "locked" : "released") << "\n";
 }
 
 /**
@@ -287,9 +343,9 @@ bool recur_release(const char *func_name, const FuncStack &func_stack) {
 }
 
 void pop_func_impl(const char *func_name, FuncStack &func_stack,
-                   const char *prompt) {
+                   BinKind kind) {
   recur_release(func_name, func_stack);
-  print_func_rec_to_file(prompt, func_name);
+  print_func_rec_to_file(kind, func_name);
   func_stack.pop_back();
 }
 
@@ -300,18 +356,18 @@ void pop_func(const char *func_name) {
 
   if (func_name == func_stack.back()) {
     // if the function name matches the top of the stack, pop it
-    pop_func_impl(func_name, func_stack, "return from");
+    pop_func_impl(func_name, func_stack, KIND_RETURN);
   } else {
     while (func_name != func_stack.back()) {
-      pop_func_impl(func_stack.back().c_str(), func_stack, "unwind from");
+      pop_func_impl(func_stack.back().c_str(), func_stack, KIND_UNWIND);
     }
-    pop_func_impl(func_name, func_stack, "return from");
+    pop_func_impl(func_name, func_stack, KIND_RETURN);
   }
 }
 
 void push_func(const char *func_name) {
   // output -> try_lock -> push to stack
-  print_func_rec_to_file("enter", func_name);
+  print_func_rec_to_file(KIND_ENTER, func_name);
   FuncStack &func_stack = get_func_stack();
   recur_lock(func_name, func_stack);
   func_stack.push_back(func_name);
@@ -336,50 +392,71 @@ bool is_recur_locked() {
   return recur_lock.is_locked();
 }
 
+bool is_out_guarded() { return exceed_loop_limit() || is_recur_locked(); }
+
 /**
 Output with No Guard Version
 */
 void print_content_to_file(const char *content) {
   std::ofstream &out = get_of();
+  if (is_bin_format()) {
+    write_bin_content(out, get_bin_ctx(), content);
+    return;
+  }
   out << content;
 }
 
-void print_rec_to_file_with_recur_guard(const char *rec) {
+// loop records are only guarded by the recursion lock
+void print_loop_rec_to_file(BinKind kind, const char *header_loc,
+                            const char *out_loc, std::size_t count) {
   if (is_recur_locked()) {
-    // if recursion is locked, do not print
     return;
   }
-  std::stringstream ss;
-  ss << rec << "\n";
-  print_content_to_file(ss.str().c_str());
+  std::ofstream &out = get_of();
+  if (is_bin_format()) {
+    write_bin_loop(out, get_bin_ctx(), kind, header_loc, out_loc, count);
+    return;
+  }
+  switch (kind) {
+  case KIND_LOOP_HIT:
+    out << "Loop Hit: " << header_loc << " at count " << count;
+    break;
+  case KIND_LOOP_EXCEED:
+    out << "Loop Limit Exceed: " << header_loc << " at count " << count;
+    break;
+  case KIND_LOOP_OUT:
+    out << "Out of Loop: " << header_loc << " " << out_loc << " at count "
+        << count;
+    break;
+  default:
+    out << "Loop end without loop start: " << header_loc << " " << out_loc;
+  }
+  out << "\n";
 }
 
 /**
 Output with Guard Version
 */
 void print_content_to_file_with_guard(const char *content) {
-  if (exceed_loop_limit()) {
-    return;
-  }
-  if (is_recur_locked()) {
+  if (is_out_guarded()) {
     return;
   }
   print_content_to_file(content);
 }
 
 void print_rec_to_file_with_guard(const char *rec) {
-  std::stringstream ss;
-  ss << rec << "\n";
-  print_content_to_file_with_guard(ss.str().c_str());
+  if (is_out_guarded()) {
+    return;
+  }
+  print_content_to_file(rec);
+  print_content_to_file("\n");
 }
 
 void push_new_entry_to_loop_stack(const char *loop_loc, LoopStack &loop_stack) {
   LoopEntry lent{loop_loc, 1};
   loop_stack.push(lent);
 
-  std::stringstream ss;
-  ss << "Loop Hit: " << loop_loc << " at count " << 1;
-  print_rec_to_file_with_recur_guard(ss.str().c_str());
+  print_loop_rec_to_file(KIND_LOOP_HIT, loop_loc, "", 1);
 }
 
 /**
@@ -402,15 +479,10 @@ void loop_entry(const char *loop_loc) {
     auto cnt = cur.second;
     if (cnt <= LOOP_LIMIT) {
       // Repeated hit for current loop entry
-      std::stringstream ss;
-      ss << "Loop Hit: " << loop_loc << " at count " << cnt;
-      //
-      print_rec_to_file_with_recur_guard(ss.str().c_str());
+      print_loop_rec_to_file(KIND_LOOP_HIT, loop_loc, "", cnt);
     } else if (cnt - LOOP_LIMIT == 1) {
       // Loop Entry Exceed
-      std::stringstream ss;
-      ss << "Loop Limit Exceed: " << loop_loc << " at count " << cnt;
-      print_rec_to_file_with_recur_guard(ss.str().c_str());
+      print_loop_rec_to_file(KIND_LOOP_EXCEED, loop_loc, "", cnt);
     }
   } else {
     // hit at nested loop
@@ -430,20 +502,15 @@ void loop_end(const char *header_loc, const char *out_loc) {
   LoopStack &loop_stack = get_loop_stack();
   if (loop_stack.empty()) {
     // if the stack is empty, this is an error
-    std::stringstream ss;
-    ss << "Loop end without loop start: " << header_loc << " " << out_loc;
-    print_rec_to_file_with_recur_guard(ss.str().c_str());
+    print_loop_rec_to_file(KIND_LOOP_NO_START, header_loc, out_loc, 0);
     return;
   }
   // consider reasonable to be hit without passing loop entry
   auto &cur = loop_stack.top();
   if (cur.first == header_loc) {
-    loop_stack.pop();
     // Loop End Out
-    std::stringstream ss;
-    ss << "Out of Loop: " << header_loc << " " << out_loc << " at count "
-       << cur.second;
-    print_rec_to_file_with_recur_guard(ss.str().c_str());
+    print_loop_rec_to_file(KIND_LOOP_OUT, header_loc, out_loc, cur.second);
+    loop_stack.pop();
   } else {
     // this is an error, loop end without loop start
     // Loop End Without Start
@@ -454,19 +521,61 @@ void loop_end(const char *header_loc, const char *out_loc) {
       return;
     }
 
-    std::stringstream ss;
-    ss << "Loop end without loop start: " << header_loc << " " << out_loc;
-    print_rec_to_file_with_recur_guard(ss.str().c_str());
+    print_loop_rec_to_file(KIND_LOOP_NO_START, header_loc, out_loc, 0);
   }
 }
 
 // thread creation instrumentation
 void thread_rec(const char *loc, void *tid_ptr) {
   pthread_t tid = *(pthread_t *)tid_ptr;
-  std::stringstream ss;
-  ss << "Thread Creation: " << loc << " " << tid << "\n";
   // regardless of guards
-  print_content_to_file(ss.str().c_str());
+  std::ofstream &out = get_of();
+  if (is_bin_format()) {
+    write_bin_thread(out, get_bin_ctx(), loc, static_cast<uint64_t>(tid));
+    return;
+  }
+  out << "Thread Creation: " << loc << " " << tid << "\n";
+}
+
+/**
+  record "Br Operands: <val_loc> <pred>", values are passed in their 64-bit
+  representations and printed according to `kind`, `width` is the bit width of
+  integer operands and printed right after the kind, e.g. `s32`
+*/
+void br_operands_rec(const char *val_loc, const char *pred, char kind,
+                     uint8_t width, uint64_t lhs, uint64_t rhs) {
+  if (is_out_guarded()) {
+    return;
+  }
+  std::ofstream &out = get_of();
+  if (is_bin_format()) {
+    write_bin_operands(out, get_bin_ctx(), val_loc, pred, kind, width, lhs,
+                       rhs);
+    return;
+  }
+
+  out << "Br Operands: " << val_loc << " " << pred << " " << kind;
+  if (width != 0) {
+    out << static_cast<unsigned>(width);
+  }
+  out << " ";
+  switch (kind) {
+  case 's':
+    out << static_cast<int64_t>(lhs) << " " << static_cast<int64_t>(rhs);
+    break;
+  case 'f': {
+    double lhs_val, rhs_val;
+    std::memcpy(&lhs_val, &lhs, sizeof(double));
+    std::memcpy(&rhs_val, &rhs, sizeof(double));
+    auto prec = out.precision(17);
+    out << lhs_val << " " << rhs_val;
+    out.precision(prec);
+    break;
+  }
+  default:
+    out << lhs << " " << rhs;
+  }
+  out << "\n";
 }
 
 // static std::unordered_map<std::size_t, unsigned int> loop_counter;
@@ -1,6 +1,8 @@
 #ifndef _FUNC_STACK_H
 #define _FUNC_STACK_H
 
+#include <stdint.h>
+
 #ifdef __cplusplus
 extern "C" {
 #endif
@@ -17,6 +19,9 @@ void loop_end(const char *loop_loc, const char *out_loc);
 
 void thread_rec(const char *loc, void *tid_ptr);
 
+void br_operands_rec(const char *val_loc, const char *pred, char kind,
+                     uint8_t width, uint64_t lhs, uint64_t rhs);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,32 @@
+// Encode a fixed sequence of events with the runtime encoder, the output is
+// checked by the reader of constraint_fuzz in
+// `analysis/constraint/inter/exec_tree/binary.rs`.
+#include "runtime/bin_trace.h"
+#include <fstream>
+#include <iostream>
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    std::cerr << "usage: " << argv[0] << " <out_path>\n";
+    return 1;
+  }
+  std::ofstream out(argv[1], std::ios::out | std::ios::binary);
+  BinTraceCtx ctx;
+
+  write_bin_header(out);
+  write_bin_content(out, ctx, "Function Invocation: /src/a.c:3:5 ");
+  write_bin_func(out, ctx, KIND_ENTER, "foo(int)");
+  write_bin_operands(out, ctx, "/src/a.c:10:7", "slt", 's', 32,
+                     static_cast<uint64_t>(-1), 4);
+  write_bin_content(out, ctx, "Br Guard: /src/a.c:10:7 /src/a.c:10:3 1 ");
+  write_bin_content(out, ctx, "/src/a.c:11:3\n");
+  write_bin_loop(out, ctx, KIND_LOOP_HIT, "/src/a.c:12:3", "", 1);
+  write_bin_loop(out, ctx, KIND_LOOP_OUT, "/src/a.c:12:3", "/src/a.c:15:1", 2);
+  write_bin_content(out, ctx, "Unknown Guard: /src/a.c:13:1\n");
+  write_bin_event(out, ctx, KIND_RECUR_LOCKED);
+  write_bin_thread(out, ctx, "/src/a.c:16:3", 7);
+  // the location is interned once
+  write_bin_content(out, ctx, "Unconditional Branch Value: /src/a.c:10:7\n");
+  write_bin_func(out, ctx, KIND_RETURN, "foo(int)");
+  return out.good() ? 0 : 1;
+}